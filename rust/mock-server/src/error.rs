@@ -0,0 +1,62 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::{json, Value};
+
+pub use spacedust::apis::api_error::*;
+
+/// An error in the same `{"error": {...}}` envelope the real API sends.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: i32,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    pub fn new(code: i32, message: impl Into<String>) -> ApiError {
+        let status = match code {
+            COOLDOWN_CONFLICT => StatusCode::CONFLICT,
+            401 | TOKEN_EMPTY => StatusCode::UNAUTHORIZED,
+            // The real API's code for a request that doesn't validate
+            422 => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> ApiError {
+        ApiError {
+            status: StatusCode::NOT_FOUND,
+            code: 404,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> ApiError {
+        self.data = Some(data);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut error = json!({
+            "message": self.message,
+            "code": self.code,
+        });
+        if let Some(data) = self.data {
+            error["data"] = data;
+        }
+        (self.status, Json(json!({ "error": error }))).into_response()
+    }
+}
@@ -0,0 +1,136 @@
+//! A small, deterministic SpaceTraders server for playing offline and for
+//! tests.
+//!
+//! It serves the same `/v2` paths as the real API, so pointing spacedust's
+//! `Configuration::base_path` at it is all a client needs to do. The universe
+//! is one system (plus a neighbour behind an unfinished jump gate) with
+//! markets, a shipyard, asteroids to mine and a gas giant to siphon. Ships
+//! burn fuel, take real time to fly and cool down between extractions.
+//!
+//! `POST /mock/reset` starts a new reset, like the real server's fortnightly
+//! wipes, optionally with `?date=YYYY-MM-DD` for the new reset date.
+//!
+//! ```no_run
+//! # async fn run() {
+//! let addr = "127.0.0.1:3002".parse().unwrap();
+//! spacetraders_mock::serve(addr).await;
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::middleware;
+use axum::routing::{get, post};
+use axum::Router;
+use parking_lot::Mutex;
+
+mod error;
+mod routes;
+pub mod templates;
+pub mod universe;
+
+pub use error::ApiError;
+pub use routes::UniverseShared;
+use universe::Universe;
+
+/// The token the default universe's agent authenticates with.
+pub fn default_token() -> String {
+    Universe::default().token
+}
+
+/// Routes for `universe`, rooted at `/v2` like the real API.
+pub fn router(universe: UniverseShared) -> Router {
+    use routes::*;
+
+    let my = Router::new()
+        .route("/agent", get(my_agent))
+        .route("/contracts", get(contracts))
+        .route("/contracts/:contract_id", get(contract))
+        .route("/contracts/:contract_id/accept", post(accept_contract))
+        .route("/contracts/:contract_id/deliver", post(deliver_contract))
+        .route("/contracts/:contract_id/fulfill", post(fulfill_contract))
+        .route("/ships", get(ships).post(purchase_ship))
+        .route("/ships/:ship_symbol", get(ship))
+        .route("/ships/:ship_symbol/cargo", get(ship_cargo))
+        .route("/ships/:ship_symbol/cooldown", get(ship_cooldown))
+        .route(
+            "/ships/:ship_symbol/nav",
+            get(ship_nav).patch(patch_ship_nav),
+        )
+        .route("/ships/:ship_symbol/orbit", post(orbit))
+        .route("/ships/:ship_symbol/dock", post(dock))
+        .route("/ships/:ship_symbol/navigate", post(navigate))
+        .route("/ships/:ship_symbol/refuel", post(refuel))
+        .route("/ships/:ship_symbol/sell", post(sell))
+        .route("/ships/:ship_symbol/purchase", post(purchase))
+        .route("/ships/:ship_symbol/jettison", post(jettison))
+        .route("/ships/:ship_symbol/transfer", post(transfer))
+        .route("/ships/:ship_symbol/survey", post(survey))
+        .route("/ships/:ship_symbol/extract", post(extract))
+        .route(
+            "/ships/:ship_symbol/extract/survey",
+            post(extract_with_survey),
+        )
+        .route("/ships/:ship_symbol/siphon", post(siphon))
+        .route("/ships/:ship_symbol/refine", post(refine))
+        .route(
+            "/ships/:ship_symbol/negotiate/contract",
+            post(negotiate_contract),
+        )
+        .route("/ships/:ship_symbol/mounts", get(ship_mounts))
+        .route("/ships/:ship_symbol/mounts/install", post(unsupported))
+        .route("/ships/:ship_symbol/mounts/remove", post(unsupported))
+        .route("/ships/:ship_symbol/chart", post(unsupported))
+        .route("/ships/:ship_symbol/jump", post(unsupported))
+        .route("/ships/:ship_symbol/warp", post(unsupported))
+        .route("/ships/:ship_symbol/scan/systems", post(unsupported))
+        .route("/ships/:ship_symbol/scan/waypoints", post(unsupported))
+        .route("/ships/:ship_symbol/scan/ships", post(unsupported))
+        .route_layer(middleware::from_fn_with_state(
+            universe.clone(),
+            require_token,
+        ));
+
+    let waypoint = "/systems/:system_symbol/waypoints/:waypoint_symbol";
+    let api = Router::new()
+        .route("/", get(status))
+        .route("/register", post(register))
+        .route("/agents", get(agents))
+        .route("/agents/:agent_symbol", get(agent))
+        .route("/factions", get(factions))
+        .route("/factions/:faction_symbol", get(faction))
+        .route("/systems", get(systems))
+        .route("/systems.json", get(systems_json))
+        .route("/systems/:system_symbol", get(system))
+        .route("/systems/:system_symbol/waypoints", get(system_waypoints))
+        .route(waypoint, get(routes::waypoint))
+        .route(&format!("{waypoint}/market"), get(market))
+        .route(&format!("{waypoint}/shipyard"), get(shipyard))
+        .route(&format!("{waypoint}/jump-gate"), get(jump_gate))
+        .route(&format!("{waypoint}/construction"), get(construction))
+        .route(
+            &format!("{waypoint}/construction/supply"),
+            post(supply_construction),
+        )
+        .nest("/my", my);
+
+    Router::new()
+        // spacedust asks for the status at `/v2/`, which `nest` doesn't match
+        .route("/v2/", get(status))
+        .nest("/v2", api)
+        .route("/mock/reset", post(reset))
+        .with_state(universe)
+}
+
+/// A router over a fresh universe.
+pub fn app() -> Router {
+    router(Arc::new(Mutex::new(Universe::default())))
+}
+
+pub async fn serve(addr: SocketAddr) {
+    axum::Server::bind(&addr)
+        .serve(app().into_make_service())
+        .await
+        .unwrap();
+}
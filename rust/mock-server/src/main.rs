@@ -0,0 +1,14 @@
+use std::net::SocketAddr;
+
+/// Set `MOCK_ADDR` to listen somewhere other than 127.0.0.1:3002.
+#[tokio::main]
+async fn main() {
+    let addr: SocketAddr = std::env::var("MOCK_ADDR")
+        .unwrap_or_else(|_| "127.0.0.1:3002".to_string())
+        .parse()
+        .expect("MOCK_ADDR should look like 127.0.0.1:3002");
+
+    println!("Mock SpaceTraders listening on http://{addr}/v2");
+    println!("Token: {}", spacetraders_mock::default_token());
+    spacetraders_mock::serve(addr).await;
+}
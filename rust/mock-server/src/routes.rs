@@ -0,0 +1,554 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use spacedust::models::{
+    DeliverContractRequest, ExtractResourcesRequest, JettisonRequest, NavigateShipRequest,
+    PatchShipNavRequest, PurchaseCargoRequest, PurchaseShipRequest, RefuelShipRequest,
+    RegisterRequest, SellCargoRequest, ShipRefineRequest, SupplyConstructionRequest,
+    TransferCargoRequest,
+};
+
+use crate::error::*;
+use crate::universe::Universe;
+
+pub type UniverseShared = Arc<Mutex<Universe>>;
+
+type Params = Query<Vec<(String, String)>>;
+
+/// Runs `f` against a universe that's caught up to the current time.
+fn with<T, F>(universe: &UniverseShared, f: F) -> ApiResult<T>
+where
+    F: FnOnce(&mut Universe) -> ApiResult<T>,
+{
+    let mut universe = universe.lock();
+    universe.settle();
+    f(&mut universe)
+}
+
+fn data(value: impl Serialize) -> Response {
+    Json(json!({ "data": value })).into_response()
+}
+
+fn created(value: impl Serialize) -> Response {
+    (StatusCode::CREATED, data(value)).into_response()
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Slices `items` the way the real list endpoints do, with the same `meta`.
+fn paginated<T: Serialize>(items: Vec<T>, params: &[(String, String)]) -> ApiResult<Response> {
+    let page = param(params, "page").map_or(Ok(1), str::parse::<usize>);
+    let limit = param(params, "limit").map_or(Ok(10), str::parse::<usize>);
+    let (Ok(page @ 1..), Ok(limit @ 1..=20)) = (page, limit) else {
+        return Err(ApiError::new(
+            422,
+            "page must be at least 1, and limit between 1 and 20.",
+        ));
+    };
+
+    let total = items.len();
+    let items: Vec<T> = items
+        .into_iter()
+        .skip((page - 1) * limit)
+        .take(limit)
+        .collect();
+    Ok(Json(json!({
+        "data": items,
+        "meta": { "total": total, "page": page, "limit": limit },
+    }))
+    .into_response())
+}
+
+/// Turns away `/my` requests that don't carry the agent's token.
+pub async fn require_token<B>(
+    State(universe): State<UniverseShared>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    let token = request
+        .headers()
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    match token {
+        None => Err(ApiError::new(
+            TOKEN_EMPTY,
+            "Missing bearer token. Register an agent to get one.",
+        )),
+        Some(token) if !universe.lock().is_authorized(&token) => Err(ApiError::new(
+            401,
+            "Invalid bearer token. It may be from before the last reset.",
+        )),
+        Some(_) => Ok(next.run(request).await),
+    }
+}
+
+pub async fn unsupported() -> ApiError {
+    ApiError::new(3000, "The mock server doesn't implement this endpoint.")
+}
+
+// Global
+
+pub async fn status(State(universe): State<UniverseShared>) -> Json<Value> {
+    let mut universe = universe.lock();
+    universe.settle();
+    Json(universe.status())
+}
+
+pub async fn register(
+    State(universe): State<UniverseShared>,
+    Json(request): Json<RegisterRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.register(&request.symbol, &request.faction.to_string())
+    })
+    .map(created)
+}
+
+/// Not part of the real API: resets the universe, to see how a client copes.
+/// Takes the new reset date as `?date=`, or moves on a fortnight.
+pub async fn reset(State(universe): State<UniverseShared>, Query(params): Params) -> Json<Value> {
+    let mut universe = universe.lock();
+    let reset_date = param(&params, "date")
+        .map(str::to_string)
+        .unwrap_or_else(|| universe.next_reset_date());
+    universe.reset(&reset_date);
+    Json(universe.status())
+}
+
+// Agents and factions
+
+pub async fn my_agent(State(universe): State<UniverseShared>) -> ApiResult<Response> {
+    with(&universe, |u| Ok(data(&u.agent)))
+}
+
+pub async fn agents(
+    State(universe): State<UniverseShared>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| paginated(vec![u.agent.clone()], &params))
+}
+
+pub async fn agent(
+    State(universe): State<UniverseShared>,
+    Path(agent_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.agent(&agent_symbol).map(data))
+}
+
+pub async fn factions(
+    State(universe): State<UniverseShared>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| paginated(u.factions().to_vec(), &params))
+}
+
+pub async fn faction(
+    State(universe): State<UniverseShared>,
+    Path(faction_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.faction(&faction_symbol).map(data))
+}
+
+// Systems
+
+pub async fn systems(
+    State(universe): State<UniverseShared>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| paginated(u.systems().to_vec(), &params))
+}
+
+pub async fn systems_json(State(universe): State<UniverseShared>) -> ApiResult<Response> {
+    with(&universe, |u| Ok(Json(u.systems()).into_response()))
+}
+
+pub async fn system(
+    State(universe): State<UniverseShared>,
+    Path(system_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.system(&system_symbol).map(data))
+}
+
+/// Supports the same `type` and (repeatable) `traits` filters as the real
+/// endpoint.
+pub async fn system_waypoints(
+    State(universe): State<UniverseShared>,
+    Path(system_symbol): Path<String>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        let waypoints = u.system_waypoints(&system_symbol)?;
+        let r#type = param(&params, "type");
+        let traits: Vec<&str> = params
+            .iter()
+            .filter(|(k, _)| k == "traits")
+            .map(|(_, v)| v.as_str())
+            .collect();
+
+        let waypoints = waypoints
+            .into_iter()
+            .filter(|w| r#type.is_none_or(|t| w.r#type.to_string() == t))
+            .filter(|w| {
+                traits
+                    .iter()
+                    .all(|t| w.traits.iter().any(|wt| wt.symbol.to_string() == *t))
+            })
+            .collect();
+        paginated(waypoints, &params)
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct WaypointParams {
+    #[allow(dead_code)]
+    system_symbol: String,
+    waypoint_symbol: String,
+}
+
+pub async fn waypoint(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.waypoint(&waypoint_symbol).map(data))
+}
+
+pub async fn market(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.market(&waypoint_symbol).map(data))
+}
+
+pub async fn shipyard(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.shipyard(&waypoint_symbol).map(data))
+}
+
+pub async fn jump_gate(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.jump_gate(&waypoint_symbol).map(data))
+}
+
+pub async fn construction(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.construction(&waypoint_symbol).map(data))
+}
+
+pub async fn supply_construction(
+    State(universe): State<UniverseShared>,
+    Path(WaypointParams {
+        waypoint_symbol, ..
+    }): Path<WaypointParams>,
+    Json(request): Json<SupplyConstructionRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.supply_construction(
+            &waypoint_symbol,
+            &request.ship_symbol,
+            &request.trade_symbol,
+            request.units,
+        )
+    })
+    .map(created)
+}
+
+// Contracts
+
+pub async fn contracts(
+    State(universe): State<UniverseShared>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| paginated(u.contracts().to_vec(), &params))
+}
+
+pub async fn contract(
+    State(universe): State<UniverseShared>,
+    Path(contract_id): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.contract(&contract_id).map(data))
+}
+
+pub async fn accept_contract(
+    State(universe): State<UniverseShared>,
+    Path(contract_id): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.accept_contract(&contract_id)).map(data)
+}
+
+pub async fn deliver_contract(
+    State(universe): State<UniverseShared>,
+    Path(contract_id): Path<String>,
+    Json(request): Json<DeliverContractRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.deliver_contract(
+            &contract_id,
+            &request.ship_symbol,
+            &request.trade_symbol,
+            request.units,
+        )
+    })
+    .map(data)
+}
+
+pub async fn fulfill_contract(
+    State(universe): State<UniverseShared>,
+    Path(contract_id): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.fulfill_contract(&contract_id)).map(data)
+}
+
+// Fleet
+
+pub async fn ships(
+    State(universe): State<UniverseShared>,
+    Query(params): Params,
+) -> ApiResult<Response> {
+    with(&universe, |u| paginated(u.ships(), &params))
+}
+
+pub async fn purchase_ship(
+    State(universe): State<UniverseShared>,
+    Json(request): Json<PurchaseShipRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.purchase_ship(request.ship_type, &request.waypoint_symbol)
+    })
+    .map(created)
+}
+
+pub async fn ship(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.ship(&ship_symbol).map(data))
+}
+
+pub async fn ship_cargo(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.cargo(&ship_symbol)).map(data)
+}
+
+pub async fn ship_nav(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.ship(&ship_symbol).map(|s| data(&s.nav)))
+}
+
+pub async fn patch_ship_nav(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<PatchShipNavRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.set_flight_mode(&ship_symbol, request.flight_mode)
+    })
+    .map(data)
+}
+
+/// 204 with no body when the ship is ready to go, like the real thing.
+pub async fn ship_cooldown(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        let ship = u.ship(&ship_symbol)?;
+        Ok(if ship.cooldown.remaining_seconds > 0 {
+            data(&ship.cooldown)
+        } else {
+            StatusCode::NO_CONTENT.into_response()
+        })
+    })
+}
+
+pub async fn orbit(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.orbit(&ship_symbol)).map(data)
+}
+
+pub async fn dock(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.dock(&ship_symbol)).map(data)
+}
+
+pub async fn navigate(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<NavigateShipRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.navigate(&ship_symbol, &request.waypoint_symbol)
+    })
+    .map(data)
+}
+
+pub async fn refuel(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    request: Option<Json<RefuelShipRequest>>,
+) -> ApiResult<Response> {
+    let request = request.map_or_else(RefuelShipRequest::new, |Json(r)| r);
+    with(&universe, |u| {
+        u.refuel(
+            &ship_symbol,
+            request.units,
+            request.from_cargo.unwrap_or(false),
+        )
+    })
+    .map(data)
+}
+
+pub async fn sell(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<SellCargoRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.sell(&ship_symbol, request.symbol, request.units)
+    })
+    .map(created)
+}
+
+pub async fn purchase(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<PurchaseCargoRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.purchase(&ship_symbol, request.symbol, request.units)
+    })
+    .map(created)
+}
+
+pub async fn jettison(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<JettisonRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.jettison(&ship_symbol, request.symbol, request.units)
+    })
+    .map(data)
+}
+
+pub async fn transfer(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<TransferCargoRequest>,
+) -> ApiResult<Response> {
+    with(&universe, |u| {
+        u.transfer(
+            &ship_symbol,
+            request.trade_symbol,
+            request.units,
+            &request.ship_symbol,
+        )
+    })
+    .map(data)
+}
+
+pub async fn survey(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.survey(&ship_symbol)).map(created)
+}
+
+pub async fn extract(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    request: Option<Json<ExtractResourcesRequest>>,
+) -> ApiResult<Response> {
+    // The real API still takes a survey here, but it's deprecated in favour
+    // of `/extract/survey`, so clients shouldn't be getting away with it
+    if request.is_some_and(|Json(r)| r.survey.is_some()) {
+        return Err(ApiError::new(
+            422,
+            "Send surveys to /extract/survey instead.",
+        ));
+    }
+    with(&universe, |u| u.extract(&ship_symbol, None)).map(created)
+}
+
+pub async fn extract_with_survey(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(survey): Json<spacedust::models::Survey>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.extract(&ship_symbol, Some(survey))).map(created)
+}
+
+pub async fn siphon(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.siphon(&ship_symbol)).map(created)
+}
+
+pub async fn refine(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+    Json(request): Json<ShipRefineRequest>,
+) -> ApiResult<Response> {
+    // Every refinable good shares its name with a trade good
+    let produce = serde_json::from_value(json!(request.produce)).map_err(|_| {
+        ApiError::new(
+            422,
+            format!("{:?} isn't something refineries make.", request.produce),
+        )
+    })?;
+    with(&universe, |u| u.refine(&ship_symbol, produce)).map(created)
+}
+
+pub async fn negotiate_contract(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.negotiate_contract(&ship_symbol)).map(created)
+}
+
+pub async fn ship_mounts(
+    State(universe): State<UniverseShared>,
+    Path(ship_symbol): Path<String>,
+) -> ApiResult<Response> {
+    with(&universe, |u| u.ship(&ship_symbol).map(|s| data(&s.mounts)))
+}
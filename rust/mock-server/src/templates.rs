@@ -0,0 +1,681 @@
+//! The fixed universe the mock server starts with.
+//!
+//! Everything is built as JSON in the shape the real API returns and then
+//! decoded into spacedust's models, so a typo here shows up as a panic at
+//! startup rather than as a confusing client-side decode error.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use spacedust::models::{
+    market_trade_good, Construction, Contract, Faction, JumpGate, Market, Ship, ShipType, Shipyard,
+    System, TradeSymbol, Waypoint, WaypointType,
+};
+
+pub const SYSTEM: &str = "X1-MOCK";
+pub const NEIGHBOR_SYSTEM: &str = "X1-MOCK2";
+pub const HEADQUARTERS: &str = "X1-MOCK-A1";
+pub const FACTION: &str = "COSMIC";
+pub const STARTING_CREDITS: i64 = 150_000;
+
+fn decode<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("mock universe template doesn't match the models")
+}
+
+fn waypoint_trait(symbol: &str) -> Value {
+    let name = symbol
+        .split('_')
+        .map(|word| word[..1].to_string() + &word[1..].to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+    json!({
+        "symbol": symbol,
+        "name": name,
+        "description": format!("{name}, as far as the mock server is concerned."),
+    })
+}
+
+struct WaypointSpec {
+    symbol: &'static str,
+    r#type: &'static str,
+    x: i32,
+    y: i32,
+    orbits: Option<&'static str>,
+    traits: &'static [&'static str],
+    under_construction: bool,
+}
+
+const WAYPOINTS: &[WaypointSpec] = &[
+    WaypointSpec {
+        symbol: "X1-MOCK-A1",
+        r#type: "PLANET",
+        x: 0,
+        y: 0,
+        orbits: None,
+        traits: &["MARKETPLACE", "SHIPYARD", "TEMPERATE", "SPRAWLING_CITIES"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-A2",
+        r#type: "MOON",
+        x: 0,
+        y: 0,
+        orbits: Some("X1-MOCK-A1"),
+        traits: &["MARKETPLACE", "BARREN"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-B1",
+        r#type: "ENGINEERED_ASTEROID",
+        x: 30,
+        y: -20,
+        orbits: None,
+        traits: &["MARKETPLACE", "COMMON_METAL_DEPOSITS"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-C1",
+        r#type: "ASTEROID",
+        x: 400,
+        y: 300,
+        orbits: None,
+        traits: &["PRECIOUS_METAL_DEPOSITS"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-D1",
+        r#type: "FUEL_STATION",
+        x: 200,
+        y: 150,
+        orbits: None,
+        traits: &["MARKETPLACE"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-E1",
+        r#type: "JUMP_GATE",
+        x: -100,
+        y: 50,
+        orbits: None,
+        traits: &[],
+        under_construction: true,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-F1",
+        r#type: "GAS_GIANT",
+        x: -50,
+        y: -200,
+        orbits: None,
+        traits: &["JOVIAN"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK-H1",
+        r#type: "PLANET",
+        x: 150,
+        y: -50,
+        orbits: None,
+        traits: &["MARKETPLACE", "INDUSTRIAL"],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK2-G1",
+        r#type: "JUMP_GATE",
+        x: 10,
+        y: 10,
+        orbits: None,
+        traits: &[],
+        under_construction: false,
+    },
+    WaypointSpec {
+        symbol: "X1-MOCK2-A1",
+        r#type: "PLANET",
+        x: -30,
+        y: 40,
+        orbits: None,
+        traits: &["UNCHARTED"],
+        under_construction: false,
+    },
+];
+
+fn system_of(waypoint_symbol: &str) -> String {
+    waypoint_symbol.rsplit_once('-').unwrap().0.to_string()
+}
+
+pub fn waypoints() -> Vec<Waypoint> {
+    WAYPOINTS
+        .iter()
+        .map(|spec| {
+            let orbitals: Vec<Value> = WAYPOINTS
+                .iter()
+                .filter(|other| other.orbits == Some(spec.symbol))
+                .map(|other| json!({ "symbol": other.symbol }))
+                .collect();
+            let charted = !spec.traits.contains(&"UNCHARTED");
+            decode(json!({
+                "symbol": spec.symbol,
+                "type": spec.r#type,
+                "systemSymbol": system_of(spec.symbol),
+                "x": spec.x,
+                "y": spec.y,
+                "orbitals": orbitals,
+                "orbits": spec.orbits,
+                "faction": { "symbol": FACTION },
+                "traits": spec.traits.iter().map(|t| waypoint_trait(t)).collect::<Vec<_>>(),
+                "modifiers": [],
+                "chart": if charted {
+                    json!({ "submittedBy": FACTION, "submittedOn": "2023-11-18T00:00:00.000Z" })
+                } else {
+                    Value::Null
+                },
+                "isUnderConstruction": spec.under_construction,
+            }))
+        })
+        .collect()
+}
+
+pub fn systems(waypoints: &[Waypoint]) -> Vec<System> {
+    [
+        (SYSTEM, "ORANGE_STAR", 0, 0),
+        (NEIGHBOR_SYSTEM, "RED_STAR", 120, -80),
+    ]
+    .into_iter()
+    .map(|(symbol, r#type, x, y)| {
+        let members: Vec<Value> = waypoints
+            .iter()
+            .filter(|w| w.system_symbol == symbol)
+            .map(|w| {
+                json!({
+                    "symbol": w.symbol,
+                    "type": w.r#type,
+                    "x": w.x,
+                    "y": w.y,
+                    "orbitals": w.orbitals,
+                    "orbits": w.orbits,
+                })
+            })
+            .collect();
+        decode(json!({
+            "symbol": symbol,
+            "sectorSymbol": "X1",
+            "type": r#type,
+            "x": x,
+            "y": y,
+            "waypoints": members,
+            "factions": [{ "symbol": FACTION }],
+        }))
+    })
+    .collect()
+}
+
+pub fn jump_gates() -> Vec<(String, JumpGate)> {
+    vec![
+        (
+            "X1-MOCK-E1".to_string(),
+            decode(json!({ "connections": ["X1-MOCK2-G1"] })),
+        ),
+        (
+            "X1-MOCK2-G1".to_string(),
+            decode(json!({ "connections": ["X1-MOCK-E1"] })),
+        ),
+    ]
+}
+
+pub fn constructions() -> Vec<Construction> {
+    vec![decode(json!({
+        "symbol": "X1-MOCK-E1",
+        "materials": [
+            { "tradeSymbol": "FAB_MATS", "required": 1600, "fulfilled": 0 },
+            { "tradeSymbol": "ADVANCED_CIRCUITRY", "required": 400, "fulfilled": 0 },
+        ],
+        "isComplete": false,
+    }))]
+}
+
+pub fn factions() -> Vec<Faction> {
+    vec![decode(json!({
+        "symbol": FACTION,
+        "name": "Cosmic Engineers",
+        "description": "The only faction in the mock universe.",
+        "headquarters": HEADQUARTERS,
+        "traits": [{
+            "symbol": "INDUSTRIOUS",
+            "name": "Industrious",
+            "description": "Always building something.",
+        }],
+        "isRecruiting": true,
+    }))]
+}
+
+/// A good a market trades, and the prices it settles back to when nobody's
+/// been buying or selling.
+pub struct GoodSpec {
+    pub symbol: TradeSymbol,
+    pub r#type: market_trade_good::Type,
+    pub purchase_price: i32,
+    pub sell_price: i32,
+    pub trade_volume: i32,
+}
+
+fn good(
+    symbol: TradeSymbol,
+    r#type: market_trade_good::Type,
+    purchase_price: i32,
+    sell_price: i32,
+    trade_volume: i32,
+) -> GoodSpec {
+    GoodSpec {
+        symbol,
+        r#type,
+        purchase_price,
+        sell_price,
+        trade_volume,
+    }
+}
+
+pub fn markets() -> Vec<(Market, Vec<GoodSpec>)> {
+    use market_trade_good::Type::{Exchange, Export, Import};
+    use TradeSymbol::*;
+
+    let specs: Vec<(&str, Vec<GoodSpec>)> = vec![
+        (
+            "X1-MOCK-A1",
+            vec![
+                good(IronOre, Import, 90, 45, 60),
+                good(CopperOre, Import, 80, 40, 60),
+                good(AluminumOre, Import, 100, 50, 60),
+                good(Machinery, Export, 400, 200, 20),
+                good(Fuel, Exchange, 72, 68, 100),
+            ],
+        ),
+        (
+            "X1-MOCK-A2",
+            vec![
+                good(IceWater, Import, 30, 16, 60),
+                good(QuartzSand, Import, 40, 22, 60),
+                good(Iron, Import, 260, 130, 20),
+                good(Food, Export, 60, 30, 40),
+                good(Fuel, Exchange, 76, 70, 100),
+            ],
+        ),
+        (
+            "X1-MOCK-B1",
+            vec![
+                good(IronOre, Export, 20, 10, 40),
+                good(CopperOre, Export, 18, 9, 40),
+                good(Machinery, Import, 700, 350, 10),
+                good(Food, Import, 140, 70, 20),
+                good(Fuel, Exchange, 80, 74, 100),
+            ],
+        ),
+        ("X1-MOCK-D1", vec![good(Fuel, Exchange, 64, 60, 200)]),
+        (
+            "X1-MOCK-H1",
+            vec![
+                good(GoldOre, Import, 220, 110, 20),
+                good(SilverOre, Import, 180, 90, 20),
+                good(PlatinumOre, Import, 300, 150, 20),
+                good(Copper, Import, 240, 120, 20),
+                good(Aluminum, Import, 280, 140, 20),
+                good(Food, Export, 50, 25, 40),
+                good(Fuel, Exchange, 70, 66, 100),
+            ],
+        ),
+    ];
+
+    specs
+        .into_iter()
+        .map(|(symbol, goods)| {
+            let listed = |r#type: market_trade_good::Type| {
+                goods
+                    .iter()
+                    .filter(|g| g.r#type == r#type)
+                    .map(|g| {
+                        json!({
+                            "symbol": g.symbol,
+                            "name": g.symbol.to_string(),
+                            "description": "",
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let market = decode(json!({
+                "symbol": symbol,
+                "exports": listed(Export),
+                "imports": listed(Import),
+                "exchange": listed(Exchange),
+            }));
+            (market, goods)
+        })
+        .collect()
+}
+
+/// Ship types the mock shipyard sells, and what they cost.
+pub const SHIPYARD_SHIPS: &[(ShipType, i32)] = &[
+    (ShipType::Probe, 25_000),
+    (ShipType::MiningDrone, 45_000),
+    (ShipType::SiphonDrone, 40_000),
+    (ShipType::Surveyor, 35_000),
+    (ShipType::LightHauler, 90_000),
+    (ShipType::RefiningFreighter, 300_000),
+];
+
+pub fn shipyard(waypoint_symbol: &str) -> Shipyard {
+    decode(json!({
+        "symbol": waypoint_symbol,
+        "shipTypes": SHIPYARD_SHIPS.iter().map(|(t, _)| json!({ "type": t })).collect::<Vec<_>>(),
+        "modificationsFee": 1000,
+    }))
+}
+
+fn requirements(power: i32, crew: i32, slots: Option<i32>) -> Value {
+    json!({ "power": power, "crew": crew, "slots": slots })
+}
+
+fn module(symbol: &str, capacity: Option<i32>) -> Value {
+    json!({
+        "symbol": symbol,
+        "capacity": capacity,
+        "name": symbol,
+        "description": "",
+        "requirements": requirements(1, 0, Some(1)),
+    })
+}
+
+fn mount(symbol: &str, strength: i32, deposits: &[&str]) -> Value {
+    json!({
+        "symbol": symbol,
+        "name": symbol,
+        "description": "",
+        "strength": strength,
+        "deposits": if deposits.is_empty() { Value::Null } else { json!(deposits) },
+        "requirements": requirements(1, 0, None),
+    })
+}
+
+const MINEABLE: &[&str] = &[
+    "QUARTZ_SAND",
+    "SILICON_CRYSTALS",
+    "ICE_WATER",
+    "IRON_ORE",
+    "COPPER_ORE",
+    "ALUMINUM_ORE",
+    "SILVER_ORE",
+    "GOLD_ORE",
+    "PLATINUM_ORE",
+];
+
+/// The parts a ship type is built from, in the shape of a `ShipyardShip`.
+fn parts(ship_type: ShipType) -> Value {
+    let (frame, fuel, reactor, engine, speed, modules, mounts, crew) = match ship_type {
+        ShipType::CommandFrigate => (
+            "FRAME_FRIGATE",
+            400,
+            "REACTOR_FISSION_I",
+            "ENGINE_ION_DRIVE_II",
+            30,
+            vec![
+                module("MODULE_CARGO_HOLD_II", Some(40)),
+                module("MODULE_CREW_QUARTERS_I", None),
+                module("MODULE_MINERAL_PROCESSOR_I", None),
+            ],
+            vec![
+                mount("MOUNT_SENSOR_ARRAY_II", 4, &[]),
+                mount("MOUNT_GAS_SIPHON_II", 20, &[]),
+                mount("MOUNT_MINING_LASER_II", 25, &[]),
+                mount("MOUNT_SURVEYOR_II", 2, MINEABLE),
+            ],
+            57,
+        ),
+        ShipType::Probe => (
+            "FRAME_PROBE",
+            0,
+            "REACTOR_SOLAR_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            9,
+            vec![],
+            vec![],
+            0,
+        ),
+        ShipType::MiningDrone => (
+            "FRAME_DRONE",
+            80,
+            "REACTOR_CHEMICAL_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            9,
+            vec![module("MODULE_CARGO_HOLD_I", Some(15))],
+            vec![mount("MOUNT_MINING_LASER_I", 10, &[])],
+            0,
+        ),
+        ShipType::SiphonDrone => (
+            "FRAME_DRONE",
+            80,
+            "REACTOR_CHEMICAL_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            9,
+            vec![module("MODULE_CARGO_HOLD_I", Some(15))],
+            vec![mount("MOUNT_GAS_SIPHON_I", 10, &[])],
+            0,
+        ),
+        ShipType::Surveyor => (
+            "FRAME_DRONE",
+            80,
+            "REACTOR_CHEMICAL_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            9,
+            vec![],
+            vec![mount("MOUNT_SURVEYOR_I", 1, MINEABLE)],
+            0,
+        ),
+        ShipType::RefiningFreighter => (
+            "FRAME_HEAVY_FREIGHTER",
+            1200,
+            "REACTOR_FUSION_I",
+            "ENGINE_ION_DRIVE_I",
+            10,
+            vec![
+                module("MODULE_CARGO_HOLD_II", Some(40)),
+                module("MODULE_CARGO_HOLD_II", Some(40)),
+                module("MODULE_ORE_REFINERY_I", None),
+                module("MODULE_CREW_QUARTERS_I", None),
+            ],
+            vec![],
+            40,
+        ),
+        // Anything else we don't model gets to be a light hauler
+        _ => (
+            "FRAME_LIGHT_FREIGHTER",
+            600,
+            "REACTOR_CHEMICAL_I",
+            "ENGINE_ION_DRIVE_I",
+            15,
+            vec![
+                module("MODULE_CARGO_HOLD_II", Some(40)),
+                module("MODULE_CARGO_HOLD_II", Some(40)),
+                module("MODULE_CREW_QUARTERS_I", None),
+            ],
+            vec![mount("MOUNT_SENSOR_ARRAY_I", 1, &[])],
+            20,
+        ),
+    };
+
+    json!({
+        "frame": {
+            "symbol": frame,
+            "name": frame,
+            "description": "",
+            "condition": 100,
+            "moduleSlots": 8,
+            "mountingPoints": 4,
+            "fuelCapacity": fuel,
+            "requirements": requirements(1, 0, None),
+        },
+        "reactor": {
+            "symbol": reactor,
+            "name": reactor,
+            "description": "",
+            "condition": 100,
+            "powerOutput": 40,
+            "requirements": requirements(0, 0, None),
+        },
+        "engine": {
+            "symbol": engine,
+            "name": engine,
+            "description": "",
+            "condition": 100,
+            "speed": speed,
+            "requirements": requirements(1, 0, None),
+        },
+        "modules": modules,
+        "mounts": mounts,
+        "crew": crew,
+    })
+}
+
+fn role(ship_type: ShipType) -> &'static str {
+    match ship_type {
+        ShipType::CommandFrigate => "COMMAND",
+        ShipType::Probe => "SATELLITE",
+        ShipType::MiningDrone | ShipType::SiphonDrone => "EXCAVATOR",
+        ShipType::Surveyor => "SURVEYOR",
+        ShipType::RefiningFreighter => "REFINERY",
+        _ => "HAULER",
+    }
+}
+
+/// A brand new ship, docked at `waypoint`.
+pub fn ship(ship_type: ShipType, symbol: &str, waypoint: &Waypoint, now: &str) -> Ship {
+    let parts = parts(ship_type);
+    let fuel = parts["frame"]["fuelCapacity"].clone();
+    let cargo: i64 = parts["modules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|m| m["capacity"].as_i64())
+        .sum();
+    let location = json!({
+        "symbol": waypoint.symbol,
+        "type": waypoint.r#type,
+        "systemSymbol": waypoint.system_symbol,
+        "x": waypoint.x,
+        "y": waypoint.y,
+    });
+    let crew = parts["crew"].as_i64().unwrap();
+
+    decode(json!({
+        "symbol": symbol,
+        "registration": {
+            "name": symbol,
+            "factionSymbol": FACTION,
+            "role": role(ship_type),
+        },
+        "nav": {
+            "systemSymbol": waypoint.system_symbol,
+            "waypointSymbol": waypoint.symbol,
+            "route": {
+                "destination": location,
+                "departure": location,
+                "origin": location,
+                "departureTime": now,
+                "arrival": now,
+            },
+            "status": "DOCKED",
+            "flightMode": "CRUISE",
+        },
+        "crew": {
+            "current": crew,
+            "required": crew,
+            "capacity": crew,
+            "rotation": "STRICT",
+            "morale": 100,
+            "wages": 0,
+        },
+        "frame": parts["frame"],
+        "reactor": parts["reactor"],
+        "engine": parts["engine"],
+        "cooldown": {
+            "shipSymbol": symbol,
+            "totalSeconds": 0,
+            "remainingSeconds": 0,
+        },
+        "modules": parts["modules"],
+        "mounts": parts["mounts"],
+        "cargo": { "capacity": cargo, "units": 0, "inventory": [] },
+        "fuel": { "current": fuel, "capacity": fuel },
+    }))
+}
+
+/// What a shipyard lists for sale, minus the price and supply which the
+/// universe fills in.
+pub fn shipyard_ship(ship_type: ShipType, purchase_price: i32) -> Value {
+    let parts = parts(ship_type);
+    let crew = parts["crew"].as_i64().unwrap();
+    json!({
+        "type": ship_type,
+        "name": ship_type.to_string(),
+        "description": "",
+        "supply": "MODERATE",
+        "purchasePrice": purchase_price,
+        "frame": parts["frame"],
+        "reactor": parts["reactor"],
+        "engine": parts["engine"],
+        "modules": parts["modules"],
+        "mounts": parts["mounts"],
+        "crew": { "required": crew, "capacity": crew },
+    })
+}
+
+/// What's underground at a waypoint, for extraction and siphoning.
+pub fn deposits(waypoint: &Waypoint) -> &'static [TradeSymbol] {
+    use TradeSymbol::*;
+    match waypoint.r#type {
+        WaypointType::EngineeredAsteroid => &[
+            IronOre,
+            CopperOre,
+            AluminumOre,
+            QuartzSand,
+            IceWater,
+            SiliconCrystals,
+        ],
+        WaypointType::Asteroid => &[GoldOre, SilverOre, PlatinumOre, QuartzSand],
+        _ => &[],
+    }
+}
+
+pub fn gases(waypoint: &Waypoint) -> &'static [TradeSymbol] {
+    use TradeSymbol::*;
+    match waypoint.r#type {
+        WaypointType::GasGiant => &[Hydrocarbon, LiquidHydrogen, LiquidNitrogen],
+        _ => &[],
+    }
+}
+
+pub fn contract(
+    id: &str,
+    trade_symbol: TradeSymbol,
+    units: i32,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Contract {
+    let timestamp = |days| {
+        (now + chrono::Duration::days(days)).to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    };
+    decode(json!({
+        "id": id,
+        "factionSymbol": FACTION,
+        "type": "PROCUREMENT",
+        "terms": {
+            "deadline": timestamp(7),
+            "payment": { "onAccepted": 5_000, "onFulfilled": units * 500 },
+            "deliver": [{
+                "tradeSymbol": trade_symbol,
+                "destinationSymbol": HEADQUARTERS,
+                "unitsRequired": units,
+                "unitsFulfilled": 0,
+            }],
+        },
+        "accepted": false,
+        "fulfilled": false,
+        "expiration": timestamp(1),
+        "deadlineToAccept": timestamp(1),
+    }))
+}
@@ -0,0 +1,1577 @@
+//! The mock universe's state and the rules of the game, as far as we model
+//! them.
+//!
+//! Every action returns the JSON `data` the real endpoint would, or an
+//! [`ApiError`] with the real error code, so clients can exercise their
+//! error handling too.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use spacedust::models::{
+    market_trade_good, ActivityLevel, Agent, Construction, Contract, Faction, JumpGate, Market,
+    MarketTradeGood, Ship, ShipCargoItem, ShipNavFlightMode, ShipNavRouteWaypoint, ShipNavStatus,
+    ShipType, SupplyLevel, Survey, System, TradeSymbol, Waypoint,
+};
+
+use crate::error::*;
+use crate::templates::{self, GoodSpec};
+
+pub fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_timestamp(timestamp: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+/// How long, in seconds, the reactor needs after each kind of action.
+const EXTRACT_COOLDOWN: i64 = 70;
+const SURVEY_COOLDOWN: i64 = 60;
+const REFINE_COOLDOWN: i64 = 70;
+
+/// How many extractions a survey is good for, by size.
+fn survey_extractions(size: spacedust::models::survey::Size) -> i32 {
+    use spacedust::models::survey::Size;
+    match size {
+        Size::Small => 5,
+        Size::Moderate => 10,
+        Size::Large => 20,
+    }
+}
+
+/// How far a price has been pushed away from where the market wants it. Each
+/// trade volume's worth sold knocks 5% off, bought adds 5%, and it halves
+/// every ten minutes.
+const PRESSURE_PER_VOLUME: f64 = 0.05;
+const PRESSURE_HALF_LIFE_SECONDS: f64 = 600.0;
+
+struct TradeGoodState {
+    spec: GoodSpec,
+    pressure: f64,
+}
+
+impl TradeGoodState {
+    fn scale(&self, price: i32) -> i32 {
+        ((price as f64) * (1.0 + self.pressure)).round().max(1.0) as i32
+    }
+
+    fn trade_good(&self) -> MarketTradeGood {
+        let supply = match self.pressure {
+            p if p < -0.3 => SupplyLevel::Abundant,
+            p if p < -0.1 => SupplyLevel::High,
+            p if p < 0.1 => SupplyLevel::Moderate,
+            p if p < 0.3 => SupplyLevel::Limited,
+            _ => SupplyLevel::Scarce,
+        };
+        let mut good = MarketTradeGood::new(
+            self.spec.symbol,
+            self.spec.r#type,
+            self.spec.trade_volume,
+            supply,
+            self.scale(self.spec.purchase_price),
+            self.scale(self.spec.sell_price),
+        );
+        good.activity = Some(ActivityLevel::Growing);
+        good
+    }
+
+    fn trade(&mut self, units: i32, sold: bool) {
+        let change = units as f64 / self.spec.trade_volume as f64 * PRESSURE_PER_VOLUME;
+        self.pressure += if sold { -change } else { change };
+    }
+}
+
+struct MarketState {
+    market: Market,
+    goods: Vec<TradeGoodState>,
+    transactions: Vec<Value>,
+}
+
+struct SurveyState {
+    survey: Survey,
+    extractions_left: i32,
+}
+
+pub struct Universe {
+    pub reset_date: String,
+    pub agent: Agent,
+    pub token: String,
+    systems: Vec<System>,
+    waypoints: Vec<Waypoint>,
+    markets: BTreeMap<String, MarketState>,
+    shipyard_transactions: Vec<Value>,
+    jump_gates: BTreeMap<String, JumpGate>,
+    constructions: BTreeMap<String, Construction>,
+    factions: Vec<Faction>,
+    ships: BTreeMap<String, Ship>,
+    contracts: Vec<Contract>,
+    surveys: HashMap<String, SurveyState>,
+    /// Bumped for anything that needs a unique, but reproducible, name
+    sequence: u64,
+    settled_at: DateTime<Utc>,
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Universe::new("MOCK_AGENT")
+    }
+}
+
+impl Universe {
+    pub fn new(agent_symbol: &str) -> Universe {
+        let now = Utc::now();
+        let waypoints = templates::waypoints();
+        let systems = templates::systems(&waypoints);
+        let markets = templates::markets()
+            .into_iter()
+            .map(|(market, goods)| {
+                let state = MarketState {
+                    market,
+                    goods: goods
+                        .into_iter()
+                        .map(|spec| TradeGoodState {
+                            spec,
+                            pressure: 0.0,
+                        })
+                        .collect(),
+                    transactions: vec![],
+                };
+                (state.market.symbol.clone(), state)
+            })
+            .collect();
+
+        let mut universe = Universe {
+            reset_date: "2023-11-18".to_string(),
+            agent: Agent::new(
+                agent_symbol.to_string(),
+                templates::HEADQUARTERS.to_string(),
+                templates::STARTING_CREDITS,
+                templates::FACTION.to_string(),
+            ),
+            token: format!("mock-token-{agent_symbol}"),
+            systems,
+            waypoints,
+            markets,
+            shipyard_transactions: vec![],
+            jump_gates: templates::jump_gates().into_iter().collect(),
+            constructions: templates::constructions()
+                .into_iter()
+                .map(|c| (c.symbol.clone(), c))
+                .collect(),
+            factions: templates::factions(),
+            ships: BTreeMap::new(),
+            contracts: vec![],
+            surveys: HashMap::new(),
+            sequence: 0,
+            settled_at: now,
+        };
+        universe.start_agent(agent_symbol);
+        universe
+    }
+
+    /// Gives `agent_symbol` the starting fleet and contract, throwing away
+    /// whoever was playing before.
+    fn start_agent(&mut self, agent_symbol: &str) {
+        let now = Utc::now();
+        self.agent = Agent::new(
+            agent_symbol.to_string(),
+            templates::HEADQUARTERS.to_string(),
+            templates::STARTING_CREDITS,
+            templates::FACTION.to_string(),
+        );
+        self.agent.account_id = Some(format!("mock-account-{agent_symbol}"));
+        self.token = format!("mock-token-{agent_symbol}");
+        self.ships.clear();
+        self.contracts.clear();
+        self.surveys.clear();
+
+        let headquarters = self.waypoint(templates::HEADQUARTERS).unwrap().clone();
+        for ship_type in [ShipType::CommandFrigate, ShipType::Probe] {
+            let symbol = format!("{agent_symbol}-{}", self.ships.len() + 1);
+            let ship = templates::ship(ship_type, &symbol, &headquarters, &timestamp(now));
+            self.ships.insert(symbol, ship);
+        }
+        self.agent.ship_count = Some(self.ships.len() as i32);
+
+        let id = self.next_id("contract");
+        self.contracts
+            .push(templates::contract(&id, TradeSymbol::IronOre, 60, now));
+    }
+
+    /// Starts a new reset with a fresh universe, like the real server does
+    /// every couple of weeks. Nobody's registered afterwards, so the old
+    /// token stops working and the agent's symbol is free to claim again.
+    pub fn reset(&mut self, reset_date: &str) {
+        let agent_symbol = self.agent.symbol.clone();
+        *self = Universe::new(&agent_symbol);
+        self.reset_date = reset_date.to_string();
+        self.token = String::new();
+    }
+
+    /// A fortnight after the current reset, when the real server would next
+    /// reset.
+    pub fn next_reset_date(&self) -> String {
+        let current = parse_timestamp(&format!("{}T00:00:00Z", self.reset_date));
+        (current + chrono::Duration::days(14))
+            .format("%Y-%m-%d")
+            .to_string()
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.sequence += 1;
+        format!("mock-{prefix}-{}", self.sequence)
+    }
+
+    /// Brings everything time-dependent up to date: ships that have arrived,
+    /// cooldowns that have run out and prices drifting back to normal.
+    pub fn settle(&mut self) {
+        let now = Utc::now();
+        let elapsed = (now - self.settled_at).num_milliseconds() as f64 / 1000.0;
+        self.settled_at = now;
+
+        let decay = 0.5f64.powf(elapsed.max(0.0) / PRESSURE_HALF_LIFE_SECONDS);
+        for market in self.markets.values_mut() {
+            for good in market.goods.iter_mut() {
+                good.pressure *= decay;
+            }
+        }
+
+        for ship in self.ships.values_mut() {
+            if ship.nav.status == ShipNavStatus::InTransit
+                && parse_timestamp(&ship.nav.route.arrival) <= now
+            {
+                ship.nav.status = ShipNavStatus::InOrbit;
+            }
+
+            if let Some(expiration) = ship.cooldown.expiration.clone() {
+                let remaining = (parse_timestamp(&expiration) - now).num_seconds();
+                if remaining <= 0 {
+                    ship.cooldown.remaining_seconds = 0;
+                    ship.cooldown.expiration = None;
+                } else {
+                    ship.cooldown.remaining_seconds = remaining as i32;
+                }
+            }
+        }
+
+        let expired: Vec<String> = self
+            .surveys
+            .iter()
+            .filter(|(_, s)| parse_timestamp(&s.survey.expiration) <= now)
+            .map(|(signature, _)| signature.clone())
+            .collect();
+        for signature in expired {
+            self.surveys.remove(&signature);
+        }
+    }
+
+    pub fn status(&self) -> Value {
+        let next_reset = parse_timestamp(&format!("{}T00:00:00Z", self.next_reset_date()));
+        json!({
+            "status": "SpaceTraders mock server is online",
+            "version": "v2.1.2",
+            "resetDate": self.reset_date,
+            "description": "A small, deterministic SpaceTraders universe for offline development.",
+            "stats": {
+                "agents": 1,
+                "ships": self.ships.len(),
+                "systems": self.systems.len(),
+                "waypoints": self.waypoints.len(),
+            },
+            "leaderboards": {
+                "mostCredits": [{ "agentSymbol": self.agent.symbol, "credits": self.agent.credits }],
+                "mostSubmittedCharts": [],
+            },
+            "serverResets": {
+                "next": timestamp(next_reset),
+                "frequency": "fortnightly",
+            },
+            "announcements": [],
+            "links": [],
+        })
+    }
+
+    pub fn register(&mut self, symbol: &str, faction: &str) -> ApiResult<Value> {
+        if faction != templates::FACTION {
+            return Err(ApiError::new(
+                4112,
+                format!(
+                    "The mock universe only has the {} faction.",
+                    templates::FACTION
+                ),
+            ));
+        }
+        if !(3..=14).contains(&symbol.len()) {
+            return Err(ApiError::new(
+                422,
+                "Agent symbols must be between 3 and 14 characters.",
+            ));
+        }
+        if !self.token.is_empty() && symbol.eq_ignore_ascii_case(&self.agent.symbol) {
+            return Err(ApiError::new(
+                REGISTER_AGENT_CONFLICT_SYMBOL,
+                format!("Agent symbol {symbol} has already been claimed."),
+            ));
+        }
+
+        self.start_agent(&symbol.to_uppercase());
+        // Tokens from before a reset mustn't work after it
+        self.token = format!("{}-{}", self.token, self.reset_date);
+        let ship = self.ships.values().next().unwrap();
+        Ok(json!({
+            "agent": self.agent,
+            "contract": self.contracts[0],
+            "faction": self.factions[0],
+            "ship": ship,
+            "token": self.token,
+        }))
+    }
+
+    pub fn is_authorized(&self, token: &str) -> bool {
+        !self.token.is_empty() && token == self.token
+    }
+
+    // Lookups
+
+    pub fn systems(&self) -> &[System] {
+        &self.systems
+    }
+
+    pub fn system(&self, symbol: &str) -> ApiResult<&System> {
+        self.systems
+            .iter()
+            .find(|s| s.symbol == symbol)
+            .ok_or_else(|| ApiError::not_found(format!("System {symbol} not found.")))
+    }
+
+    pub fn system_waypoints(&self, system_symbol: &str) -> ApiResult<Vec<Waypoint>> {
+        self.system(system_symbol)?;
+        Ok(self
+            .waypoints
+            .iter()
+            .filter(|w| w.system_symbol == system_symbol)
+            .cloned()
+            .collect())
+    }
+
+    pub fn waypoint(&self, symbol: &str) -> ApiResult<&Waypoint> {
+        self.waypoints
+            .iter()
+            .find(|w| w.symbol == symbol)
+            .ok_or_else(|| ApiError::not_found(format!("Waypoint {symbol} not found.")))
+    }
+
+    /// Whether one of our ships is sitting at the waypoint, which is what it
+    /// takes to see live prices.
+    fn has_ship_at(&self, waypoint_symbol: &str) -> bool {
+        self.ships.values().any(|s| {
+            s.nav.waypoint_symbol == waypoint_symbol && s.nav.status != ShipNavStatus::InTransit
+        })
+    }
+
+    pub fn market(&self, waypoint_symbol: &str) -> ApiResult<Market> {
+        self.waypoint(waypoint_symbol)?;
+        let state = self.markets.get(waypoint_symbol).ok_or_else(|| {
+            ApiError::new(
+                MARKET_NOT_FOUND,
+                format!("Market not found at {waypoint_symbol}."),
+            )
+        })?;
+
+        let mut market = state.market.clone();
+        if self.has_ship_at(waypoint_symbol) {
+            market.trade_goods = Some(state.goods.iter().map(|g| g.trade_good()).collect());
+            market.transactions = Some(
+                state
+                    .transactions
+                    .iter()
+                    .map(|t| serde_json::from_value(t.clone()).unwrap())
+                    .collect(),
+            );
+        }
+        Ok(market)
+    }
+
+    pub fn shipyard(&self, waypoint_symbol: &str) -> ApiResult<Value> {
+        let waypoint = self.waypoint(waypoint_symbol)?;
+        if !has_trait(waypoint, "SHIPYARD") {
+            return Err(ApiError::new(
+                4700,
+                format!("Shipyard not found at {waypoint_symbol}."),
+            ));
+        }
+
+        let mut shipyard = serde_json::to_value(templates::shipyard(waypoint_symbol)).unwrap();
+        if self.has_ship_at(waypoint_symbol) {
+            shipyard["ships"] = templates::SHIPYARD_SHIPS
+                .iter()
+                .map(|(ship_type, price)| templates::shipyard_ship(*ship_type, *price))
+                .collect();
+            shipyard["transactions"] = json!(self.shipyard_transactions);
+        }
+        Ok(shipyard)
+    }
+
+    pub fn jump_gate(&self, waypoint_symbol: &str) -> ApiResult<&JumpGate> {
+        self.waypoint(waypoint_symbol)?;
+        self.jump_gates.get(waypoint_symbol).ok_or_else(|| {
+            ApiError::new(
+                WAYPOINT_NO_ACCESS,
+                format!("{waypoint_symbol} is not a jump gate."),
+            )
+        })
+    }
+
+    pub fn construction(&self, waypoint_symbol: &str) -> ApiResult<&Construction> {
+        self.waypoint(waypoint_symbol)?;
+        self.constructions.get(waypoint_symbol).ok_or_else(|| {
+            ApiError::new(
+                4800,
+                format!("{waypoint_symbol} is not under construction."),
+            )
+        })
+    }
+
+    pub fn factions(&self) -> &[Faction] {
+        &self.factions
+    }
+
+    pub fn faction(&self, symbol: &str) -> ApiResult<&Faction> {
+        self.factions
+            .iter()
+            .find(|f| f.symbol.to_string() == symbol)
+            .ok_or_else(|| ApiError::not_found(format!("Faction {symbol} not found.")))
+    }
+
+    pub fn agent(&self, symbol: &str) -> ApiResult<&Agent> {
+        if symbol != self.agent.symbol {
+            return Err(ApiError::not_found(format!("Agent {symbol} not found.")));
+        }
+        Ok(&self.agent)
+    }
+
+    pub fn contracts(&self) -> &[Contract] {
+        &self.contracts
+    }
+
+    pub fn contract(&self, id: &str) -> ApiResult<&Contract> {
+        self.contracts
+            .iter()
+            .find(|c| c.id == id)
+            .ok_or_else(|| ApiError::not_found(format!("Contract {id} not found.")))
+    }
+
+    fn contract_mut(&mut self, id: &str) -> ApiResult<&mut Contract> {
+        self.contracts
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| ApiError::not_found(format!("Contract {id} not found.")))
+    }
+
+    pub fn ships(&self) -> Vec<Ship> {
+        self.ships.values().cloned().collect()
+    }
+
+    pub fn ship(&self, symbol: &str) -> ApiResult<&Ship> {
+        self.ships
+            .get(symbol)
+            .ok_or_else(|| ApiError::not_found(format!("Ship {symbol} not found.")))
+    }
+
+    /// Lands the ship if it's flying and ends its cooldown, for tests that
+    /// can't wait around in real time.
+    pub fn skip_waits(&mut self, symbol: &str) -> ApiResult<()> {
+        let now = timestamp(Utc::now());
+        let ship = self.ship_mut(symbol)?;
+        if ship.nav.status == ShipNavStatus::InTransit {
+            ship.nav.status = ShipNavStatus::InOrbit;
+            ship.nav.route.arrival = now;
+        }
+        ship.cooldown.remaining_seconds = 0;
+        ship.cooldown.expiration = None;
+        Ok(())
+    }
+
+    fn ship_mut(&mut self, symbol: &str) -> ApiResult<&mut Ship> {
+        self.ships
+            .get_mut(symbol)
+            .ok_or_else(|| ApiError::not_found(format!("Ship {symbol} not found.")))
+    }
+
+    // Preconditions
+
+    fn ship_not_in_transit(&self, symbol: &str) -> ApiResult<&Ship> {
+        let ship = self.ship(symbol)?;
+        if ship.nav.status == ShipNavStatus::InTransit {
+            let seconds = (parse_timestamp(&ship.nav.route.arrival) - Utc::now()).num_seconds();
+            return Err(ApiError::new(
+                SHIP_IN_TRANSIT,
+                format!("Ship {symbol} is currently in transit."),
+            )
+            .with_data(json!({
+                "departureSymbol": ship.nav.route.origin.symbol,
+                "destinationSymbol": ship.nav.route.destination.symbol,
+                "arrival": ship.nav.route.arrival,
+                "departureTime": ship.nav.route.departure_time,
+                "secondsToArrival": seconds,
+            })));
+        }
+        Ok(ship)
+    }
+
+    fn ship_in_orbit(&self, symbol: &str) -> ApiResult<&Ship> {
+        let ship = self.ship_not_in_transit(symbol)?;
+        if ship.nav.status != ShipNavStatus::InOrbit {
+            return Err(ApiError::new(
+                SHIP_NOT_IN_ORBIT,
+                format!("Ship {symbol} must be in orbit to do that."),
+            ));
+        }
+        Ok(ship)
+    }
+
+    fn ship_docked(&self, symbol: &str) -> ApiResult<&Ship> {
+        let ship = self.ship_not_in_transit(symbol)?;
+        if ship.nav.status != ShipNavStatus::Docked {
+            return Err(ApiError::new(
+                SHIP_NOT_DOCKED,
+                format!("Ship {symbol} must be docked to do that."),
+            ));
+        }
+        Ok(ship)
+    }
+
+    fn ship_ready(&self, symbol: &str) -> ApiResult<&Ship> {
+        let ship = self.ship_in_orbit(symbol)?;
+        if ship.cooldown.remaining_seconds > 0 {
+            return Err(ApiError::new(
+                COOLDOWN_CONFLICT,
+                format!("Ship {symbol} is still cooling down."),
+            )
+            .with_data(json!({ "cooldown": ship.cooldown })));
+        }
+        Ok(ship)
+    }
+
+    fn start_cooldown(&mut self, symbol: &str, seconds: i64) -> ApiResult<Value> {
+        let ship = self.ship_mut(symbol)?;
+        ship.cooldown.total_seconds = seconds as i32;
+        ship.cooldown.remaining_seconds = seconds as i32;
+        ship.cooldown.expiration = Some(timestamp(Utc::now() + chrono::Duration::seconds(seconds)));
+        Ok(json!(ship.cooldown))
+    }
+
+    // Navigation
+
+    pub fn orbit(&mut self, symbol: &str) -> ApiResult<Value> {
+        self.ship_not_in_transit(symbol)?;
+        let ship = self.ship_mut(symbol)?;
+        ship.nav.status = ShipNavStatus::InOrbit;
+        Ok(json!({ "nav": ship.nav }))
+    }
+
+    pub fn dock(&mut self, symbol: &str) -> ApiResult<Value> {
+        self.ship_not_in_transit(symbol)?;
+        let ship = self.ship_mut(symbol)?;
+        ship.nav.status = ShipNavStatus::Docked;
+        Ok(json!({ "nav": ship.nav }))
+    }
+
+    pub fn set_flight_mode(
+        &mut self,
+        symbol: &str,
+        mode: Option<ShipNavFlightMode>,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_mut(symbol)?;
+        if let Some(mode) = mode {
+            ship.nav.flight_mode = mode;
+        }
+        Ok(json!(ship.nav))
+    }
+
+    pub fn navigate(&mut self, symbol: &str, destination: &str) -> ApiResult<Value> {
+        let ship = self.ship_in_orbit(symbol)?;
+        let origin = self.waypoint(&ship.nav.waypoint_symbol)?.clone();
+        let destination = self.waypoint(destination)?.clone();
+
+        if destination.system_symbol != origin.system_symbol {
+            return Err(ApiError::new(
+                NAVIGATE_OUTSIDE_SYSTEM,
+                format!("{} is outside the ship's system.", destination.symbol),
+            ));
+        }
+        if destination.symbol == origin.symbol {
+            return Err(ApiError::new(
+                NAVIGATE_SAME_DESTINATION,
+                format!("Ship {symbol} is already at {}.", destination.symbol),
+            ));
+        }
+
+        let distance =
+            (((destination.x - origin.x).pow(2) + (destination.y - origin.y).pow(2)) as f64).sqrt();
+        let rounded = distance.round().max(1.0) as i32;
+        let mode = ship.nav.flight_mode;
+        let fuel_required = if ship.fuel.capacity == 0 {
+            0
+        } else {
+            match mode {
+                ShipNavFlightMode::Drift => 1,
+                ShipNavFlightMode::Stealth | ShipNavFlightMode::Cruise => rounded,
+                ShipNavFlightMode::Burn => rounded * 2,
+            }
+        };
+        if fuel_required > ship.fuel.current {
+            return Err(ApiError::new(
+                NAVIGATE_INSUFFICIENT_FUEL,
+                format!(
+                    "Ship {symbol} doesn't have enough fuel to reach {}.",
+                    destination.symbol
+                ),
+            )
+            .with_data(json!({
+                "fuelRequired": fuel_required,
+                "fuelAvailable": ship.fuel.current,
+            })));
+        }
+
+        let multiplier = match mode {
+            ShipNavFlightMode::Drift => 250.0,
+            ShipNavFlightMode::Stealth => 30.0,
+            ShipNavFlightMode::Cruise => 25.0,
+            ShipNavFlightMode::Burn => 12.5,
+        };
+        let seconds =
+            (rounded as f64 * (multiplier / ship.engine.speed.max(1) as f64) + 15.0).round() as i64;
+
+        let now = Utc::now();
+        let route_waypoint = |w: &Waypoint| {
+            Box::new(ShipNavRouteWaypoint::new(
+                w.symbol.clone(),
+                w.r#type,
+                w.system_symbol.clone(),
+                w.x,
+                w.y,
+            ))
+        };
+
+        let ship = self.ship_mut(symbol)?;
+        ship.fuel.current -= fuel_required;
+        ship.fuel.consumed = Some(Box::new(spacedust::models::ShipFuelConsumed::new(
+            fuel_required,
+            timestamp(now),
+        )));
+        ship.nav.status = ShipNavStatus::InTransit;
+        ship.nav.waypoint_symbol = destination.symbol.clone();
+        ship.nav.route.origin = route_waypoint(&origin);
+        ship.nav.route.departure = route_waypoint(&origin);
+        ship.nav.route.destination = route_waypoint(&destination);
+        ship.nav.route.departure_time = timestamp(now);
+        ship.nav.route.arrival = timestamp(now + chrono::Duration::seconds(seconds));
+
+        Ok(json!({ "fuel": ship.fuel, "nav": ship.nav }))
+    }
+
+    // Markets
+
+    fn market_good(
+        &mut self,
+        waypoint_symbol: &str,
+        trade_symbol: TradeSymbol,
+    ) -> ApiResult<&mut TradeGoodState> {
+        let market = self.markets.get_mut(waypoint_symbol).ok_or_else(|| {
+            ApiError::new(
+                MARKET_NOT_FOUND,
+                format!("Market not found at {waypoint_symbol}."),
+            )
+        })?;
+        market
+            .goods
+            .iter_mut()
+            .find(|g| g.spec.symbol == trade_symbol)
+            .ok_or_else(|| {
+                ApiError::new(
+                    MARKET_TRADE_NOT_SOLD,
+                    format!(
+                        "Market at {waypoint_symbol} doesn't trade {}.",
+                        trade_symbol.to_string()
+                    ),
+                )
+            })
+    }
+
+    fn record_transaction(&mut self, waypoint_symbol: &str, transaction: &Value) {
+        if let Some(market) = self.markets.get_mut(waypoint_symbol) {
+            market.transactions.push(transaction.clone());
+            let excess = market.transactions.len().saturating_sub(20);
+            market.transactions.drain(..excess);
+        }
+    }
+
+    pub fn sell(
+        &mut self,
+        symbol: &str,
+        trade_symbol: TradeSymbol,
+        units: i32,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_docked(symbol)?;
+        let waypoint_symbol = ship.nav.waypoint_symbol.clone();
+        let held = cargo_units(ship, trade_symbol);
+
+        let good = self.market_good(&waypoint_symbol, trade_symbol)?;
+        if good.spec.r#type == market_trade_good::Type::Export {
+            return Err(ApiError::new(
+                MARKET_TRADE_NOT_SOLD,
+                format!(
+                    "Market at {waypoint_symbol} only sells {}.",
+                    trade_symbol.to_string()
+                ),
+            ));
+        }
+        if units > good.spec.trade_volume {
+            return Err(trade_unit_limit(
+                trade_symbol,
+                units,
+                good.spec.trade_volume,
+            ));
+        }
+        if held < units {
+            return Err(cargo_missing(symbol, trade_symbol, units, held));
+        }
+
+        let price = good.trade_good().sell_price;
+        good.trade(units, true);
+
+        let ship = self.ship_mut(symbol)?;
+        remove_cargo(ship, trade_symbol, units);
+        let cargo = json!(ship.cargo);
+        self.agent.credits += (price * units) as i64;
+
+        let transaction =
+            self.transaction(&waypoint_symbol, symbol, trade_symbol, "SELL", units, price);
+        Ok(json!({ "agent": self.agent, "cargo": cargo, "transaction": transaction }))
+    }
+
+    pub fn purchase(
+        &mut self,
+        symbol: &str,
+        trade_symbol: TradeSymbol,
+        units: i32,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_docked(symbol)?;
+        let waypoint_symbol = ship.nav.waypoint_symbol.clone();
+        let free = ship.cargo.capacity - ship.cargo.units;
+        let (capacity, held) = (ship.cargo.capacity, ship.cargo.units);
+        let credits = self.agent.credits;
+
+        let good = self.market_good(&waypoint_symbol, trade_symbol)?;
+        if good.spec.r#type == market_trade_good::Type::Import {
+            return Err(ApiError::new(
+                MARKET_TRADE_NO_PURCHASE,
+                format!(
+                    "Market at {waypoint_symbol} only buys {}.",
+                    trade_symbol.to_string()
+                ),
+            ));
+        }
+        if units > good.spec.trade_volume {
+            return Err(trade_unit_limit(
+                trade_symbol,
+                units,
+                good.spec.trade_volume,
+            ));
+        }
+        if units > free {
+            return Err(cargo_exceeds_limit(symbol, capacity, held, units));
+        }
+        let price = good.trade_good().purchase_price;
+        let total = (price * units) as i64;
+        if total > credits {
+            return Err(insufficient_credits(credits, total));
+        }
+        good.trade(units, false);
+
+        let ship = self.ship_mut(symbol)?;
+        add_cargo(ship, trade_symbol, units);
+        let cargo = json!(ship.cargo);
+        self.agent.credits -= total;
+
+        let transaction = self.transaction(
+            &waypoint_symbol,
+            symbol,
+            trade_symbol,
+            "PURCHASE",
+            units,
+            price,
+        );
+        Ok(json!({ "agent": self.agent, "cargo": cargo, "transaction": transaction }))
+    }
+
+    fn transaction(
+        &mut self,
+        waypoint_symbol: &str,
+        ship_symbol: &str,
+        trade_symbol: TradeSymbol,
+        r#type: &str,
+        units: i32,
+        price: i32,
+    ) -> Value {
+        let transaction = json!({
+            "waypointSymbol": waypoint_symbol,
+            "shipSymbol": ship_symbol,
+            "tradeSymbol": trade_symbol,
+            "type": r#type,
+            "units": units,
+            "pricePerUnit": price,
+            "totalPrice": price * units,
+            "timestamp": timestamp(Utc::now()),
+        });
+        self.record_transaction(waypoint_symbol, &transaction);
+        transaction
+    }
+
+    /// Fills the tank, or adds `units` of fuel. Fuel is sold by the market
+    /// unit, which is 100 units of ship fuel, rounded up.
+    pub fn refuel(
+        &mut self,
+        symbol: &str,
+        units: Option<i32>,
+        from_cargo: bool,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_docked(symbol)?;
+        let waypoint_symbol = ship.nav.waypoint_symbol.clone();
+        let wanted = units
+            .unwrap_or(ship.fuel.capacity - ship.fuel.current)
+            .min(ship.fuel.capacity - ship.fuel.current)
+            .max(0);
+        let market_units = (wanted + 99) / 100;
+
+        if from_cargo {
+            let held = cargo_units(ship, TradeSymbol::Fuel);
+            if held < market_units {
+                return Err(cargo_missing(symbol, TradeSymbol::Fuel, market_units, held));
+            }
+            let ship = self.ship_mut(symbol)?;
+            remove_cargo(ship, TradeSymbol::Fuel, market_units);
+            ship.fuel.current += wanted;
+            let fuel = json!(ship.fuel);
+            let transaction = self.transaction(
+                &waypoint_symbol,
+                symbol,
+                TradeSymbol::Fuel,
+                "PURCHASE",
+                market_units,
+                0,
+            );
+            return Ok(json!({ "agent": self.agent, "fuel": fuel, "transaction": transaction }));
+        }
+
+        let credits = self.agent.credits;
+        let good = self
+            .market_good(&waypoint_symbol, TradeSymbol::Fuel)
+            .map_err(|_| {
+                ApiError::new(
+                    4226,
+                    format!("There's no fuel for sale at {waypoint_symbol}."),
+                )
+            })?;
+        let price = good.trade_good().purchase_price;
+        let total = (price * market_units) as i64;
+        if total > credits {
+            return Err(insufficient_credits(credits, total));
+        }
+        good.trade(market_units, false);
+
+        let ship = self.ship_mut(symbol)?;
+        ship.fuel.current += wanted;
+        let fuel = json!(ship.fuel);
+        self.agent.credits -= total;
+        let transaction = self.transaction(
+            &waypoint_symbol,
+            symbol,
+            TradeSymbol::Fuel,
+            "PURCHASE",
+            market_units,
+            price,
+        );
+        Ok(json!({ "agent": self.agent, "fuel": fuel, "transaction": transaction }))
+    }
+
+    // Cargo
+
+    pub fn cargo(&self, symbol: &str) -> ApiResult<Value> {
+        Ok(json!(self.ship(symbol)?.cargo))
+    }
+
+    pub fn jettison(
+        &mut self,
+        symbol: &str,
+        trade_symbol: TradeSymbol,
+        units: i32,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_not_in_transit(symbol)?;
+        let held = cargo_units(ship, trade_symbol);
+        if held < units {
+            return Err(cargo_missing(symbol, trade_symbol, units, held));
+        }
+        let ship = self.ship_mut(symbol)?;
+        remove_cargo(ship, trade_symbol, units);
+        Ok(json!({ "cargo": ship.cargo }))
+    }
+
+    pub fn transfer(
+        &mut self,
+        symbol: &str,
+        trade_symbol: TradeSymbol,
+        units: i32,
+        to: &str,
+    ) -> ApiResult<Value> {
+        if symbol == to {
+            return Err(ApiError::new(
+                4233,
+                "Ships can't transfer cargo to themselves.",
+            ));
+        }
+        let from_ship = self.ship_not_in_transit(symbol)?;
+        let held = cargo_units(from_ship, trade_symbol);
+        let location = (from_ship.nav.waypoint_symbol.clone(), from_ship.nav.status);
+        let to_ship = self.ship_not_in_transit(to).map_err(|_| {
+            ApiError::new(4231, format!("Ship {to} isn't available to transfer to."))
+        })?;
+        if (to_ship.nav.waypoint_symbol.clone(), to_ship.nav.status) != location {
+            return Err(ApiError::new(
+                4234,
+                format!("Ships {symbol} and {to} must be at the same waypoint, both docked or both in orbit."),
+            ));
+        }
+        if held < units {
+            return Err(cargo_missing(symbol, trade_symbol, units, held));
+        }
+        if to_ship.cargo.units + units > to_ship.cargo.capacity {
+            return Err(cargo_exceeds_limit(
+                to,
+                to_ship.cargo.capacity,
+                to_ship.cargo.units,
+                units,
+            ));
+        }
+
+        add_cargo(self.ship_mut(to)?, trade_symbol, units);
+        let ship = self.ship_mut(symbol)?;
+        remove_cargo(ship, trade_symbol, units);
+        Ok(json!({ "cargo": ship.cargo }))
+    }
+
+    // Mining
+
+    pub fn survey(&mut self, symbol: &str) -> ApiResult<Value> {
+        let ship = self.ship_ready(symbol)?;
+        let surveyors: Vec<i32> = ship
+            .mounts
+            .iter()
+            .filter(|m| name(&m.symbol).starts_with("MOUNT_SURVEYOR"))
+            .map(|m| m.strength.unwrap_or(1))
+            .collect();
+        if surveyors.is_empty() {
+            return Err(ApiError::new(
+                4240,
+                format!("Ship {symbol} has no surveyor mounted."),
+            ));
+        }
+        let waypoint = self.waypoint(&ship.nav.waypoint_symbol)?.clone();
+        let deposits = templates::deposits(&waypoint);
+        if deposits.is_empty() {
+            return Err(ApiError::new(
+                4222,
+                format!("There's nothing to survey at {}.", waypoint.symbol),
+            ));
+        }
+
+        let mut surveys = vec![];
+        for strength in surveyors {
+            let signature = format!("{}-{}", waypoint.symbol, self.next_id("survey"));
+            let size = match strength {
+                1 => "SMALL",
+                2 => "MODERATE",
+                _ => "LARGE",
+            };
+            // Rotate which deposits turn up so repeated surveys differ
+            let offset = self.sequence as usize % deposits.len();
+            let picked: Vec<Value> = (0..5)
+                .map(|i| json!({ "symbol": deposits[(offset + i / 2) % deposits.len()] }))
+                .collect();
+            let survey: Survey = serde_json::from_value(json!({
+                "signature": signature,
+                "symbol": waypoint.symbol,
+                "deposits": picked,
+                "expiration": timestamp(Utc::now() + chrono::Duration::minutes(30)),
+                "size": size,
+            }))
+            .unwrap();
+            self.surveys.insert(
+                signature,
+                SurveyState {
+                    extractions_left: survey_extractions(survey.size),
+                    survey: survey.clone(),
+                },
+            );
+            surveys.push(survey);
+        }
+
+        let cooldown = self.start_cooldown(symbol, SURVEY_COOLDOWN)?;
+        Ok(json!({ "cooldown": cooldown, "surveys": surveys }))
+    }
+
+    pub fn extract(&mut self, symbol: &str, survey: Option<Survey>) -> ApiResult<Value> {
+        let ship = self.ship_ready(symbol)?;
+        let strength: i32 = ship
+            .mounts
+            .iter()
+            .filter(|m| name(&m.symbol).starts_with("MOUNT_MINING_LASER"))
+            .map(|m| m.strength.unwrap_or(10))
+            .sum();
+        if strength == 0 {
+            return Err(ApiError::new(
+                4227,
+                format!("Ship {symbol} has no mining laser mounted."),
+            ));
+        }
+        let waypoint = self.waypoint(&ship.nav.waypoint_symbol)?.clone();
+        let free = ship.cargo.capacity - ship.cargo.units;
+        if free <= 0 {
+            return Err(ApiError::new(
+                SHIP_CARGO_FULL,
+                format!("Ship {symbol} has no room left."),
+            ));
+        }
+
+        let candidates: Vec<TradeSymbol> = match &survey {
+            Some(survey) => {
+                let state = self.surveys.get_mut(&survey.signature).ok_or_else(|| {
+                    ApiError::new(
+                        SHIP_SURVEY_EXPIRATION,
+                        format!("Survey {} has expired.", survey.signature),
+                    )
+                })?;
+                if state.survey != *survey || survey.symbol != waypoint.symbol {
+                    return Err(ApiError::new(
+                        SHIP_SURVEY_VERIFICATION,
+                        format!("Survey {} isn't valid here.", survey.signature),
+                    ));
+                }
+                if state.extractions_left <= 0 {
+                    return Err(ApiError::new(
+                        SHIP_SURVEY_EXHAUSTED,
+                        format!("Survey {} has been exhausted.", survey.signature),
+                    ));
+                }
+                state.extractions_left -= 1;
+                survey
+                    .deposits
+                    .iter()
+                    .filter_map(|d| serde_json::from_value(json!(d.symbol)).ok())
+                    .collect()
+            }
+            None => templates::deposits(&waypoint).to_vec(),
+        };
+        if candidates.is_empty() {
+            return Err(ApiError::new(
+                4205,
+                format!("There's nothing to extract at {}.", waypoint.symbol),
+            ));
+        }
+
+        self.sequence += 1;
+        let trade_symbol = candidates[self.sequence as usize % candidates.len()];
+        // Surveys point the laser at the good stuff
+        let bonus = if survey.is_some() { 2 } else { 1 };
+        let units = (strength / 2 * bonus).clamp(1, free);
+
+        let ship = self.ship_mut(symbol)?;
+        add_cargo(ship, trade_symbol, units);
+        let cargo = json!(ship.cargo);
+        let cooldown = self.start_cooldown(symbol, EXTRACT_COOLDOWN)?;
+        Ok(json!({
+            "cooldown": cooldown,
+            "extraction": {
+                "shipSymbol": symbol,
+                "yield": { "symbol": trade_symbol, "units": units },
+            },
+            "cargo": cargo,
+        }))
+    }
+
+    pub fn siphon(&mut self, symbol: &str) -> ApiResult<Value> {
+        let ship = self.ship_ready(symbol)?;
+        let strength: i32 = ship
+            .mounts
+            .iter()
+            .filter(|m| name(&m.symbol).starts_with("MOUNT_GAS_SIPHON"))
+            .map(|m| m.strength.unwrap_or(10))
+            .sum();
+        if strength == 0 {
+            return Err(ApiError::new(
+                4227,
+                format!("Ship {symbol} has no gas siphon mounted."),
+            ));
+        }
+        let waypoint = self.waypoint(&ship.nav.waypoint_symbol)?.clone();
+        let gases = templates::gases(&waypoint);
+        if gases.is_empty() {
+            return Err(ApiError::new(
+                4205,
+                format!("There's nothing to siphon at {}.", waypoint.symbol),
+            ));
+        }
+        let free = ship.cargo.capacity - ship.cargo.units;
+        if free <= 0 {
+            return Err(ApiError::new(
+                SHIP_CARGO_FULL,
+                format!("Ship {symbol} has no room left."),
+            ));
+        }
+
+        self.sequence += 1;
+        let trade_symbol = gases[self.sequence as usize % gases.len()];
+        let units = (strength / 2).clamp(1, free);
+
+        let ship = self.ship_mut(symbol)?;
+        add_cargo(ship, trade_symbol, units);
+        let cargo = json!(ship.cargo);
+        let cooldown = self.start_cooldown(symbol, EXTRACT_COOLDOWN)?;
+        Ok(json!({
+            "cooldown": cooldown,
+            "siphon": {
+                "shipSymbol": symbol,
+                "yield": { "symbol": trade_symbol, "units": units },
+            },
+            "cargo": cargo,
+        }))
+    }
+
+    /// Turns 30 units of ore into 10 units of metal (or hydrocarbons into
+    /// fuel).
+    pub fn refine(&mut self, symbol: &str, produce: TradeSymbol) -> ApiResult<Value> {
+        let ship = self.ship_not_in_transit(symbol)?;
+        if ship.cooldown.remaining_seconds > 0 {
+            return Err(ApiError::new(
+                COOLDOWN_CONFLICT,
+                format!("Ship {symbol} is still cooling down."),
+            )
+            .with_data(json!({ "cooldown": ship.cooldown })));
+        }
+        let has_refinery = ship.modules.iter().any(|m| {
+            let module = name(&m.symbol);
+            module == "MODULE_ORE_REFINERY_I" || module == "MODULE_FUEL_REFINERY_I"
+        });
+        if !has_refinery {
+            return Err(ApiError::new(
+                4239,
+                format!("Ship {symbol} has no refinery module."),
+            ));
+        }
+        let Some(input) = refinery_input(produce) else {
+            return Err(ApiError::new(
+                4237,
+                format!("Refineries can't produce {}.", produce.to_string()),
+            ));
+        };
+        let held = cargo_units(ship, input);
+        if held < 30 {
+            return Err(cargo_missing(symbol, input, 30, held));
+        }
+
+        let ship = self.ship_mut(symbol)?;
+        remove_cargo(ship, input, 30);
+        add_cargo(ship, produce, 10);
+        let cargo = json!(ship.cargo);
+        let cooldown = self.start_cooldown(symbol, REFINE_COOLDOWN)?;
+        Ok(json!({
+            "cargo": cargo,
+            "cooldown": cooldown,
+            "produced": [{ "tradeSymbol": produce, "units": 10 }],
+            "consumed": [{ "tradeSymbol": input, "units": 30 }],
+        }))
+    }
+
+    // Shipyards
+
+    pub fn purchase_ship(
+        &mut self,
+        ship_type: ShipType,
+        waypoint_symbol: &str,
+    ) -> ApiResult<Value> {
+        self.shipyard(waypoint_symbol)?;
+        if !self.has_ship_at(waypoint_symbol) {
+            return Err(ApiError::new(
+                4701,
+                format!("You need a ship at {waypoint_symbol} to buy from its shipyard."),
+            ));
+        }
+        let price = templates::SHIPYARD_SHIPS
+            .iter()
+            .find(|(t, _)| *t == ship_type)
+            .map(|(_, price)| *price)
+            .ok_or_else(|| {
+                ApiError::new(
+                    4702,
+                    format!(
+                        "The shipyard at {waypoint_symbol} doesn't sell {}.",
+                        ship_type.to_string()
+                    ),
+                )
+            })?;
+        if price as i64 > self.agent.credits {
+            return Err(insufficient_credits(self.agent.credits, price as i64));
+        }
+
+        let waypoint = self.waypoint(waypoint_symbol)?.clone();
+        let symbol = format!("{}-{:X}", self.agent.symbol, self.ships.len() + 1);
+        let ship = templates::ship(ship_type, &symbol, &waypoint, &timestamp(Utc::now()));
+        self.ships.insert(symbol.clone(), ship.clone());
+        self.agent.credits -= price as i64;
+        self.agent.ship_count = Some(self.ships.len() as i32);
+
+        let transaction = json!({
+            "waypointSymbol": waypoint_symbol,
+            "shipSymbol": symbol,
+            "price": price,
+            "agentSymbol": self.agent.symbol,
+            "timestamp": timestamp(Utc::now()),
+        });
+        self.shipyard_transactions.push(transaction.clone());
+        Ok(json!({ "agent": self.agent, "ship": ship, "transaction": transaction }))
+    }
+
+    // Contracts
+
+    pub fn accept_contract(&mut self, id: &str) -> ApiResult<Value> {
+        let contract = self.contract_mut(id)?;
+        if contract.accepted {
+            return Err(ApiError::new(
+                4501,
+                format!("Contract {id} has already been accepted."),
+            ));
+        }
+        contract.accepted = true;
+        let contract = contract.clone();
+        self.agent.credits += contract.terms.payment.on_accepted as i64;
+        Ok(json!({ "agent": self.agent, "contract": contract }))
+    }
+
+    pub fn deliver_contract(
+        &mut self,
+        id: &str,
+        ship_symbol: &str,
+        trade_symbol: &str,
+        units: i32,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_docked(ship_symbol)?;
+        let location = ship.nav.waypoint_symbol.clone();
+        let good: TradeSymbol = serde_json::from_value(json!(trade_symbol))
+            .map_err(|_| ApiError::new(422, format!("{trade_symbol} isn't a trade good.")))?;
+        let held = cargo_units(ship, good);
+
+        let contract = self.contract(id)?;
+        if !contract.accepted {
+            return Err(ApiError::new(
+                4505,
+                format!("Contract {id} hasn't been accepted."),
+            ));
+        }
+        if contract.fulfilled {
+            return Err(ApiError::new(
+                4504,
+                format!("Contract {id} has already been fulfilled."),
+            ));
+        }
+        let Some(term) = contract
+            .terms
+            .deliver
+            .iter()
+            .flatten()
+            .find(|d| d.trade_symbol == trade_symbol)
+        else {
+            return Err(ApiError::new(
+                4508,
+                format!("Contract {id} doesn't need {trade_symbol}."),
+            ));
+        };
+        if term.destination_symbol != location {
+            return Err(ApiError::new(
+                4510,
+                format!(
+                    "{trade_symbol} for contract {id} goes to {}.",
+                    term.destination_symbol
+                ),
+            ));
+        }
+        if term.units_fulfilled + units > term.units_required {
+            return Err(ApiError::new(
+                4509,
+                format!(
+                    "Contract {id} only needs {} more {trade_symbol}.",
+                    term.units_required - term.units_fulfilled
+                ),
+            ));
+        }
+        if held < units {
+            return Err(cargo_missing(ship_symbol, good, units, held));
+        }
+
+        let ship = self.ship_mut(ship_symbol)?;
+        remove_cargo(ship, good, units);
+        let cargo = json!(ship.cargo);
+        let contract = self.contract_mut(id)?;
+        for term in contract.terms.deliver.iter_mut().flatten() {
+            if term.trade_symbol == trade_symbol {
+                term.units_fulfilled += units;
+            }
+        }
+        Ok(json!({ "contract": contract, "cargo": cargo }))
+    }
+
+    pub fn fulfill_contract(&mut self, id: &str) -> ApiResult<Value> {
+        let contract = self.contract_mut(id)?;
+        if !contract.accepted {
+            return Err(ApiError::new(
+                4505,
+                format!("Contract {id} hasn't been accepted."),
+            ));
+        }
+        if contract.fulfilled {
+            return Err(ApiError::new(
+                4504,
+                format!("Contract {id} has already been fulfilled."),
+            ));
+        }
+        let done = contract
+            .terms
+            .deliver
+            .iter()
+            .flatten()
+            .all(|d| d.units_fulfilled >= d.units_required);
+        if !done {
+            return Err(ApiError::new(
+                4502,
+                format!("Contract {id} still has deliveries outstanding."),
+            ));
+        }
+        contract.fulfilled = true;
+        let contract = contract.clone();
+        self.agent.credits += contract.terms.payment.on_fulfilled as i64;
+        Ok(json!({ "agent": self.agent, "contract": contract }))
+    }
+
+    pub fn negotiate_contract(&mut self, ship_symbol: &str) -> ApiResult<Value> {
+        let ship = self.ship_not_in_transit(ship_symbol)?;
+        let waypoint = self.waypoint(&ship.nav.waypoint_symbol)?;
+        if waypoint.faction.is_none() {
+            return Err(ApiError::new(
+                4510,
+                format!("There's nobody to negotiate with at {}.", waypoint.symbol),
+            ));
+        }
+        if self.contracts.iter().any(|c| !c.fulfilled) {
+            return Err(ApiError::new(
+                4511,
+                "Finish your current contract before negotiating another.",
+            ));
+        }
+
+        // Alternate between the goods the starting system is good for
+        let goods = [
+            TradeSymbol::CopperOre,
+            TradeSymbol::AluminumOre,
+            TradeSymbol::IronOre,
+        ];
+        let trade_symbol = goods[self.contracts.len() % goods.len()];
+        let id = self.next_id("contract");
+        let contract = templates::contract(&id, trade_symbol, 40, Utc::now());
+        self.contracts.push(contract.clone());
+        Ok(json!({ "contract": contract }))
+    }
+
+    // Construction
+
+    pub fn supply_construction(
+        &mut self,
+        waypoint_symbol: &str,
+        ship_symbol: &str,
+        trade_symbol: &str,
+        units: i32,
+    ) -> ApiResult<Value> {
+        let ship = self.ship_docked(ship_symbol)?;
+        if ship.nav.waypoint_symbol != waypoint_symbol {
+            return Err(ApiError::new(
+                4801,
+                format!("Ship {ship_symbol} must be docked at {waypoint_symbol}."),
+            ));
+        }
+        let good: TradeSymbol = serde_json::from_value(json!(trade_symbol))
+            .map_err(|_| ApiError::new(422, format!("{trade_symbol} isn't a trade good.")))?;
+        let held = cargo_units(ship, good);
+        let construction = self.construction(waypoint_symbol)?;
+        let Some(material) = construction
+            .materials
+            .iter()
+            .find(|m| m.trade_symbol == good)
+        else {
+            return Err(ApiError::new(
+                4802,
+                format!("{waypoint_symbol} doesn't need {trade_symbol}."),
+            ));
+        };
+        if material.fulfilled + units > material.required {
+            return Err(ApiError::new(
+                4803,
+                format!(
+                    "{waypoint_symbol} only needs {} more {trade_symbol}.",
+                    material.required - material.fulfilled
+                ),
+            ));
+        }
+        if held < units {
+            return Err(cargo_missing(ship_symbol, good, units, held));
+        }
+
+        let ship = self.ship_mut(ship_symbol)?;
+        remove_cargo(ship, good, units);
+        let cargo = json!(ship.cargo);
+        let construction = self.constructions.get_mut(waypoint_symbol).unwrap();
+        for material in construction.materials.iter_mut() {
+            if material.trade_symbol == good {
+                material.fulfilled += units;
+            }
+        }
+        construction.is_complete = construction
+            .materials
+            .iter()
+            .all(|m| m.fulfilled >= m.required);
+        Ok(json!({ "construction": construction, "cargo": cargo }))
+    }
+}
+
+/// The wire name of one of the generated enums, not all of which implement
+/// `Display`.
+fn name(value: &impl serde::Serialize) -> String {
+    match serde_json::to_value(value).unwrap() {
+        Value::String(name) => name,
+        other => other.to_string(),
+    }
+}
+
+fn has_trait(waypoint: &Waypoint, symbol: &str) -> bool {
+    waypoint.traits.iter().any(|t| name(&t.symbol) == symbol)
+}
+
+fn refinery_input(produce: TradeSymbol) -> Option<TradeSymbol> {
+    use TradeSymbol::*;
+    Some(match produce {
+        Iron => IronOre,
+        Copper => CopperOre,
+        Silver => SilverOre,
+        Gold => GoldOre,
+        Aluminum => AluminumOre,
+        Platinum => PlatinumOre,
+        Uranite => UraniteOre,
+        Meritium => MeritiumOre,
+        Fuel => Hydrocarbon,
+        _ => return None,
+    })
+}
+
+fn cargo_units(ship: &Ship, trade_symbol: TradeSymbol) -> i32 {
+    ship.cargo
+        .inventory
+        .iter()
+        .filter(|i| i.symbol == trade_symbol)
+        .map(|i| i.units)
+        .sum()
+}
+
+fn add_cargo(ship: &mut Ship, trade_symbol: TradeSymbol, units: i32) {
+    ship.cargo.units += units;
+    match ship
+        .cargo
+        .inventory
+        .iter_mut()
+        .find(|i| i.symbol == trade_symbol)
+    {
+        Some(item) => item.units += units,
+        None => ship.cargo.inventory.push(ShipCargoItem::new(
+            trade_symbol,
+            trade_symbol.to_string(),
+            String::new(),
+            units,
+        )),
+    }
+}
+
+fn remove_cargo(ship: &mut Ship, trade_symbol: TradeSymbol, units: i32) {
+    ship.cargo.units -= units;
+    for item in ship.cargo.inventory.iter_mut() {
+        if item.symbol == trade_symbol {
+            item.units -= units;
+        }
+    }
+    ship.cargo.inventory.retain(|i| i.units > 0);
+}
+
+fn trade_unit_limit(trade_symbol: TradeSymbol, units: i32, trade_volume: i32) -> ApiError {
+    ApiError::new(
+        MARKET_TRADE_UNIT_LIMIT,
+        format!(
+            "Market only trades {trade_volume} units of {} at a time.",
+            trade_symbol.to_string()
+        ),
+    )
+    .with_data(json!({
+        "tradeSymbol": trade_symbol,
+        "units": units,
+        "tradeVolume": trade_volume,
+    }))
+}
+
+fn cargo_missing(ship_symbol: &str, trade_symbol: TradeSymbol, units: i32, held: i32) -> ApiError {
+    ApiError::new(
+        SHIP_CARGO_UNIT_COUNT,
+        format!(
+            "Ship {ship_symbol} has {held} units of {}, not {units}.",
+            trade_symbol.to_string()
+        ),
+    )
+    .with_data(json!({
+        "shipSymbol": ship_symbol,
+        "tradeSymbol": trade_symbol,
+        "cargoUnits": held,
+        "unitsToRemove": units,
+    }))
+}
+
+fn cargo_exceeds_limit(ship_symbol: &str, capacity: i32, held: i32, units: i32) -> ApiError {
+    ApiError::new(
+        SHIP_CARGO_EXCEEDS_LIMIT,
+        format!("Ship {ship_symbol} doesn't have room for {units} more units."),
+    )
+    .with_data(json!({
+        "cargoCapacity": capacity,
+        "cargoUnits": held,
+        "unitsToAdd": units,
+    }))
+}
+
+fn insufficient_credits(credits: i64, total: i64) -> ApiError {
+    ApiError::new(
+        MARKET_TRADE_INSUFFICIENT_CREDITS,
+        format!("That costs {total} credits, and you have {credits}."),
+    )
+    .with_data(json!({
+        "creditsAvailable": credits,
+        "totalPrice": total,
+    }))
+}
@@ -0,0 +1,63 @@
+//! Records a session against the mock server, then replays it with the
+//! server gone.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use spacedust::apis::configuration::Configuration;
+use spacedust::apis::{agents_api, fleet_api};
+use spacedust::cassette::{self, Cassette};
+
+async fn start() -> SocketAddr {
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+        .serve(spacetraders_mock::app().into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+fn conf(base_path: String, cassette: Cassette) -> Configuration {
+    let mut conf = Configuration::new();
+    conf.base_path = base_path;
+    conf.bearer_access_token = Some(spacetraders_mock::default_token());
+    conf.client = cassette::client(cassette);
+    conf
+}
+
+fn cassette_path() -> PathBuf {
+    std::env::temp_dir().join(format!("spacedust-cassette-{}.json", std::process::id()))
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    let path = cassette_path();
+    let addr = start().await;
+
+    let recording = conf(
+        format!("http://{addr}/v2"),
+        Cassette::record(&path).unwrap(),
+    );
+    let agent = agents_api::get_my_agent(&recording).await.unwrap().data;
+    let ships = fleet_api::get_my_ships(&recording, Some(1), Some(20))
+        .await
+        .unwrap()
+        .data;
+
+    // Nothing listens here, so anything not on the tape fails
+    let replay = Cassette::replay(&path).unwrap();
+    let replaying = conf("http://127.0.0.1:9/v2".to_string(), replay.clone());
+    let replayed_ships = fleet_api::get_my_ships(&replaying, Some(1), Some(20))
+        .await
+        .unwrap()
+        .data;
+    let replayed_agent = agents_api::get_my_agent(&replaying).await.unwrap().data;
+    assert!(replay.unused().is_empty());
+
+    assert_eq!(replayed_agent, agent);
+    assert_eq!(replayed_ships, ships);
+
+    // Each recording is used once
+    assert!(agents_api::get_my_agent(&replaying).await.is_err());
+
+    let _ = std::fs::remove_file(path);
+}
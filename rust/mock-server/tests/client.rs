@@ -0,0 +1,136 @@
+//! The spacedust client against a mock server on a spare port, the way the
+//! app's own tests would use it.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use spacedust::apis::api_error::ApiErrorKind;
+use spacedust::apis::configuration::Configuration;
+use spacedust::apis::{agents_api, default_api, fleet_api};
+use spacedust::models::{
+    ExtractResourcesRequest, FactionSymbol, NavigateShipRequest, RegisterRequest, ShipNavStatus,
+};
+
+use spacetraders_mock::universe::Universe;
+use spacetraders_mock::{templates, UniverseShared};
+
+async fn start() -> SocketAddr {
+    start_with(Arc::new(Mutex::new(Universe::default()))).await
+}
+
+/// Serves `universe`, so the test can reach in and skip the waiting.
+async fn start_with(universe: UniverseShared) -> SocketAddr {
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+        .serve(spacetraders_mock::router(universe).into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+fn conf(addr: SocketAddr, token: Option<String>) -> Configuration {
+    let mut conf = Configuration::new();
+    conf.base_path = format!("http://{addr}/v2");
+    conf.bearer_access_token = token;
+    conf
+}
+
+#[tokio::test]
+async fn register_then_fly() {
+    let addr = start().await;
+
+    let request = RegisterRequest::new(FactionSymbol::Cosmic, "TESTER".to_string());
+    let registered = default_api::register(&conf(addr, None), Some(request))
+        .await
+        .unwrap();
+    let conf = conf(addr, Some(registered.data.token));
+
+    let agent = agents_api::get_my_agent(&conf).await.unwrap().data;
+    assert_eq!(agent.symbol, "TESTER");
+    assert_eq!(agent.headquarters, templates::HEADQUARTERS);
+
+    let ship = registered.data.ship;
+    assert_eq!(ship.nav.waypoint_symbol, templates::HEADQUARTERS);
+    if ship.nav.status == ShipNavStatus::Docked {
+        fleet_api::orbit_ship(&conf, &ship.symbol).await.unwrap();
+    }
+    let request = NavigateShipRequest::new("X1-MOCK-B1".to_string());
+    let nav = fleet_api::navigate_ship(&conf, &ship.symbol, Some(request))
+        .await
+        .unwrap()
+        .data
+        .nav;
+    assert_eq!(nav.status, ShipNavStatus::InTransit);
+    assert_eq!(nav.route.destination.symbol, "X1-MOCK-B1");
+}
+
+#[tokio::test]
+async fn old_token_stops_working() {
+    let addr = start().await;
+    let old = conf(addr, Some(spacetraders_mock::default_token()));
+    agents_api::get_my_agent(&old).await.unwrap();
+
+    let request = RegisterRequest::new(FactionSymbol::Cosmic, "TESTER".to_string());
+    default_api::register(&conf(addr, None), Some(request))
+        .await
+        .unwrap();
+
+    let err = agents_api::get_my_agent(&old).await.unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(ApiErrorKind::Unauthorized));
+}
+
+#[tokio::test]
+async fn taken_symbol_is_a_conflict() {
+    let addr = start().await;
+    let request = RegisterRequest::new(FactionSymbol::Cosmic, "TESTER".to_string());
+    default_api::register(&conf(addr, None), Some(request.clone()))
+        .await
+        .unwrap();
+
+    let err = default_api::register(&conf(addr, None), Some(request))
+        .await
+        .unwrap_err();
+    assert_eq!(err.api_error_kind(), Some(ApiErrorKind::SymbolTaken));
+}
+
+#[tokio::test]
+async fn extract_with_survey() {
+    let universe: UniverseShared = Arc::new(Mutex::new(Universe::default()));
+    let addr = start_with(universe.clone()).await;
+    let conf = conf(addr, Some(spacetraders_mock::default_token()));
+    let ship_symbol = "MOCK_AGENT-1";
+
+    fleet_api::orbit_ship(&conf, ship_symbol).await.unwrap();
+    let request = NavigateShipRequest::new("X1-MOCK-B1".to_string());
+    fleet_api::navigate_ship(&conf, ship_symbol, Some(request))
+        .await
+        .unwrap();
+    universe.lock().skip_waits(ship_symbol).unwrap();
+
+    let survey = fleet_api::create_survey(&conf, ship_symbol)
+        .await
+        .unwrap()
+        .data
+        .surveys
+        .remove(0);
+    universe.lock().skip_waits(ship_symbol).unwrap();
+
+    // Surveys belong on `/extract/survey` now
+    let mut request = ExtractResourcesRequest::new();
+    request.survey = Some(Box::new(survey.clone()));
+    assert!(
+        fleet_api::extract_resources(&conf, ship_symbol, Some(request))
+            .await
+            .is_err()
+    );
+
+    let extraction =
+        fleet_api::extract_resources_with_survey(&conf, ship_symbol, Some(survey.clone()))
+            .await
+            .unwrap()
+            .data
+            .extraction;
+    let deposits: Vec<_> = survey.deposits.iter().map(|d| d.symbol.as_str()).collect();
+    assert!(deposits.contains(&extraction.r#yield.symbol.to_string().as_str()));
+}
@@ -0,0 +1,17 @@
+// Swaps in ship cards as the server says they've changed, instead of every
+// card polling for itself. Each event is a whole ship card, and unpoly works
+// out which one it replaces from its classes. If the server fell behind and
+// dropped some, it says to resync and we reload the whole fleet.
+up.compiler(".ships", (el) => {
+  const events = new EventSource("/fleet/events");
+
+  events.addEventListener("ship", (event) => {
+    up.render({ fragment: event.data });
+  });
+
+  events.addEventListener("resync", () => {
+    up.reload(el);
+  });
+
+  return () => events.close();
+});
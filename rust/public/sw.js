@@ -0,0 +1,23 @@
+// Shows the notifications the server pushes (see push.rs), and takes you to
+// the page they're about when clicked.
+self.addEventListener("push", (event) => {
+  const { title, body, url } = event.data.json();
+  event.waitUntil(
+    self.registration.showNotification(title, { body, data: { url } }),
+  );
+});
+
+self.addEventListener("notificationclick", (event) => {
+  event.notification.close();
+  const url = new URL(event.notification.data.url, self.location.origin).href;
+  event.waitUntil(
+    (async () => {
+      const windows = await clients.matchAll({ type: "window" });
+      const open = windows.find((client) => client.url === url);
+      if (open) {
+        return open.focus();
+      }
+      return clients.openWindow(url);
+    })(),
+  );
+});
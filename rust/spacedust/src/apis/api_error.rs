@@ -0,0 +1,213 @@
+//! Catalog of the error codes the SpaceTraders API is known to return.
+//!
+//! Every failed request carries an [`ApiErrorBody`] with a numeric `code` and
+//! a free-form `data` object. [`ApiErrorBody::kind`] turns that pair into an
+//! [`ApiErrorKind`] so callers can match on the cause of a failure instead of
+//! poking at the message text.
+
+use crate::models::{ApiErrorBody, Cooldown, TradeSymbol};
+
+pub const RATE_LIMITED: i32 = 429;
+pub const COOLDOWN_CONFLICT: i32 = 4000;
+pub const WAYPOINT_NO_ACCESS: i32 = 4001;
+pub const TOKEN_EMPTY: i32 = 4100;
+pub const TOKEN_ACCOUNT_HAS_NO_AGENT: i32 = 4108;
+pub const REGISTER_AGENT_EXISTS: i32 = 4109;
+pub const REGISTER_AGENT_SYMBOL_RESERVED: i32 = 4110;
+pub const REGISTER_AGENT_CONFLICT_SYMBOL: i32 = 4111;
+pub const NAVIGATE_IN_TRANSIT: i32 = 4200;
+pub const NAVIGATE_INVALID_DESTINATION: i32 = 4201;
+pub const NAVIGATE_OUTSIDE_SYSTEM: i32 = 4202;
+pub const NAVIGATE_INSUFFICIENT_FUEL: i32 = 4203;
+pub const NAVIGATE_SAME_DESTINATION: i32 = 4204;
+pub const SHIP_IN_TRANSIT: i32 = 4214;
+pub const PURCHASE_SHIP_CREDITS: i32 = 4216;
+pub const SHIP_CARGO_EXCEEDS_LIMIT: i32 = 4217;
+pub const SHIP_CARGO_MISSING: i32 = 4218;
+pub const SHIP_CARGO_UNIT_COUNT: i32 = 4219;
+pub const SHIP_SURVEY_VERIFICATION: i32 = 4220;
+pub const SHIP_SURVEY_EXPIRATION: i32 = 4221;
+pub const SHIP_SURVEY_EXHAUSTED: i32 = 4224;
+pub const SHIP_REFUEL_DOCKED: i32 = 4225;
+pub const SHIP_CARGO_FULL: i32 = 4228;
+pub const SHIP_NOT_IN_ORBIT: i32 = 4236;
+pub const SHIP_NOT_DOCKED: i32 = 4244;
+pub const MARKET_TRADE_INSUFFICIENT_CREDITS: i32 = 4600;
+pub const MARKET_TRADE_NO_PURCHASE: i32 = 4601;
+pub const MARKET_TRADE_NOT_SOLD: i32 = 4602;
+pub const MARKET_NOT_FOUND: i32 = 4603;
+pub const MARKET_TRADE_UNIT_LIMIT: i32 = 4604;
+
+/// The reason a request failed, decoded from [`ApiErrorBody::code`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiErrorKind {
+    /// Too many requests. Wait `retry_after` seconds before trying again.
+    RateLimited(RateLimitErrorData),
+    /// The ship's reactor is still cooling down from a previous action.
+    Cooldown(CooldownErrorData),
+    /// The ship is flying and can't do anything until it arrives.
+    InTransit(InTransitErrorData),
+    /// The destination is farther than the fuel in the tank allows.
+    InsufficientFuel(InsufficientFuelErrorData),
+    /// The ship is already at the requested destination.
+    SameDestination,
+    /// The action requires the ship to be in orbit.
+    NotInOrbit,
+    /// The action requires the ship to be docked.
+    NotDocked,
+    /// The agent can't afford the purchase.
+    InsufficientFunds(InsufficientFundsErrorData),
+    /// The purchase or sale is larger than the market's trade volume.
+    TradeUnitLimit(TradeUnitLimitErrorData),
+    /// The market doesn't buy or sell the requested good.
+    TradeNotAvailable,
+    /// There's no room left in the ship's hold.
+    CargoFull,
+    /// Adding the goods would go over the ship's cargo capacity.
+    CargoExceedsLimit(CargoExceedsLimitErrorData),
+    /// The ship doesn't have (enough of) the good in its hold.
+    CargoMissing,
+    /// The survey has expired.
+    SurveyExpired,
+    /// The survey's deposits have been mined out.
+    SurveyExhausted,
+    /// The survey was tampered with or is for a different waypoint.
+    SurveyInvalid,
+    /// The waypoint isn't charted, or doesn't have what was asked for, like
+    /// a jump gate.
+    WaypointNoAccess,
+    /// The agent token is missing or wasn't accepted.
+    Unauthorized,
+    /// Registration failed because the agent symbol is already taken or
+    /// reserved.
+    SymbolTaken,
+    /// A code this catalog doesn't know about yet.
+    Other(i32),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitErrorData {
+    #[serde(rename = "type", default)]
+    pub r#type: Option<String>,
+    /// Seconds to wait before sending another request
+    #[serde(rename = "retryAfter", default)]
+    pub retry_after: Option<f64>,
+    #[serde(rename = "limitBurst", default)]
+    pub limit_burst: Option<i32>,
+    #[serde(rename = "limitPerSecond", default)]
+    pub limit_per_second: Option<i32>,
+    #[serde(rename = "remaining", default)]
+    pub remaining: Option<i32>,
+    /// When the limit resets, in ISO 8601 format
+    #[serde(rename = "reset", default)]
+    pub reset: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CooldownErrorData {
+    #[serde(rename = "cooldown")]
+    pub cooldown: Box<Cooldown>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InTransitErrorData {
+    #[serde(rename = "departureSymbol", default)]
+    pub departure_symbol: Option<String>,
+    #[serde(rename = "destinationSymbol", default)]
+    pub destination_symbol: Option<String>,
+    /// Arrival time in ISO 8601 format
+    #[serde(rename = "arrival", default)]
+    pub arrival: Option<String>,
+    #[serde(rename = "departureTime", default)]
+    pub departure_time: Option<String>,
+    #[serde(rename = "secondsToArrival", default)]
+    pub seconds_to_arrival: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InsufficientFuelErrorData {
+    #[serde(rename = "fuelRequired", default)]
+    pub fuel_required: Option<i32>,
+    #[serde(rename = "fuelAvailable", default)]
+    pub fuel_available: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InsufficientFundsErrorData {
+    #[serde(rename = "creditsAvailable", default)]
+    pub credits_available: Option<i64>,
+    /// What the purchase would have cost. The API calls this `totalPrice` for
+    /// market trades and `shipPrice` for ship purchases.
+    #[serde(rename = "totalPrice", alias = "shipPrice", default)]
+    pub total_price: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeUnitLimitErrorData {
+    #[serde(rename = "tradeSymbol", default)]
+    pub trade_symbol: Option<TradeSymbol>,
+    #[serde(rename = "units", default)]
+    pub units: Option<i32>,
+    #[serde(rename = "tradeVolume", default)]
+    pub trade_volume: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CargoExceedsLimitErrorData {
+    #[serde(rename = "cargoCapacity", default)]
+    pub cargo_capacity: Option<i32>,
+    #[serde(rename = "cargoUnits", default)]
+    pub cargo_units: Option<i32>,
+    #[serde(rename = "unitsToAdd", default)]
+    pub units_to_add: Option<i32>,
+}
+
+impl ApiErrorBody {
+    /// Classifies this error by its code, decoding `data` for the codes that
+    /// carry a payload.
+    pub fn kind(&self) -> ApiErrorKind {
+        match self.code {
+            RATE_LIMITED => ApiErrorKind::RateLimited(self.data_or_default()),
+            COOLDOWN_CONFLICT => match self.data_as::<CooldownErrorData>() {
+                Some(data) => ApiErrorKind::Cooldown(data),
+                None => ApiErrorKind::Other(self.code),
+            },
+            NAVIGATE_IN_TRANSIT | SHIP_IN_TRANSIT => {
+                ApiErrorKind::InTransit(self.data_or_default())
+            }
+            NAVIGATE_INSUFFICIENT_FUEL => ApiErrorKind::InsufficientFuel(self.data_or_default()),
+            NAVIGATE_SAME_DESTINATION => ApiErrorKind::SameDestination,
+            SHIP_NOT_IN_ORBIT => ApiErrorKind::NotInOrbit,
+            SHIP_NOT_DOCKED | SHIP_REFUEL_DOCKED => ApiErrorKind::NotDocked,
+            MARKET_TRADE_INSUFFICIENT_CREDITS | PURCHASE_SHIP_CREDITS => {
+                ApiErrorKind::InsufficientFunds(self.data_or_default())
+            }
+            MARKET_TRADE_UNIT_LIMIT => ApiErrorKind::TradeUnitLimit(self.data_or_default()),
+            MARKET_TRADE_NO_PURCHASE | MARKET_TRADE_NOT_SOLD | MARKET_NOT_FOUND => {
+                ApiErrorKind::TradeNotAvailable
+            }
+            SHIP_CARGO_FULL => ApiErrorKind::CargoFull,
+            SHIP_CARGO_EXCEEDS_LIMIT => ApiErrorKind::CargoExceedsLimit(self.data_or_default()),
+            SHIP_CARGO_MISSING | SHIP_CARGO_UNIT_COUNT => ApiErrorKind::CargoMissing,
+            SHIP_SURVEY_EXPIRATION => ApiErrorKind::SurveyExpired,
+            SHIP_SURVEY_EXHAUSTED => ApiErrorKind::SurveyExhausted,
+            SHIP_SURVEY_VERIFICATION => ApiErrorKind::SurveyInvalid,
+            WAYPOINT_NO_ACCESS => ApiErrorKind::WaypointNoAccess,
+            401 | TOKEN_EMPTY..=TOKEN_ACCOUNT_HAS_NO_AGENT => ApiErrorKind::Unauthorized,
+            REGISTER_AGENT_EXISTS..=REGISTER_AGENT_CONFLICT_SYMBOL => ApiErrorKind::SymbolTaken,
+            code => ApiErrorKind::Other(code),
+        }
+    }
+
+    /// Decodes `data` into `T`, or `None` if it's absent or has a different
+    /// shape.
+    pub fn data_as<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+        self.data
+            .as_ref()
+            .and_then(|data| serde_json::from_value(data.clone()).ok())
+    }
+
+    fn data_or_default<T: serde::de::DeserializeOwned + Default>(&self) -> T {
+        self.data_as().unwrap_or_default()
+    }
+}
@@ -9,6 +9,7 @@
  */
 
 use crate::middleware::ContentLengthFixMiddleware;
+use crate::rate_limit::{RateLimitMiddleware, RateLimiter};
 
 
 
@@ -43,6 +44,7 @@ impl Default for Configuration {
     fn default() -> Self {
         let reqwest_middleware_builder = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
             .with(ContentLengthFixMiddleware)
+            .with(RateLimitMiddleware::new(RateLimiter::default()))
             .build();
 
         Configuration {
@@ -19,6 +19,7 @@ use super::{Error, configuration};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetFactionError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -26,6 +27,7 @@ pub enum GetFactionError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetFactionsError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
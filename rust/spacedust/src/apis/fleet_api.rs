@@ -19,6 +19,7 @@ use super::{Error, configuration};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CreateChartError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -26,6 +27,7 @@ pub enum CreateChartError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CreateShipShipScanError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -33,6 +35,7 @@ pub enum CreateShipShipScanError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CreateShipSystemScanError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -40,6 +43,7 @@ pub enum CreateShipSystemScanError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CreateShipWaypointScanError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -47,6 +51,7 @@ pub enum CreateShipWaypointScanError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CreateSurveyError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -54,6 +59,7 @@ pub enum CreateSurveyError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum DockShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -61,6 +67,15 @@ pub enum DockShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ExtractResourcesError {
+    DefaultResponse(crate::models::ApiErrorResponse),
+    UnknownValue(serde_json::Value),
+}
+
+/// struct for typed errors of method [`extract_resources_with_survey`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExtractResourcesWithSurveyError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -68,6 +83,7 @@ pub enum ExtractResourcesError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetMountsError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -75,6 +91,7 @@ pub enum GetMountsError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetMyShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -82,6 +99,7 @@ pub enum GetMyShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetMyShipCargoError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -89,6 +107,7 @@ pub enum GetMyShipCargoError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetMyShipsError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -96,6 +115,7 @@ pub enum GetMyShipsError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetShipCooldownError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -103,6 +123,7 @@ pub enum GetShipCooldownError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GetShipNavError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -110,6 +131,7 @@ pub enum GetShipNavError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum InstallMountError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -117,6 +139,7 @@ pub enum InstallMountError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JettisonError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -124,6 +147,7 @@ pub enum JettisonError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JumpShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -131,6 +155,7 @@ pub enum JumpShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum NavigateShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -138,6 +163,7 @@ pub enum NavigateShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum NegotiateContractError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -145,6 +171,7 @@ pub enum NegotiateContractError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum OrbitShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -152,6 +179,7 @@ pub enum OrbitShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum PatchShipNavError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -159,6 +187,7 @@ pub enum PatchShipNavError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum PurchaseCargoError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -166,6 +195,7 @@ pub enum PurchaseCargoError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum PurchaseShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -173,6 +203,7 @@ pub enum PurchaseShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RefuelShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -180,6 +211,7 @@ pub enum RefuelShipError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum RemoveMountError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -187,6 +219,7 @@ pub enum RemoveMountError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum SellCargoError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -194,6 +227,15 @@ pub enum SellCargoError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ShipRefineError {
+    DefaultResponse(crate::models::ApiErrorResponse),
+    UnknownValue(serde_json::Value),
+}
+
+/// struct for typed errors of method [`siphon_resources`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SiphonResourcesError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -201,6 +243,7 @@ pub enum ShipRefineError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum TransferCargoError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -208,6 +251,7 @@ pub enum TransferCargoError {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum WarpShipError {
+    DefaultResponse(crate::models::ApiErrorResponse),
     UnknownValue(serde_json::Value),
 }
 
@@ -430,6 +474,38 @@ pub async fn extract_resources(configuration: &configuration::Configuration, shi
     }
 }
 
+/// Use a survey when extracting resources from a waypoint. This endpoint requires a survey as the payload, which allows your ship to extract specific yields.  Send the full survey object as the payload which will be validated according to the signature. If the signature is invalid, or any properties of the survey are changed, the request will fail.
+pub async fn extract_resources_with_survey(configuration: &configuration::Configuration, ship_symbol: &str, survey: Option<crate::models::Survey>) -> Result<crate::models::ExtractResources201Response, Error<ExtractResourcesWithSurveyError>> {
+    let local_var_configuration = configuration;
+
+    let local_var_client = &local_var_configuration.client;
+
+    let local_var_uri_str = format!("{}/my/ships/{shipSymbol}/extract/survey", local_var_configuration.base_path, shipSymbol=crate::apis::urlencode(ship_symbol));
+    let mut local_var_req_builder = local_var_client.request(reqwest::Method::POST, local_var_uri_str.as_str());
+
+    if let Some(ref local_var_user_agent) = local_var_configuration.user_agent {
+        local_var_req_builder = local_var_req_builder.header(reqwest::header::USER_AGENT, local_var_user_agent.clone());
+    }
+    if let Some(ref local_var_token) = local_var_configuration.bearer_access_token {
+        local_var_req_builder = local_var_req_builder.bearer_auth(local_var_token.to_owned());
+    };
+    local_var_req_builder = local_var_req_builder.json(&survey);
+
+    let local_var_req = local_var_req_builder.build()?;
+    let local_var_resp = local_var_client.execute(local_var_req).await?;
+
+    let local_var_status = local_var_resp.status();
+    let local_var_content = local_var_resp.text().await?;
+
+    if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
+        serde_json::from_str(&local_var_content).map_err(Error::from)
+    } else {
+        let local_var_entity: Option<ExtractResourcesWithSurveyError> = serde_json::from_str(&local_var_content).ok();
+        let local_var_error = ResponseContent { status: local_var_status, content: local_var_content, entity: local_var_entity };
+        Err(Error::ResponseError(local_var_error))
+    }
+}
+
 /// Get the mounts installed on a ship.
 pub async fn get_mounts(configuration: &configuration::Configuration, ship_symbol: &str) -> Result<crate::models::GetMounts200Response, Error<GetMountsError>> {
     let local_var_configuration = configuration;
@@ -1036,6 +1112,37 @@ pub async fn ship_refine(configuration: &configuration::Configuration, ship_symb
     }
 }
 
+/// Siphon gases, such as hydrocarbon, from gas giants.  The ship must be in orbit to be able to siphon and must have siphon mounts and a gas processor installed.
+pub async fn siphon_resources(configuration: &configuration::Configuration, ship_symbol: &str) -> Result<crate::models::SiphonResources201Response, Error<SiphonResourcesError>> {
+    let local_var_configuration = configuration;
+
+    let local_var_client = &local_var_configuration.client;
+
+    let local_var_uri_str = format!("{}/my/ships/{shipSymbol}/siphon", local_var_configuration.base_path, shipSymbol=crate::apis::urlencode(ship_symbol));
+    let mut local_var_req_builder = local_var_client.request(reqwest::Method::POST, local_var_uri_str.as_str());
+
+    if let Some(ref local_var_user_agent) = local_var_configuration.user_agent {
+        local_var_req_builder = local_var_req_builder.header(reqwest::header::USER_AGENT, local_var_user_agent.clone());
+    }
+    if let Some(ref local_var_token) = local_var_configuration.bearer_access_token {
+        local_var_req_builder = local_var_req_builder.bearer_auth(local_var_token.to_owned());
+    };
+
+    let local_var_req = local_var_req_builder.build()?;
+    let local_var_resp = local_var_client.execute(local_var_req).await?;
+
+    let local_var_status = local_var_resp.status();
+    let local_var_content = local_var_resp.text().await?;
+
+    if !local_var_status.is_client_error() && !local_var_status.is_server_error() {
+        serde_json::from_str(&local_var_content).map_err(Error::from)
+    } else {
+        let local_var_entity: Option<SiphonResourcesError> = serde_json::from_str(&local_var_content).ok();
+        let local_var_error = ResponseContent { status: local_var_status, content: local_var_content, entity: local_var_entity };
+        Err(Error::ResponseError(local_var_error))
+    }
+}
+
 /// Transfer cargo between ships.  The receiving ship must be in the same waypoint as the transferring ship, and it must able to hold the additional cargo after the transfer is complete. Both ships also must be in the same state, either both are docked or both are orbiting.  The response body's cargo shows the cargo of the transferring ship after the transfer is complete.
 pub async fn transfer_cargo(configuration: &configuration::Configuration, ship_symbol: &str, transfer_cargo_request: Option<crate::models::TransferCargoRequest>) -> Result<crate::models::TransferCargo200Response, Error<TransferCargoError>> {
     let local_var_configuration = configuration;
@@ -24,7 +24,10 @@ impl <T> fmt::Display for Error<T> {
             Error::ReqwestMiddleware(e) => ("reqwest-middleware", e.to_string()),
             Error::Serde(e) => ("serde", e.to_string()),
             Error::Io(e) => ("IO", e.to_string()),
-            Error::ResponseError(e) => ("response", format!("status code {}", e.status)),
+            Error::ResponseError(e) => match e.api_error() {
+                Some(api_error) => ("response", format!("status code {}: {} (code {})", e.status, api_error.message, api_error.code)),
+                None => ("response", format!("status code {}", e.status)),
+            },
         };
         write!(f, "error in {}: {}", module, e)
     }
@@ -42,6 +45,30 @@ impl <T: fmt::Debug> error::Error for Error<T> {
     }
 }
 
+impl <T> ResponseContent<T> {
+    /// Decodes the `{"error": {...}}` envelope the API sends with every failure.
+    pub fn api_error(&self) -> Option<crate::models::ApiErrorBody> {
+        serde_json::from_str::<crate::models::ApiErrorResponse>(&self.content)
+            .ok()
+            .map(|response| *response.error)
+    }
+}
+
+impl <T> Error<T> {
+    /// The API's explanation of the failure, if the server sent one.
+    pub fn api_error(&self) -> Option<crate::models::ApiErrorBody> {
+        match self {
+            Error::ResponseError(e) => e.api_error(),
+            _ => None,
+        }
+    }
+
+    /// The catalogued reason for the failure, if the server sent one.
+    pub fn api_error_kind(&self) -> Option<api_error::ApiErrorKind> {
+        self.api_error().map(|e| e.kind())
+    }
+}
+
 impl <T> From<reqwest::Error> for Error<T> {
     fn from(e: reqwest::Error) -> Self {
         Error::Reqwest(e)
@@ -106,4 +133,6 @@ pub mod factions_api;
 pub mod fleet_api;
 pub mod systems_api;
 
+pub mod api_error;
 pub mod configuration;
+pub mod pagination;
@@ -0,0 +1,178 @@
+//! Streams that walk every page of the paginated list endpoints.
+//!
+//! Each `all_*` function yields items one at a time, fetching the next page
+//! only once the previous one has been consumed. Paging stops when `Meta`
+//! says we've seen `total` items or the server returns an empty page.
+
+use std::future::Future;
+
+use futures::stream::{self, Stream, TryStreamExt};
+
+use super::{agents_api, configuration, contracts_api, factions_api, fleet_api, systems_api, Error};
+use crate::models::{
+    Agent, Contract, Faction, GetSystemWaypointsTraitsParameter, Meta, Ship, System, Waypoint,
+    WaypointType,
+};
+
+/// The largest page size the API accepts.
+pub const PAGE_LIMIT: i32 = 20;
+
+/// Turns a page fetcher into a stream of items. `fetch` is called with the
+/// 1-indexed page number and the page size.
+pub fn paginate<'a, T, E, F, Fut>(mut fetch: F) -> impl Stream<Item = Result<T, Error<E>>> + 'a
+where
+    T: 'a,
+    E: 'a,
+    F: FnMut(i32, i32) -> Fut + 'a,
+    Fut: Future<Output = Result<(Vec<T>, Meta), Error<E>>> + 'a,
+{
+    stream::try_unfold(Some(1), move |page| {
+        let next = page.map(|page| fetch(page, PAGE_LIMIT));
+        async move {
+            let Some(next) = next else {
+                return Ok::<_, Error<E>>(None);
+            };
+            let (items, meta) = next.await?;
+            let done = items.is_empty() || meta.page * meta.limit >= meta.total;
+            let next_page = if done { None } else { Some(meta.page + 1) };
+            Ok(Some((stream::iter(items.into_iter().map(Ok)), next_page)))
+        }
+    })
+    .try_flatten()
+}
+
+pub fn all_systems(
+    configuration: &configuration::Configuration,
+) -> impl Stream<Item = Result<System, Error<systems_api::GetSystemsError>>> + '_ {
+    paginate(move |page, limit| async move {
+        let response = systems_api::get_systems(configuration, Some(page), Some(limit)).await?;
+        Ok((response.data, *response.meta))
+    })
+}
+
+pub fn all_system_waypoints<'a>(
+    configuration: &'a configuration::Configuration,
+    system_symbol: &'a str,
+    r#type: Option<WaypointType>,
+    traits: Option<GetSystemWaypointsTraitsParameter>,
+) -> impl Stream<Item = Result<Waypoint, Error<systems_api::GetSystemWaypointsError>>> + 'a {
+    paginate(move |page, limit| {
+        let traits = traits.clone();
+        async move {
+            let response = systems_api::get_system_waypoints(
+                configuration,
+                system_symbol,
+                Some(page),
+                Some(limit),
+                r#type,
+                traits,
+            )
+            .await?;
+            Ok((response.data, *response.meta))
+        }
+    })
+}
+
+pub fn all_my_ships(
+    configuration: &configuration::Configuration,
+) -> impl Stream<Item = Result<Ship, Error<fleet_api::GetMyShipsError>>> + '_ {
+    paginate(move |page, limit| async move {
+        let response = fleet_api::get_my_ships(configuration, Some(page), Some(limit)).await?;
+        Ok((response.data, *response.meta))
+    })
+}
+
+pub fn all_contracts(
+    configuration: &configuration::Configuration,
+) -> impl Stream<Item = Result<Contract, Error<contracts_api::GetContractsError>>> + '_ {
+    paginate(move |page, limit| async move {
+        let response = contracts_api::get_contracts(configuration, Some(page), Some(limit)).await?;
+        Ok((response.data, *response.meta))
+    })
+}
+
+pub fn all_factions(
+    configuration: &configuration::Configuration,
+) -> impl Stream<Item = Result<Faction, Error<factions_api::GetFactionsError>>> + '_ {
+    paginate(move |page, limit| async move {
+        let response = factions_api::get_factions(configuration, Some(page), Some(limit)).await?;
+        Ok((response.data, *response.meta))
+    })
+}
+
+pub fn all_agents(
+    configuration: &configuration::Configuration,
+) -> impl Stream<Item = Result<Agent, Error<agents_api::GetAgentsError>>> + '_ {
+    paginate(move |page, limit| async move {
+        let response = agents_api::get_agents(configuration, Some(page), Some(limit)).await?;
+        Ok((response.data, *response.meta))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::cell::RefCell;
+
+    /// Pages of `total` numbers, recording which pages were asked for.
+    fn numbers<'a>(
+        total: i32,
+        fetched: &'a RefCell<Vec<i32>>,
+    ) -> impl Stream<Item = Result<i32, Error<()>>> + 'a {
+        paginate(move |page, limit| {
+            fetched.borrow_mut().push(page);
+            async move {
+                let first = (page - 1) * limit;
+                let items = (first..total.min(first + limit)).collect();
+                Ok((items, Meta::new(total, page, limit)))
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn walks_every_page_until_total() {
+        let fetched = RefCell::new(vec![]);
+        let items: Vec<_> = numbers(45, &fetched).try_collect().await.unwrap();
+        assert_eq!(items, (0..45).collect::<Vec<_>>());
+        assert_eq!(*fetched.borrow(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn fetches_pages_as_they_are_needed() {
+        let fetched = RefCell::new(vec![]);
+        let first: Vec<_> = numbers(45, &fetched)
+            .take(PAGE_LIMIT as usize)
+            .collect()
+            .await;
+        assert_eq!(first.len(), PAGE_LIMIT as usize);
+        assert_eq!(*fetched.borrow(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_an_empty_page() {
+        let fetched = RefCell::new(vec![]);
+        let stream = paginate(|page, limit| {
+            fetched.borrow_mut().push(page);
+            // Claims there's more than there is
+            async move { Ok::<_, Error<()>>((Vec::<i32>::new(), Meta::new(100, page, limit))) }
+        });
+        let items: Vec<_> = stream.try_collect().await.unwrap();
+        assert!(items.is_empty());
+        assert_eq!(*fetched.borrow(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn errors_end_the_stream() {
+        let stream = paginate(|page, limit| async move {
+            if page == 2 {
+                return Err(Error::<()>::Io(std::io::Error::other("gone")));
+            }
+            Ok((vec![page], Meta::new(100, page, limit)))
+        });
+        let items: Vec<_> = stream.collect().await;
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[0], Ok(1)));
+        assert!(matches!(items[1], Err(Error::Io(_))));
+    }
+}
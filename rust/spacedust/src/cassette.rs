@@ -0,0 +1,283 @@
+//! Record-and-replay for API traffic.
+//!
+//! [`CassetteMiddleware`] in [`Mode::Record`] passes requests through and
+//! writes each request/response pair to a JSON cassette file. In
+//! [`Mode::Replay`] it never touches the network: each request is answered
+//! with the first recorded response that matches it on method, path, query and
+//! body and hasn't been used yet, so a session that polls the same endpoint
+//! and sees it change replays the same way.
+//!
+//! Only the path is matched, not the host, so a cassette recorded against the
+//! live API replays just as well with `base_path` pointed anywhere else.
+//! Request headers aren't recorded, which keeps bearer tokens out of the
+//! cassette.
+//!
+//! ```no_run
+//! use spacedust::apis::configuration::Configuration;
+//! use spacedust::cassette::{self, Cassette};
+//!
+//! let mut conf = Configuration::new();
+//! conf.client = cassette::client(Cassette::replay("tests/cassettes/session.json").unwrap());
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use reqwest::{Request, Response};
+use reqwest_middleware::{ClientWithMiddleware, Middleware, Next, Result};
+use serde_json::Value;
+use task_local_extensions::Extensions;
+
+use crate::middleware::ContentLengthFixMiddleware;
+use crate::rate_limit::{RateLimitMiddleware, RateLimiter};
+
+/// What a request is matched on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// Sorted, so parameter order doesn't matter
+    pub query: Vec<(String, String)>,
+    /// JSON bodies are stored parsed, so whitespace and key order don't
+    /// matter either. Anything else is stored as a string.
+    pub body: Option<Value>,
+}
+
+impl RecordedRequest {
+    fn from_request(req: &Request) -> RecordedRequest {
+        let mut query: Vec<(String, String)> = req
+            .url()
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        query.sort();
+
+        let body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .filter(|b| !b.is_empty())
+            .map(|bytes| {
+                serde_json::from_slice(bytes)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+            });
+
+        RecordedRequest {
+            method: req.method().to_string(),
+            path: req.url().path().to_owned(),
+            query,
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Response {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        // Only fails on a bad status or header, which we recorded from a real
+        // response
+        builder.body(self.body.clone()).unwrap().into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    /// Which interactions have already been replayed
+    used: Vec<bool>,
+}
+
+/// A cassette file and our place in it. Clones share state.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    mode: Mode,
+    path: PathBuf,
+    tape: Arc<Mutex<Tape>>,
+    /// How many interactions the file on disk holds. Saves run on the
+    /// blocking pool and can finish out of order, so one that's older than
+    /// what's already written is dropped.
+    written: Arc<Mutex<usize>>,
+}
+
+impl Cassette {
+    /// Starts a fresh recording, replacing whatever is at `path`.
+    pub fn record(path: impl AsRef<Path>) -> io::Result<Cassette> {
+        let cassette = Cassette::new(Mode::Record, path.as_ref(), vec![]);
+        cassette.save()?;
+        Ok(cassette)
+    }
+
+    pub fn replay(path: impl AsRef<Path>) -> io::Result<Cassette> {
+        let interactions = serde_json::from_str(&fs::read_to_string(path.as_ref())?)?;
+        Ok(Cassette::new(Mode::Replay, path.as_ref(), interactions))
+    }
+
+    fn new(mode: Mode, path: &Path, interactions: Vec<Interaction>) -> Cassette {
+        Cassette {
+            mode,
+            path: path.to_owned(),
+            tape: Arc::new(Mutex::new(Tape {
+                used: vec![false; interactions.len()],
+                interactions,
+            })),
+            written: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Recorded interactions that haven't been replayed, e.g. to check a test
+    /// made every request it was expected to.
+    pub fn unused(&self) -> Vec<Interaction> {
+        let tape = self.tape.lock().unwrap();
+        tape.interactions
+            .iter()
+            .zip(&tape.used)
+            .filter(|(_, used)| !**used)
+            .map(|(interaction, _)| interaction.clone())
+            .collect()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let (json, count) = self.snapshot()?;
+        self.write(json, count)
+    }
+
+    /// [`Cassette::save`] without blocking the runtime on the write.
+    async fn save_in_background(&self) -> io::Result<()> {
+        let (json, count) = self.snapshot()?;
+        let cassette = self.clone();
+        tokio::task::spawn_blocking(move || cassette.write(json, count))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    fn snapshot(&self) -> io::Result<(String, usize)> {
+        let tape = self.tape.lock().unwrap();
+        let json = serde_json::to_string_pretty(&tape.interactions)?;
+        Ok((json, tape.interactions.len()))
+    }
+
+    fn write(&self, json: String, count: usize) -> io::Result<()> {
+        let mut written = self.written.lock().unwrap();
+        if count < *written {
+            return Ok(());
+        }
+        fs::write(&self.path, json)?;
+        *written = count;
+        Ok(())
+    }
+
+    fn play(&self, request: &RecordedRequest) -> Option<RecordedResponse> {
+        let mut tape = self.tape.lock().unwrap();
+        let Tape { interactions, used } = &mut *tape;
+        let index = interactions
+            .iter()
+            .zip(used.iter())
+            .position(|(interaction, used)| !used && interaction.request == *request)?;
+        used[index] = true;
+        Some(interactions[index].response.clone())
+    }
+}
+
+pub struct CassetteMiddleware {
+    cassette: Cassette,
+}
+
+impl CassetteMiddleware {
+    pub fn new(cassette: Cassette) -> CassetteMiddleware {
+        CassetteMiddleware { cassette }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CassetteMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let request = RecordedRequest::from_request(&req);
+
+        if self.cassette.mode == Mode::Replay {
+            return match self.cassette.play(&request) {
+                Some(response) => Ok(response.to_response()),
+                None => Err(reqwest_middleware::Error::Middleware(anyhow::anyhow!(
+                    "no unused recording in {} for {} {}",
+                    self.cassette.path.display(),
+                    request.method,
+                    req.url()
+                ))),
+            };
+        }
+
+        let res = next.run(req, extensions).await?;
+        let status = res.status().as_u16();
+        let headers = res
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+        let body = res.text().await?;
+
+        let response = RecordedResponse {
+            status,
+            headers,
+            body,
+        };
+        self.cassette
+            .tape
+            .lock()
+            .unwrap()
+            .interactions
+            .push(Interaction {
+                request,
+                response: response.clone(),
+            });
+        // Saving as we go means a crash doesn't lose the session
+        self.cassette
+            .save_in_background()
+            .await
+            .map_err(|e| reqwest_middleware::Error::Middleware(e.into()))?;
+
+        Ok(response.to_response())
+    }
+}
+
+/// A client for `cassette`. Recording goes through the usual rate limiting;
+/// replaying doesn't need it.
+pub fn client(cassette: Cassette) -> ClientWithMiddleware {
+    let builder = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+        .with(ContentLengthFixMiddleware);
+    let builder = match cassette.mode {
+        Mode::Record => builder.with(RateLimitMiddleware::new(RateLimiter::default())),
+        Mode::Replay => builder,
+    };
+    builder.with(CassetteMiddleware::new(cassette)).build()
+}
@@ -7,5 +7,7 @@ extern crate url;
 extern crate reqwest;
 
 mod middleware;
+pub mod cassette;
+pub mod rate_limit;
 pub mod apis;
 pub mod models;
@@ -0,0 +1,39 @@
+/*
+ * SpaceTraders API
+ *
+ * SpaceTraders is an open-universe game and learning platform that offers a set of HTTP endpoints to control a fleet of ships and explore a multiplayer universe.  The API is documented using [OpenAPI](https://github.com/SpaceTradersAPI/api-docs). You can send your first request right here in your browser to check the status of the game server.  ```json http {   \"method\": \"GET\",   \"url\": \"https://api.spacetraders.io/v2\", } ```  Unlike a traditional game, SpaceTraders does not have a first-party client or app to play the game. Instead, you can use the API to build your own client, write a script to automate your ships, or try an app built by the community.  We have a [Discord channel](https://discord.com/invite/jh6zurdWk5) where you can share your projects, ask questions, and get help from other players.   
+ *
+ * The version of the OpenAPI document: 2.0.0
+ * Contact: joel@spacetraders.io
+ * Generated by: https://openapi-generator.tech
+ */
+
+/// ApiErrorBody : Details of why a request failed.
+
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApiErrorBody {
+    /// A human-readable description of the error.
+    #[serde(rename = "message")]
+    pub message: String,
+    /// The SpaceTraders error code. See [`crate::apis::api_error::ApiErrorKind`] for the known codes.
+    #[serde(rename = "code")]
+    pub code: i32,
+    /// Extra details about the error. The shape depends on the error code.
+    #[serde(rename = "data", skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl ApiErrorBody {
+    /// Details of why a request failed.
+    pub fn new(message: String, code: i32) -> ApiErrorBody {
+        ApiErrorBody {
+            message,
+            code,
+            data: None,
+        }
+    }
+}
+
+
@@ -8,18 +8,36 @@
  * Generated by: https://openapi-generator.tech
  */
 
+/// GetSystemWaypointsTraitsParameter : Filter waypoints by one or more traits.
 
 
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct GetSystemWaypointsTraitsParameter {
+#[serde(untagged)]
+pub enum GetSystemWaypointsTraitsParameter {
+    Single(crate::models::WaypointTraitSymbol),
+    Multiple(Vec<crate::models::WaypointTraitSymbol>),
 }
 
 impl GetSystemWaypointsTraitsParameter {
-    pub fn new() -> GetSystemWaypointsTraitsParameter {
-        GetSystemWaypointsTraitsParameter {
+    /// The traits to filter by, in the order they'll be sent.
+    pub fn symbols(&self) -> Vec<crate::models::WaypointTraitSymbol> {
+        match self {
+            Self::Single(symbol) => vec![*symbol],
+            Self::Multiple(symbols) => symbols.clone(),
         }
     }
 }
 
+impl From<crate::models::WaypointTraitSymbol> for GetSystemWaypointsTraitsParameter {
+    fn from(symbol: crate::models::WaypointTraitSymbol) -> Self {
+        Self::Single(symbol)
+    }
+}
+
+impl From<Vec<crate::models::WaypointTraitSymbol>> for GetSystemWaypointsTraitsParameter {
+    fn from(symbols: Vec<crate::models::WaypointTraitSymbol>) -> Self {
+        Self::Multiple(symbols)
+    }
+}
 
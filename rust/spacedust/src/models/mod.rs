@@ -6,6 +6,10 @@ pub mod activity_level;
 pub use self::activity_level::ActivityLevel;
 pub mod agent;
 pub use self::agent::Agent;
+pub mod api_error_body;
+pub use self::api_error_body::ApiErrorBody;
+pub mod api_error_response;
+pub use self::api_error_response::ApiErrorResponse;
 pub mod chart;
 pub use self::chart::Chart;
 pub mod construction;
@@ -0,0 +1,395 @@
+//! Client-side scheduling that keeps us inside the SpaceTraders rate limits.
+//!
+//! The server allows a steady number of requests per second plus a burst pool
+//! that refills over a longer window. [`RateLimitMiddleware`] mirrors both
+//! buckets locally, corrects them from the `x-ratelimit-*` headers on every
+//! response, and backs off for `retry-after` when we get a 429 anyway.
+//!
+//! Requests waiting for a token are served in priority order. Everything is
+//! [`Priority::Interactive`] unless it's run inside [`background`], so polling
+//! never makes someone's button click wait.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next, Result};
+use task_local_extensions::Extensions;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Background,
+    Interactive,
+}
+
+tokio::task_local! {
+    static PRIORITY: Priority;
+}
+
+/// Runs `fut` with every API request inside it queued at
+/// [`Priority::Background`].
+pub async fn background<F: Future>(fut: F) -> F::Output {
+    PRIORITY.scope(Priority::Background, fut).await
+}
+
+fn current_priority() -> Priority {
+    PRIORITY.try_with(|p| *p).unwrap_or(Priority::Interactive)
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Steady-state requests per second
+    pub per_second: f64,
+    /// Extra requests available once the steady rate is used up
+    pub burst: f64,
+    /// How long the burst pool takes to refill completely
+    pub burst_window: Duration,
+    /// How many times to resend a request that got a 429
+    pub max_retries: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_second: 2.0,
+            burst: 30.0,
+            burst_window: Duration::from_secs(60),
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Ticket {
+    // Reversed so that BTreeSet::first() is the highest priority, oldest
+    // ticket.
+    priority: std::cmp::Reverse<Priority>,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    config: RateLimitConfig,
+    steady: f64,
+    burst: f64,
+    refilled_at: Instant,
+    blocked_until: Option<Instant>,
+    queue: BTreeSet<Ticket>,
+    next_seq: u64,
+}
+
+impl Buckets {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.refilled_at = now;
+
+        let burst_rate = self.config.burst / self.config.burst_window.as_secs_f64();
+        self.steady = (self.steady + elapsed * self.config.per_second).min(self.config.per_second);
+        self.burst = (self.burst + elapsed * burst_rate).min(self.config.burst);
+    }
+
+    /// Takes a token, or says how long until one is available.
+    fn take(&mut self, now: Instant) -> std::result::Result<(), Duration> {
+        if let Some(until) = self.blocked_until {
+            if now < until {
+                return Err(until - now);
+            }
+            self.blocked_until = None;
+        }
+
+        if self.steady >= 1.0 {
+            self.steady -= 1.0;
+            return Ok(());
+        }
+        if self.burst >= 1.0 {
+            self.burst -= 1.0;
+            return Ok(());
+        }
+
+        let burst_rate = self.config.burst / self.config.burst_window.as_secs_f64();
+        let steady_wait = (1.0 - self.steady) / self.config.per_second;
+        let burst_wait = (1.0 - self.burst) / burst_rate;
+        Err(Duration::from_secs_f64(steady_wait.min(burst_wait)))
+    }
+
+    fn block_for(&mut self, now: Instant, wait: Duration) {
+        let until = now + wait;
+        self.blocked_until = Some(self.blocked_until.map_or(until, |b| b.max(until)));
+        self.steady = 0.0;
+        self.burst = 0.0;
+    }
+}
+
+/// The shared token buckets and wait queue. Clones share state, so give every
+/// client that uses the same agent token the same limiter.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+    notify: Arc<Notify>,
+}
+
+/// Pulls a ticket back out of the queue if the request gives up waiting, so
+/// the tickets behind it aren't stuck forever.
+struct QueuedTicket<'a> {
+    limiter: &'a RateLimiter,
+    ticket: Ticket,
+}
+
+impl Drop for QueuedTicket<'_> {
+    fn drop(&mut self) {
+        self.limiter.buckets.lock().unwrap().queue.remove(&self.ticket);
+        self.limiter.notify.notify_waiters();
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            buckets: Arc::new(Mutex::new(Buckets {
+                steady: config.per_second,
+                burst: config.burst,
+                config,
+                refilled_at: Instant::now(),
+                blocked_until: None,
+                queue: BTreeSet::new(),
+                next_seq: 0,
+            })),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Waits until it's this request's turn and a token is available.
+    pub async fn acquire(&self, priority: Priority) {
+        let queued = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let ticket = Ticket {
+                priority: std::cmp::Reverse(priority),
+                seq: buckets.next_seq,
+            };
+            buckets.next_seq += 1;
+            buckets.queue.insert(ticket);
+            QueuedTicket {
+                limiter: self,
+                ticket,
+            }
+        };
+
+        loop {
+            // Register for wakeups before looking at the queue so we can't
+            // miss a notification sent in between.
+            let notified = self.notify.notified();
+
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                if buckets.queue.first() != Some(&queued.ticket) {
+                    None
+                } else {
+                    let now = Instant::now();
+                    buckets.refill(now);
+                    match buckets.take(now) {
+                        Ok(()) => return,
+                        Err(wait) => Some(wait),
+                    }
+                }
+            };
+
+            match wait {
+                Some(wait) => {
+                    let _ = tokio::time::timeout(wait, notified).await;
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Syncs our view of the limits with what the server reported.
+    pub fn observe(&self, response: &Response) {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        };
+        let number = |name: &str| header(name).and_then(|v| v.parse::<f64>().ok());
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.refill(now);
+
+        if let Some(per_second) = number("x-ratelimit-limit-per-second") {
+            buckets.config.per_second = per_second;
+        }
+        if let Some(burst) = number("x-ratelimit-limit-burst") {
+            buckets.config.burst = burst;
+        }
+        if let Some(remaining) = number("x-ratelimit-remaining") {
+            buckets.burst = buckets.burst.min(remaining);
+            if remaining < 1.0 {
+                let reset = header("x-ratelimit-reset")
+                    .and_then(|reset| chrono::DateTime::parse_from_rfc3339(&reset).ok())
+                    .and_then(|reset| (reset.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok())
+                    .unwrap_or_else(|| Duration::from_secs_f64(1.0 / buckets.config.per_second));
+                let wait = reset.min(buckets.config.burst_window);
+                buckets.block_for(now, wait);
+            }
+        }
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let wait = number("retry-after").unwrap_or(1.0);
+            buckets.block_for(now, Duration::from_secs_f64(wait.max(0.0)));
+        }
+
+        drop(buckets);
+        self.notify.notify_waiters();
+    }
+
+    fn max_retries(&self) -> u32 {
+        self.buckets.lock().unwrap().config.max_retries
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(RateLimitConfig::default())
+    }
+}
+
+pub struct RateLimitMiddleware {
+    limiter: RateLimiter,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: RateLimiter) -> RateLimitMiddleware {
+        RateLimitMiddleware { limiter }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let priority = current_priority();
+        let mut req = req;
+        let mut attempt = 0;
+
+        loop {
+            // Requests with streaming bodies can't be cloned, so those just
+            // get one shot.
+            let retry = req.try_clone();
+
+            self.limiter.acquire(priority).await;
+            let res = next.clone().run(req, extensions).await?;
+            self.limiter.observe(&res);
+
+            match retry {
+                Some(retry)
+                    if res.status() == StatusCode::TOO_MANY_REQUESTS
+                        && attempt < self.limiter.max_retries() =>
+                {
+                    attempt += 1;
+                    req = retry;
+                }
+                _ => return Ok(res),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            per_second: 2.0,
+            burst: 3.0,
+            burst_window: Duration::from_secs(30),
+            max_retries: 3,
+        })
+    }
+
+    /// Takes every token there is without waiting.
+    async fn drain(limiter: &RateLimiter) {
+        for _ in 0..5 {
+            limiter
+                .acquire(Priority::Interactive)
+                .now_or_never()
+                .expect("a token should be free");
+        }
+    }
+
+    fn too_many_requests(retry_after: &str) -> Response {
+        http::Response::builder()
+            .status(429)
+            .header("retry-after", retry_after)
+            .body("")
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_drains_then_falls_back_to_steady_rate() {
+        let limiter = limiter();
+        drain(&limiter).await;
+        assert!(limiter
+            .acquire(Priority::Interactive)
+            .now_or_never()
+            .is_none());
+
+        let start = Instant::now();
+        limiter.acquire(Priority::Interactive).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn buckets_refill() {
+        let limiter = limiter();
+        drain(&limiter).await;
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        drain(&limiter).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn interactive_jumps_background_queue() {
+        let limiter = limiter();
+        drain(&limiter).await;
+        let order = Arc::new(Mutex::new(vec![]));
+
+        let queue = |limiter: &RateLimiter, name| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            async move {
+                limiter.acquire(current_priority()).await;
+                order.lock().unwrap().push(name);
+            }
+        };
+        let polling = tokio::spawn(background(queue(&limiter, "background")));
+        tokio::task::yield_now().await;
+        let click = tokio::spawn(queue(&limiter, "interactive"));
+
+        click.await.unwrap();
+        polling.await.unwrap();
+        assert_eq!(*order.lock().unwrap(), vec!["interactive", "background"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn too_many_requests_waits_for_retry_after() {
+        let limiter = limiter();
+        limiter.observe(&too_many_requests("5"));
+
+        let start = Instant::now();
+        limiter.acquire(Priority::Interactive).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+}
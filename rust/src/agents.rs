@@ -0,0 +1,188 @@
+use std::convert::Infallible;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+
+use spacedust::apis::configuration::Configuration;
+use spacedust::cassette::{self, Cassette};
+
+use crate::config::{AgentConfig, Config};
+use crate::executor::Executor;
+use crate::fleet::Fleet;
+use crate::miner::{Miner, MiningSettings};
+use crate::push::{Notifier, WebPush};
+use crate::render::AgentSwitcher;
+use crate::store::Store;
+use crate::AppStateShared;
+
+/// Which agent a browser is looking at. Set by the switcher in the header.
+pub const AGENT_COOKIE: &str = "agent";
+
+/// Everything that belongs to one agent: its API client, its cache, and the
+/// routes its ships are flying and the mining they're doing.
+pub struct AgentState {
+    pub name: String,
+    pub config: AgentConfig,
+    pub conf: Configuration,
+    pub store: Store,
+    pub executor: Executor,
+    pub miner: Miner,
+    pub fleet: Fleet,
+    pub notifier: Notifier,
+    /// Set when the server doesn't know our token and we've nobody to
+    /// register as, so someone has to fill in the registration form.
+    pub registration_needed: AtomicBool,
+}
+
+impl AgentState {
+    pub async fn open(
+        config: &Config,
+        agent: &AgentConfig,
+        web_push: Option<Arc<WebPush>>,
+    ) -> anyhow::Result<AgentState> {
+        let mut conf = Configuration::new();
+        conf.base_path = agent
+            .base_path
+            .clone()
+            .unwrap_or_else(|| config.base_path.clone());
+        conf.bearer_access_token = agent.token.clone();
+        // Capture a session to a cassette, or play one back without the
+        // network
+        if let Some(path) = &agent.record {
+            conf.client = cassette::client(Cassette::record(path)?);
+        } else if let Some(path) = &agent.replay {
+            conf.client = cassette::client(Cassette::replay(path)?);
+        }
+
+        let store = Store::open(&agent.db_path(), config.cache.clone())?;
+        let notifier = Notifier::new(web_push, store.clone());
+        let fleet = Fleet::start(conf.clone(), store.clone(), notifier.clone());
+        let executor = Executor::new(conf.clone(), store.clone(), fleet.clone());
+        executor.resume().await?;
+        let settings = MiningSettings {
+            sell_floor: config.sell_floor,
+            jettison_below: config.jettison_below,
+        };
+        let miner = Miner::new(
+            conf.clone(),
+            store.clone(),
+            fleet.clone(),
+            executor.clone(),
+            settings,
+        );
+        miner.resume().await?;
+
+        Ok(AgentState {
+            name: agent.name.clone(),
+            config: agent.clone(),
+            conf,
+            store,
+            executor,
+            miner,
+            fleet,
+            notifier,
+            registration_needed: AtomicBool::new(false),
+        })
+    }
+
+    /**
+     * The same agent, talking to the server as `token` instead. It shares our
+     * cache, but gets its own executor and miner, which are left for
+     * `AppState::replace_agent` to start, and its own fleet tracker.
+     */
+    pub fn with_token(&self, token: String) -> AgentState {
+        let mut conf = self.conf.clone();
+        conf.bearer_access_token = Some(token);
+        let fleet = Fleet::start(conf.clone(), self.store.clone(), self.notifier.clone());
+        let executor = Executor::new(conf.clone(), self.store.clone(), fleet.clone());
+        AgentState {
+            name: self.name.clone(),
+            config: self.config.clone(),
+            miner: Miner::new(
+                conf.clone(),
+                self.store.clone(),
+                fleet.clone(),
+                executor.clone(),
+                self.miner.settings,
+            ),
+            executor,
+            fleet,
+            notifier: self.notifier.clone(),
+            conf,
+            store: self.store.clone(),
+            registration_needed: AtomicBool::new(false),
+        }
+    }
+}
+
+/**
+ * The agent the request's browser has picked, or the first one configured if
+ * it hasn't picked one (or picked one that's since been removed from the
+ * config).
+ */
+pub struct CurrentAgent(pub Arc<AgentState>);
+
+#[async_trait]
+impl FromRequestParts<AppStateShared> for CurrentAgent {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppStateShared,
+    ) -> Result<Self, Self::Rejection> {
+        let name = parts
+            .headers
+            .get_all("cookie")
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(';'))
+            .filter_map(|c| c.trim().split_once('='))
+            .find(|(k, _)| *k == AGENT_COOKIE)
+            .map(|(_, v)| v);
+
+        let agent = name
+            .and_then(|name| state.agent(name))
+            .unwrap_or_else(|| state.agents.read()[0].clone());
+        Ok(CurrentAgent(agent))
+    }
+}
+
+impl crate::AppState {
+    pub fn agent(&self, name: &str) -> Option<Arc<AgentState>> {
+        self.agents.read().iter().find(|a| a.name == name).cloned()
+    }
+
+    pub fn agent_switcher(&self, current: &AgentState) -> AgentSwitcher {
+        AgentSwitcher {
+            current: current.name.clone(),
+            agents: self.agents.read().iter().map(|a| a.name.clone()).collect(),
+        }
+    }
+
+    /**
+     * Swaps in `agent` for the one with the same name, e.g. after registering
+     * it with a new token. Requests already holding the old one finish with
+     * it, but its ships stop flying and mining, and the new executor and
+     * miner take over.
+     */
+    pub async fn replace_agent(&self, agent: AgentState) -> anyhow::Result<Arc<AgentState>> {
+        let agent = Arc::new(agent);
+        let old = {
+            let mut agents = self.agents.write();
+            let slot = agents
+                .iter_mut()
+                .find(|a| a.name == agent.name)
+                .ok_or_else(|| anyhow::anyhow!("No agent called {}", agent.name))?;
+            std::mem::replace(slot, agent.clone())
+        };
+        old.executor.stop();
+        old.miner.stop();
+        old.fleet.stop();
+        agent.executor.resume().await?;
+        agent.miner.resume().await?;
+        Ok(agent)
+    }
+}
@@ -0,0 +1,255 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Deserializer};
+
+use spacedust::models::FactionSymbol;
+
+/**
+ * Settings come from `config.toml` (or whatever `SPACETRADERS_CONFIG` points
+ * at), and then the environment gets the last word. Something like:
+ *
+ * ```toml
+ * bind = "0.0.0.0:3001"
+ * sell_floor = 0.8
+ * jettison_below = 5
+ *
+ * [cache]
+ * shipyard = "5m"
+ * market_listing = "1day"
+ *
+ * [web_push]
+ * private_key = "IQ9Ur0ykXo..."
+ * subject = "mailto:me@example.com"
+ *
+ * [[agents]]
+ * name = "main"
+ * token = "eyJhbGciOi..."
+ *
+ * [[agents]]
+ * name = "mock"
+ * base_path = "http://127.0.0.1:3002/v2"
+ * # Sign up again by ourselves whenever the server resets
+ * register = { symbol = "MOCK_AGENT", faction = "COSMIC" }
+ * ```
+ *
+ * An agent can also come entirely from the environment, via
+ * `SPACETRADERS_TOKEN` and friends. That's the one `SPACETRADERS_RECORD` and
+ * `SPACETRADERS_REPLAY` apply to. With nothing configured at all, we start a
+ * "default" agent and ask for its details on the registration page.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    /// Where agents that don't say otherwise find the API
+    pub base_path: String,
+    pub cache: CacheTtls,
+    /// How often to ask the server whether it's been reset
+    #[serde(deserialize_with = "duration")]
+    pub reset_check: Duration,
+    /// Stop selling a good once its price falls below this fraction of what
+    /// the first lot went for
+    pub sell_floor: f64,
+    /// Ships mining on their own throw out anything no market pays at least
+    /// this much a unit for
+    pub jettison_below: i32,
+    /// Leave out to not send notifications
+    pub web_push: Option<WebPushConfig>,
+    pub agents: Vec<AgentConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: "0.0.0.0:3001".parse().unwrap(),
+            base_path: "https://api.spacetraders.io/v2".to_string(),
+            cache: CacheTtls::default(),
+            reset_check: Duration::from_secs(10 * 60),
+            sell_floor: 0.5,
+            jettison_below: 1,
+            web_push: None,
+            agents: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    /// What the agent switcher calls it. Doesn't have to match the agent's
+    /// symbol in the game.
+    pub name: String,
+    pub token: Option<String>,
+    pub base_path: Option<String>,
+    /// Defaults to `db-<name>.sqlite`
+    pub db: Option<String>,
+    /// Write this agent's API traffic to a cassette file
+    pub record: Option<String>,
+    /// Answer this agent's API requests from a cassette file instead of the
+    /// network
+    pub replay: Option<String>,
+    /// Who to register as when the token stops working
+    pub register: Option<Registration>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registration {
+    pub symbol: String,
+    pub faction: FactionSymbol,
+    pub email: Option<String>,
+}
+
+impl AgentConfig {
+    fn named(name: String) -> AgentConfig {
+        AgentConfig {
+            name,
+            token: None,
+            base_path: None,
+            db: None,
+            record: None,
+            replay: None,
+            register: None,
+        }
+    }
+
+    pub fn db_path(&self) -> String {
+        self.db
+            .clone()
+            .unwrap_or_else(|| format!("db-{}.sqlite", self.name))
+    }
+}
+
+/**
+ * The VAPID key that signs our notifications, so push services know they're
+ * from us. `just vapid_key` makes one.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebPushConfig {
+    /// The raw private key, base64url encoded
+    pub private_key: String,
+    /// How push services can get hold of us, like "mailto:me@example.com"
+    pub subject: Option<String>,
+    /// How long before a contract's deadline to warn about it
+    #[serde(default = "contract_warning", deserialize_with = "duration")]
+    pub contract_warning: Duration,
+}
+
+fn contract_warning() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+/**
+ * How long cached API responses stay good for. Anything without a TTL is kept
+ * until it's replaced.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheTtls {
+    #[serde(deserialize_with = "duration")]
+    pub shipyard: Duration,
+    /// What a market trades (not its prices) only changes between resets
+    #[serde(deserialize_with = "optional_duration")]
+    pub market_listing: Option<Duration>,
+    #[serde(deserialize_with = "optional_duration")]
+    pub waypoints: Option<Duration>,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        CacheTtls {
+            shipyard: Duration::from_secs(5 * 60),
+            market_listing: None,
+            waypoints: None,
+        }
+    }
+}
+
+/// Durations are written the humantime way, like "5m" or "2h 30m".
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+fn optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    duration(deserializer).map(Some)
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Config> {
+        let path = env("SPACETRADERS_CONFIG");
+        let mut config = match std::fs::read_to_string(path.as_deref().unwrap_or("config.toml")) {
+            Ok(text) => toml::from_str(&text).context("Couldn't parse the config file")?,
+            // The file's optional unless someone asked for it by name
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && path.is_none() => {
+                Config::default()
+            }
+            Err(err) => return Err(err).context("Couldn't read the config file"),
+        };
+
+        if let Some(bind) = env("SPACETRADERS_BIND") {
+            config.bind = bind.parse().context("SPACETRADERS_BIND isn't an address")?;
+        }
+        if let Some(base_path) = env("SPACETRADERS_BASE_PATH") {
+            config.base_path = base_path;
+        }
+        if let Some(private_key) = env("SPACETRADERS_VAPID_KEY") {
+            match &mut config.web_push {
+                Some(web_push) => web_push.private_key = private_key,
+                None => {
+                    config.web_push = Some(WebPushConfig {
+                        private_key,
+                        subject: None,
+                        contract_warning: contract_warning(),
+                    })
+                }
+            }
+        }
+
+        let env_agent = ["TOKEN", "RECORD", "REPLAY"]
+            .iter()
+            .any(|v| env(&format!("SPACETRADERS_{v}")).is_some());
+        if env_agent {
+            let name = env("SPACETRADERS_AGENT").unwrap_or_else(|| "default".to_string());
+            let index = match config.agents.iter().position(|a| a.name == name) {
+                Some(index) => index,
+                None => {
+                    config.agents.push(AgentConfig::named(name));
+                    config.agents.len() - 1
+                }
+            };
+            let agent = &mut config.agents[index];
+            agent.token = env("SPACETRADERS_TOKEN").or(agent.token.take());
+            agent.record = env("SPACETRADERS_RECORD").or(agent.record.take());
+            agent.replay = env("SPACETRADERS_REPLAY").or(agent.replay.take());
+        }
+
+        if config.agents.is_empty() {
+            config
+                .agents
+                .push(AgentConfig::named("default".to_string()));
+        }
+        for agent in &config.agents {
+            if config
+                .agents
+                .iter()
+                .filter(|a| a.name == agent.name)
+                .count()
+                > 1
+            {
+                anyhow::bail!("More than one agent is called {}", agent.name);
+            }
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+use spacedust::apis::api_error::ApiErrorKind;
+use spacedust::apis::Error;
+
+use crate::fragments;
+
+/**
+ * Why a request couldn't be done. The game turning us down is the usual
+ * reason, and the interesting one: the kind of refusal decides both what we
+ * tell the user and the status we send back, so unpoly can swap the message
+ * into the right spot (see `fragments::error_html`).
+ */
+#[derive(Debug)]
+pub enum AppError {
+    /// The game understood the request, and said no
+    Game {
+        status: StatusCode,
+        /// Boxed, since some kinds carry a fair bit of detail
+        kind: Box<ApiErrorKind>,
+        message: String,
+    },
+    /// We turned the request down ourselves, before asking the game
+    Invalid(String),
+    /// We couldn't reach the game, or couldn't make sense of its answer
+    Upstream(String),
+    /// Our own fault, like the database falling over
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Game { status, kind, .. } => match kind.as_ref() {
+                ApiErrorKind::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+                ApiErrorKind::Cooldown(_)
+                | ApiErrorKind::InTransit(_)
+                | ApiErrorKind::SameDestination
+                | ApiErrorKind::NotInOrbit
+                | ApiErrorKind::NotDocked
+                | ApiErrorKind::SurveyExpired
+                | ApiErrorKind::SurveyExhausted
+                | ApiErrorKind::SymbolTaken => StatusCode::CONFLICT,
+                ApiErrorKind::InsufficientFuel(_)
+                | ApiErrorKind::InsufficientFunds(_)
+                | ApiErrorKind::TradeUnitLimit(_)
+                | ApiErrorKind::TradeNotAvailable
+                | ApiErrorKind::CargoFull
+                | ApiErrorKind::CargoExceedsLimit(_)
+                | ApiErrorKind::CargoMissing
+                | ApiErrorKind::SurveyInvalid
+                | ApiErrorKind::WaypointNoAccess => StatusCode::UNPROCESSABLE_ENTITY,
+                ApiErrorKind::Unauthorized => StatusCode::UNAUTHORIZED,
+                // Pass on whatever the game said, as long as it blames the
+                // request rather than itself
+                ApiErrorKind::Other(_) if status.is_client_error() => *status,
+                ApiErrorKind::Other(_) => StatusCode::BAD_GATEWAY,
+            },
+            AppError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Something to show the user, in the game's terms where we can.
+    pub fn message(&self) -> String {
+        match self {
+            AppError::Game { kind, message, .. } => match kind.as_ref() {
+                ApiErrorKind::Cooldown(data) => format!(
+                    "Ship is on cooldown for {}s",
+                    data.cooldown.remaining_seconds
+                ),
+                ApiErrorKind::InTransit(data) => match data.seconds_to_arrival {
+                    Some(seconds) => format!("Ship is in transit for another {seconds}s"),
+                    None => "Ship is in transit".to_string(),
+                },
+                ApiErrorKind::InsufficientFuel(data) => {
+                    match (data.fuel_required, data.fuel_available) {
+                        (Some(required), Some(available)) => {
+                            format!("Not enough fuel: need {required}, have {available}")
+                        }
+                        _ => "Not enough fuel".to_string(),
+                    }
+                }
+                ApiErrorKind::InsufficientFunds(data) => {
+                    match (data.total_price, data.credits_available) {
+                        (Some(price), Some(credits)) => {
+                            format!("Not enough credits: costs {price}, have {credits}")
+                        }
+                        _ => "Not enough credits".to_string(),
+                    }
+                }
+                ApiErrorKind::RateLimited(data) => match data.retry_after {
+                    Some(seconds) => format!("Rate limited, try again in {seconds:.1}s"),
+                    None => "Rate limited".to_string(),
+                },
+                ApiErrorKind::NotInOrbit => "Ship needs to be in orbit".to_string(),
+                ApiErrorKind::NotDocked => "Ship needs to be docked".to_string(),
+                ApiErrorKind::CargoFull => "Cargo hold is full".to_string(),
+                _ => message.clone(),
+            },
+            AppError::Invalid(message) => message.clone(),
+            AppError::Upstream(message) => format!("Couldn't reach the game: {message}"),
+            AppError::Internal(err) => format!("Something went wrong: {err:#}"),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if let AppError::Internal(err) = &self {
+            println!("Request failed: {err:#}");
+        }
+        (self.status(), fragments::error_html(&self)).into_response()
+    }
+}
+
+impl<T> From<Error<T>> for AppError {
+    fn from(err: Error<T>) -> Self {
+        match &err {
+            Error::ResponseError(response) => match response.api_error() {
+                Some(body) => AppError::Game {
+                    status: response.status,
+                    kind: Box::new(body.kind()),
+                    message: body.message,
+                },
+                None => AppError::Game {
+                    status: response.status,
+                    kind: Box::new(ApiErrorKind::Other(response.status.as_u16().into())),
+                    message: format!("The game responded with {}", response.status),
+                },
+            },
+            _ => AppError::Upstream(err.to_string()),
+        }
+    }
+}
+
+// Anything else, like the store failing, is on us. An AppError that's been
+// through anyhow (say, from the executor) keeps its classification.
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<AppError>() {
+            Ok(err) => err,
+            Err(err) => AppError::Internal(err),
+        }
+    }
+}
+
+pub type AppResult<T> = Result<T, AppError>;
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use spacedust::apis::configuration::Configuration;
+use spacedust::apis::fleet_api;
+use spacedust::models::{
+    NavigateShipRequest, PatchShipNavRequest, RefuelShipRequest, Ship, ShipNavStatus,
+};
+use spacedust::rate_limit;
+
+use crate::fleet::Fleet;
+use crate::nav::{Hop, RoutePlan};
+use crate::spacetraders;
+use crate::store::Store;
+
+/// A plan a ship is partway through flying.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveRoute {
+    pub plan: RoutePlan,
+    /// Index into `plan.hops` of the leg we haven't finished yet
+    pub next_hop: usize,
+    /// Why we gave up, if we did. The route stays around so the ship card can
+    /// say what happened until someone cancels it.
+    pub error: Option<String>,
+}
+
+impl ActiveRoute {
+    pub fn destination(&self) -> Option<&str> {
+        self.plan.hops.last().map(|h| h.to.as_str())
+    }
+}
+
+/**
+ * Flies ships along their planned routes without anyone clicking "go" at
+ * every stop. Progress is saved after each leg, so a restart picks up where
+ * it left off via `resume`.
+ */
+#[derive(Clone)]
+pub struct Executor {
+    conf: Configuration,
+    store: Store,
+    /// Told whenever a ship moves on, so its card keeps up
+    fleet: Fleet,
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+/// Each ship's task, with a number telling it apart from any task that flew
+/// the ship before.
+type Tasks = HashMap<String, (u64, JoinHandle<()>)>;
+
+static NEXT_TASK: AtomicU64 = AtomicU64::new(0);
+
+impl Executor {
+    pub fn new(conf: Configuration, store: Store, fleet: Fleet) -> Executor {
+        Executor {
+            conf,
+            store,
+            fleet,
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Restarts every route that was still in flight when we last shut down.
+    pub async fn resume(&self) -> anyhow::Result<()> {
+        for (ship_symbol, route) in self.store.ship_routes().await? {
+            if route.error.is_none() {
+                self.spawn(ship_symbol);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces whatever the ship was doing with `plan`.
+    pub async fn start(&self, ship_symbol: &str, plan: RoutePlan) -> anyhow::Result<()> {
+        self.abort(ship_symbol);
+        let route = ActiveRoute {
+            plan,
+            next_hop: 0,
+            error: None,
+        };
+        self.store.put_ship_route(ship_symbol, &route).await?;
+        self.spawn(ship_symbol.to_string());
+        Ok(())
+    }
+
+    pub async fn cancel(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        self.abort(ship_symbol);
+        self.store.delete_ship_route(ship_symbol).await
+    }
+
+    /// Stops flying everything, but leaves the routes saved so another
+    /// executor can `resume` them.
+    pub fn stop(&self) {
+        for (_, (_, task)) in self.tasks.lock().drain() {
+            task.abort();
+        }
+    }
+
+    fn abort(&self, ship_symbol: &str) {
+        if let Some((_, task)) = self.tasks.lock().remove(ship_symbol) {
+            task.abort();
+        }
+    }
+
+    fn spawn(&self, ship_symbol: String) {
+        // Held until the handle's in, or a quick task could finish and clear
+        // its entry before there was one, leaving a dead handle behind
+        let mut tasks = self.tasks.lock();
+        let id = NEXT_TASK.fetch_add(1, Ordering::Relaxed);
+        let executor = self.clone();
+        let task_ship_symbol = ship_symbol.clone();
+        let task = tokio::spawn(async move {
+            let ship_symbol = task_ship_symbol;
+            // Flying on autopilot should never hold up someone's click
+            let result = rate_limit::background(executor.run(&ship_symbol)).await;
+            if let Err(err) = result {
+                println!("Route for {ship_symbol} failed: {err:#}");
+                if let Ok(Some(mut route)) = executor.store.ship_route(&ship_symbol).await {
+                    route.error = Some(format!("{err:#}"));
+                    let _ = executor.store.put_ship_route(&ship_symbol, &route).await;
+                }
+            }
+            {
+                // If the ship's been given a new route since, the entry is
+                // the new task's to clear
+                let mut tasks = executor.tasks.lock();
+                if tasks
+                    .get(&ship_symbol)
+                    .is_some_and(|(task_id, _)| *task_id == id)
+                {
+                    tasks.remove(&ship_symbol);
+                }
+            }
+            // Either way the route's over, or stuck, which the card should
+            // say
+            let _ = executor.fleet.refresh(&ship_symbol).await;
+        });
+        tasks.insert(ship_symbol, (id, task));
+    }
+
+    async fn run(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        loop {
+            let Some(mut route) = self.store.ship_route(ship_symbol).await? else {
+                return Ok(());
+            };
+            let Some(hop) = route.plan.hops.get(route.next_hop).cloned() else {
+                self.store.delete_ship_route(ship_symbol).await?;
+                return Ok(());
+            };
+
+            let ship = self.fly(ship_symbol, &hop).await?;
+            self.fleet.track(&ship);
+            // The card lagging behind is no reason to strand the ship
+            if let Err(err) = self.fleet.refresh(ship_symbol).await {
+                println!("Couldn't refresh {ship_symbol}: {err}");
+            }
+            wait_for_arrival(&ship).await;
+
+            route.next_hop += 1;
+            self.store.put_ship_route(ship_symbol, &route).await?;
+        }
+    }
+
+    /// Gets the ship moving along `hop`, refuelling first if the plan says
+    /// to.
+    async fn fly(&self, ship_symbol: &str, hop: &Hop) -> anyhow::Result<Ship> {
+        let conf = &self.conf;
+        let mut ship = spacetraders::get_ship(conf, ship_symbol).await?;
+
+        // We may have restarted after sending the ship off but before saving
+        // that we had
+        if ship.nav.route.destination.symbol == hop.to
+            && (ship.nav.status == ShipNavStatus::InTransit || ship.nav.waypoint_symbol == hop.to)
+        {
+            return Ok(ship);
+        }
+
+        if ship.nav.status == ShipNavStatus::InTransit {
+            wait_for_arrival(&ship).await;
+            ship = spacetraders::get_ship(conf, ship_symbol).await?;
+        }
+
+        if ship.nav.waypoint_symbol != hop.from {
+            anyhow::bail!(
+                "expected {ship_symbol} to be at {}, but it's at {}",
+                hop.from,
+                ship.nav.waypoint_symbol
+            );
+        }
+
+        if hop.refuel && ship.fuel.current < ship.fuel.capacity {
+            if ship.nav.status != ShipNavStatus::Docked {
+                fleet_api::dock_ship(conf, ship_symbol).await?;
+            }
+            fleet_api::refuel_ship(conf, ship_symbol, Some(RefuelShipRequest::new())).await?;
+            ship.nav.status = ShipNavStatus::Docked;
+        }
+
+        if ship.nav.status == ShipNavStatus::Docked {
+            fleet_api::orbit_ship(conf, ship_symbol).await?;
+        }
+
+        if ship.nav.flight_mode != hop.mode {
+            fleet_api::patch_ship_nav(
+                conf,
+                ship_symbol,
+                Some(PatchShipNavRequest {
+                    flight_mode: Some(hop.mode),
+                }),
+            )
+            .await?;
+        }
+
+        let response = fleet_api::navigate_ship(
+            conf,
+            ship_symbol,
+            Some(NavigateShipRequest::new(hop.to.clone())),
+        )
+        .await?;
+        ship.nav = response.data.nav;
+        Ok(ship)
+    }
+}
+
+async fn wait_for_arrival(ship: &Ship) {
+    let Ok(arrival) = chrono::DateTime::parse_from_rfc3339(&ship.nav.route.arrival) else {
+        return;
+    };
+    let wait = arrival.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    if let Ok(wait) = wait.to_std() {
+        // The server sometimes takes a moment to agree that we've landed
+        tokio::time::sleep(wait + std::time::Duration::from_secs(1)).await;
+    }
+}
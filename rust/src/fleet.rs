@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+
+use spacedust::apis::configuration::Configuration;
+use spacedust::models::{Ship, ShipNavStatus};
+use spacedust::rate_limit;
+
+use crate::error::AppResult;
+use crate::fragments;
+use crate::push::{Notification, Notifier};
+use crate::spacetraders::{self, ShipOrShipSymbol};
+use crate::store::Store;
+
+/**
+ * Knows when each ship is next due to change, i.e. when it lands or its
+ * cooldown runs out, and only asks the API about a ship then. The fresh ship
+ * card goes out to every browser subscribed through `/fleet/events`, so
+ * pages don't have to poll for each ship themselves.
+ *
+ * The whole fleet gets tracked when we start, and a ship again whenever
+ * something shows or moves it (the index page, the buttons on a ship card,
+ * the executor). When a ship lands, its cooldown runs out or its hold fills
+ * up, subscribers to web push hear about it too. That doesn't need the API,
+ * so it happens even with nobody on the page, for any ship, not just ones
+ * someone's looked at since we started.
+ */
+#[derive(Clone)]
+pub struct Fleet {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    conf: Configuration,
+    store: Store,
+    /// Ships with a change coming up, as we last saw them
+    ships: Mutex<HashMap<String, Ship>>,
+    /// Ships we've already said are full, so we only say it once
+    cargo_full: Mutex<HashSet<String>>,
+    notifier: Notifier,
+    /// Wakes the loop when a ship is due sooner than it was sleeping for
+    changed: Notify,
+    /// Ship cards that have changed, as HTML
+    updates: broadcast::Sender<String>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Fleet {
+    pub fn start(conf: Configuration, store: Store, notifier: Notifier) -> Fleet {
+        let (updates, _) = broadcast::channel(64);
+        let fleet = Fleet {
+            inner: Arc::new(Inner {
+                conf,
+                store,
+                ships: Mutex::new(HashMap::new()),
+                cargo_full: Mutex::new(HashSet::new()),
+                notifier,
+                changed: Notify::new(),
+                updates,
+                task: Mutex::new(None),
+            }),
+        };
+        let task = tokio::spawn(fleet.clone().run());
+        *fleet.inner.task.lock() = Some(task);
+        fleet
+    }
+
+    /// Stops refreshing ships, e.g. when the agent's been replaced.
+    pub fn stop(&self) {
+        if let Some(task) = self.inner.task.lock().take() {
+            task.abort();
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.inner.updates.subscribe()
+    }
+
+    /// Notes when `ship` will next change, if it's in transit or cooling
+    /// down.
+    pub fn track(&self, ship: &Ship) {
+        self.check_cargo(ship);
+
+        let mut ships = self.inner.ships.lock();
+        if next_change(ship).is_some() {
+            ships.insert(ship.symbol.clone(), ship.clone());
+            self.inner.changed.notify_one();
+        } else {
+            ships.remove(&ship.symbol);
+        }
+    }
+
+    fn check_cargo(&self, ship: &Ship) {
+        let mut full = self.inner.cargo_full.lock();
+        if !cargo_full(ship) {
+            full.remove(&ship.symbol);
+        } else if full.insert(ship.symbol.clone()) {
+            self.inner.notifier.notify(Notification {
+                title: format!("{}'s cargo hold is full", ship.symbol),
+                body: format!(
+                    "{}/{} units at {}",
+                    ship.cargo.units, ship.cargo.capacity, ship.nav.waypoint_symbol
+                ),
+                url: "/".to_string(),
+            });
+        }
+    }
+
+    /**
+     * Sends out a fresh card for the ship now, for when it's changed in a way
+     * we couldn't have predicted, like the executor sending it off on the
+     * next leg of a route.
+     */
+    pub async fn refresh(&self, ship_symbol: &str) -> AppResult<()> {
+        if self.inner.updates.receiver_count() == 0 {
+            return Ok(());
+        }
+
+        let inner = &self.inner;
+        let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
+            &inner.conf,
+            ShipOrShipSymbol::Symbol(ship_symbol.to_string()),
+            &inner.store,
+        )
+        .await?;
+        self.track(&ship);
+
+        let route = inner.store.ship_route(&ship.symbol).await?;
+        let mining = inner.store.mining_job(&ship.symbol).await?;
+        let html = fragments::ship_html(ship, waypoint, route, mining, None).into_string();
+        // Only fails if everyone's stopped listening since we checked
+        let _ = inner.updates.send(html);
+        Ok(())
+    }
+
+    async fn run(self) {
+        // Start from the whole fleet, so a ship that was already flying when
+        // we started still gets noticed when it lands
+        match rate_limit::background(spacetraders::get_my_ships(&self.inner.conf)).await {
+            Ok(ships) => {
+                for ship in &ships {
+                    // Holds that were full before we started aren't news
+                    if cargo_full(ship) {
+                        self.inner.cargo_full.lock().insert(ship.symbol.clone());
+                    }
+                    self.track(ship);
+                }
+            }
+            Err(err) => println!("Couldn't load the fleet: {err}"),
+        }
+
+        loop {
+            let next = self
+                .inner
+                .ships
+                .lock()
+                .values()
+                .filter_map(next_change)
+                .min();
+            let changed = self.inner.changed.notified();
+            match next.and_then(|at| (at - Utc::now()).to_std().ok()) {
+                Some(wait) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = changed => continue,
+                    }
+                }
+                // Either something's already due, or nothing is
+                None if next.is_none() => {
+                    changed.await;
+                    continue;
+                }
+                None => {}
+            }
+
+            let now = Utc::now();
+            let ready: Vec<Ship> = {
+                let mut ships = self.inner.ships.lock();
+                let ready = ships
+                    .iter()
+                    .filter(|(_, ship)| next_change(ship).is_some_and(|at| at <= now))
+                    .map(|(ship_symbol, _)| ship_symbol.clone())
+                    .collect::<Vec<_>>();
+                ready
+                    .iter()
+                    .filter_map(|ship_symbol| ships.remove(ship_symbol))
+                    .collect()
+            };
+
+            for ship in ready {
+                let ship = self.changed(ship, now).await;
+                if self.inner.updates.receiver_count() == 0 {
+                    // Keep going on what we know, in case there's another
+                    // change coming
+                    self.track(&ship);
+                    continue;
+                }
+                // Catching up on the fleet should never hold up a click
+                if let Err(err) = rate_limit::background(self.refresh(&ship.symbol)).await {
+                    println!("Couldn't refresh {}: {err}", ship.symbol);
+                }
+            }
+        }
+    }
+
+    /// Says what's happened to `ship` by `now`, and works out what it must
+    /// look like as a result.
+    async fn changed(&self, mut ship: Ship, now: DateTime<Utc>) -> Ship {
+        let notifier = &self.inner.notifier;
+
+        let landed = ship.nav.status == ShipNavStatus::InTransit
+            && parse(&ship.nav.route.arrival).is_some_and(|at| at <= now);
+        if landed {
+            let destination = ship.nav.route.destination.symbol.clone();
+            // Partway through a route isn't worth mentioning
+            let route = self.inner.store.ship_route(&ship.symbol).await;
+            let onward = matches!(route, Ok(Some(route)) if route.destination() != Some(destination.as_str()));
+            if !onward {
+                notifier.notify(Notification {
+                    title: format!("{} has arrived", ship.symbol),
+                    body: format!("It's at {destination}"),
+                    url: "/".to_string(),
+                });
+            }
+            ship.nav.status = ShipNavStatus::InOrbit;
+            ship.nav.waypoint_symbol = destination;
+        }
+
+        let cooled_down = ship
+            .cooldown
+            .expiration
+            .as_deref()
+            .and_then(parse)
+            .is_some_and(|at| at <= now);
+        if cooled_down {
+            notifier.notify(Notification {
+                title: format!("{} is ready", ship.symbol),
+                body: "Its cooldown is over".to_string(),
+                url: "/".to_string(),
+            });
+            ship.cooldown.expiration = None;
+            ship.cooldown.remaining_seconds = 0;
+        }
+
+        ship
+    }
+}
+
+fn cargo_full(ship: &Ship) -> bool {
+    ship.cargo.capacity > 0 && ship.cargo.units >= ship.cargo.capacity
+}
+
+/// When the ship lands or its cooldown runs out, whichever's first.
+fn next_change(ship: &Ship) -> Option<DateTime<Utc>> {
+    let arrival = (ship.nav.status == ShipNavStatus::InTransit)
+        .then(|| parse(&ship.nav.route.arrival))
+        .flatten();
+    let cooldown = ship.cooldown.expiration.as_deref().and_then(parse);
+    // The server sometimes takes a moment to agree that we've landed
+    [arrival, cooldown]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|at| at + chrono::Duration::seconds(1))
+}
+
+fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
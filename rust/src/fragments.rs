@@ -1,18 +1,42 @@
+use std::collections::{BTreeMap, HashSet};
+
 use maud::{html, Markup};
-use spacedust::models::{ExtractionYield, Ship, ShipNavStatus, Shipyard, WaypointTraitSymbol};
+use spacedust::models::{
+    ExtractionYield, Faction, JumpGate, Market, MarketTradeGood, Ship, ShipNavStatus,
+    ShipRefine201ResponseData, Shipyard, Survey, TradeGood, TradeSymbol, WaypointTraitSymbol,
+};
 
-use crate::spacetraders::{ShipWaypoint, WaypointFeatures};
+use crate::error::AppError;
+use crate::executor::ActiveRoute;
+use crate::miner::{MiningJob, MiningRole, MiningStage};
+use crate::nav::RoutePlan;
+use crate::spacetraders::{
+    can_mine, can_survey, minable, refinery_products, units_held, ShipWaypoint, WaypointFeatures,
+};
+use crate::store::PriceObservation;
+use crate::trade::{CargoSale, RefineOption, TradeRoute, REFINE_INPUT, REFINE_OUTPUT};
 
 fn from_now(iso: String) -> String {
     let now = chrono::Utc::now();
-    let deadline = chrono::DateTime::parse_from_rfc3339(&iso).unwrap();
+    let Ok(deadline) = chrono::DateTime::parse_from_rfc3339(&iso) else {
+        return iso;
+    };
     let duration = deadline.signed_duration_since(now);
-    let duration = duration.to_std().unwrap();
+    // Deadlines pass between the API answering and us rendering
+    let duration = duration.to_std().unwrap_or_default();
     let duration = std::time::Duration::from_secs(duration.as_secs());
     let duration = humantime::format_duration(duration);
     duration.to_string()
 }
 
+/// How long ago `at` was, to the second.
+fn ago(at: chrono::DateTime<chrono::Utc>) -> String {
+    let duration = chrono::Utc::now().signed_duration_since(at);
+    let duration = duration.to_std().unwrap_or_default();
+    let duration = std::time::Duration::from_secs(duration.as_secs());
+    humantime::format_duration(duration).to_string()
+}
+
 pub fn agent_html(agent: spacedust::models::Agent) -> Markup {
     html! {
         dl class="[&_dt]:text-sm [&_dt]:font-semibold [&_dt]:italic" {
@@ -57,7 +81,49 @@ pub fn contract_terms_html(terms: spacedust::models::ContractTerms) -> Markup {
     }
 }
 
-pub fn contract_html(contract: spacedust::models::Contract) -> Markup {
+/**
+ * The contracts, with buttons for whatever can be done with them next. The
+ * buttons all answer with this fragment again, since any of them can change
+ * more than the one contract (negotiating adds a new one, say).
+ */
+pub fn contracts_html(
+    contracts: Vec<spacedust::models::Contract>,
+    ships: &[Ship],
+    ships_at_factions: &HashSet<String>,
+) -> Markup {
+    html! {
+        div class="contracts" {
+            // Where the buttons below report failures
+            div class="error" {}
+
+            @if contracts.is_empty() {
+                div {"You have no contracts."}
+            }
+            @for contract in contracts {
+                (contract_html(contract, ships))
+            }
+
+            @for ship in ships.iter().filter(|s| ships_at_factions.contains(&s.symbol)) {
+                button
+                    up-href={"/ship_contract/" (ship.symbol) "/negotiate"}
+                    up-method="post"
+                    up-target=".contracts"
+                    up-fail-target=".error"
+                    class="underline"
+                {"Negotiate a contract with " (ship.symbol) " at " (ship.nav.waypoint_symbol)}
+            }
+        }
+    }
+}
+
+pub fn contract_html(contract: spacedust::models::Contract, ships: &[Ship]) -> Markup {
+    let terms = *contract.terms;
+    let delivers = terms.deliver.clone().unwrap_or_default();
+    let delivered = delivers
+        .iter()
+        .all(|d| d.units_fulfilled >= d.units_required);
+    let delivering = contract.accepted && !contract.fulfilled;
+
     html! {
         dl class="[&_dt]:text-sm [&_dt]:font-semibold [&_dt]:italic" {
             dt {"ID"}
@@ -67,20 +133,96 @@ pub fn contract_html(contract: spacedust::models::Contract) -> Markup {
             dd {(contract.faction_symbol)}
 
             dt {"Accepted"}
-            dd {(contract.accepted)}
+            dd {
+                (contract.accepted)
+                @if !contract.accepted {
+                    button
+                        up-href={"/contracts/" (contract.id) "/accept"}
+                        up-method="post"
+                        up-target=".contracts"
+                        up-fail-target=".error"
+                        class="ml-2 underline"
+                    {"Accept"}
+                }
+            }
 
             dt {"Fulfilled"}
-            dd {(contract.fulfilled)}
+            dd {
+                (contract.fulfilled)
+                @if delivering && delivered {
+                    button
+                        up-href={"/contracts/" (contract.id) "/fulfill"}
+                        up-method="post"
+                        up-target=".contracts"
+                        up-fail-target=".error"
+                        class="ml-2 underline"
+                    {"Fulfill"}
+                }
+            }
 
             dt {"Expiration"}
             dd {(from_now(contract.expiration))}
 
             dt {"Terms"}
-            dd class="ml-4" {(contract_terms_html(*contract.terms))}
+            dd class="ml-4" {(contract_terms_html(terms))}
+
+            @if delivering {
+                @for deliver in &delivers {
+                    @let remaining = deliver.units_required - deliver.units_fulfilled;
+                    @for (ship, held) in ships_to_deliver(ships, deliver) {
+                        dt {"Deliver " (deliver.trade_symbol) " from " (ship.symbol)}
+                        dd {
+                            form
+                                method="POST"
+                                action={"/contracts/" (contract.id) "/deliver"}
+                                up-target=".contracts"
+                                up-fail-target=".error"
+                            {
+                                input type="hidden" name="ship_symbol" value=(ship.symbol);
+                                input type="hidden" name="trade_symbol" value=(deliver.trade_symbol);
+                                input
+                                    type="number"
+                                    name="units"
+                                    min="1"
+                                    max=(held.min(remaining))
+                                    value=(held.min(remaining))
+                                    class="w-20 border";
+                                button class="ml-2 underline" {"Deliver"}
+                            }
+                        }
+                    }
+                }
+            }
         }
     }
 }
 
+/// Ships docked where the goods need to go, with how many of them they've got.
+fn ships_to_deliver<'a>(
+    ships: &'a [Ship],
+    deliver: &spacedust::models::ContractDeliverGood,
+) -> Vec<(&'a Ship, i32)> {
+    if deliver.units_fulfilled >= deliver.units_required {
+        return vec![];
+    }
+    ships
+        .iter()
+        .filter(|s| {
+            s.nav.status == ShipNavStatus::Docked
+                && s.nav.waypoint_symbol == deliver.destination_symbol
+        })
+        .filter_map(|s| {
+            let held = s
+                .cargo
+                .inventory
+                .iter()
+                .find(|i| i.symbol.to_string() == deliver.trade_symbol)?
+                .units;
+            Some((s, held))
+        })
+        .collect()
+}
+
 pub fn waypoint_html(
     waypoint: spacedust::models::Waypoint,
     nav_distance: Option<(&Ship, f64)>,
@@ -92,10 +234,11 @@ pub fn waypoint_html(
                 span class="text-sm text-gray-700 italic" {
                     (waypoint.symbol)
                     @if let Some((ship, dist)) = nav_distance {
-                        form method="POST" action=(format!("/ship_nav/{ship_symbol}/go/{waypoint}", ship_symbol=ship.symbol, waypoint=waypoint.symbol)) up-layer="parent" {
+                        form method="POST" action=(format!("/ship_nav/{ship_symbol}/go/{waypoint}", ship_symbol=ship.symbol, waypoint=waypoint.symbol)) up-layer="parent" up-fail-target=".error" {
                             button {
                                 (dist) i class="bi-arrow-right" {}
                             }
+                            div class="error not-italic" {}
                         }
                     }
                 }
@@ -112,6 +255,14 @@ pub fn waypoint_html(
                                     up-history="false"
                                 {(waypoint_trait.name)}
                             }
+                            WaypointTraitSymbol::Marketplace => {
+                                a
+                                    href={"/market/" (waypoint.system_symbol) "/" (waypoint.symbol)}
+                                    class="underline"
+                                    up-layer="new"
+                                    up-history="false"
+                                {(waypoint_trait.name)}
+                            }
                             _ => {
                                 (waypoint_trait.name)
                             }
@@ -123,6 +274,37 @@ pub fn waypoint_html(
     }
 }
 
+pub fn route_plan_html(plan: Option<RoutePlan>) -> Markup {
+    let Some(plan) = plan else {
+        return html! {
+            div class="text-sm text-red-700 ml-4" {"Can't get there from here, even drifting."}
+        };
+    };
+
+    html! {
+        details class="text-sm ml-4" {
+            summary {
+                (humantime::format_duration(std::time::Duration::from_secs(plan.duration() as u64)))
+                ", " (plan.fuel()) " fuel"
+                @if plan.refuel_stops() > 0 {
+                    ", " (plan.refuel_stops()) " refuel stops"
+                }
+            }
+            ol class="ml-4 list-decimal" {
+                @for hop in plan.hops {
+                    li {
+                        @if hop.refuel {
+                            "Refuel at " (hop.from) ", then "
+                        }
+                        (hop.mode.to_string().to_lowercase()) " to " (hop.to)
+                        " (" (hop.fuel) " fuel, " (humantime::format_duration(std::time::Duration::from_secs(hop.duration as u64))) ")"
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn waypoints_html(waypoints: Vec<spacedust::models::Waypoint>) -> Markup {
     let by_type = waypoints.clone();
     let (asteroids, by_type): (Vec<_>, Vec<_>) = by_type
@@ -166,6 +348,72 @@ pub fn waypoints_html(waypoints: Vec<spacedust::models::Waypoint>) -> Markup {
     }
 }
 
+pub fn jump_gates_html(jump_gates: Vec<(String, JumpGate)>) -> Markup {
+    html! {
+        @if jump_gates.is_empty() {
+            div {"No charted jump gates in this system."}
+        }
+        ul {
+            @for (waypoint_symbol, jump_gate) in jump_gates {
+                li {
+                    (waypoint_symbol) " connects to: " (jump_gate.connections.join(", "))
+                }
+            }
+        }
+    }
+}
+
+pub fn best_trades_html(best_trades: Vec<(String, Vec<TradeRoute>)>) -> Markup {
+    html! {
+        @if best_trades.is_empty() {
+            div {"None of your ships can carry cargo."}
+        }
+        @for (ship_symbol, trades) in best_trades {
+            details open {
+                summary {(ship_symbol)}
+                @if trades.is_empty() {
+                    div {"No profitable trades among the markets we've seen prices for."}
+                }
+                table class="[&_td]:px-2 [&_th]:px-2 text-left" {
+                    @if !trades.is_empty() {
+                        thead {
+                            tr {
+                                th {"Good"}
+                                th {"Buy at"}
+                                th {"Sell at"}
+                                th {"Units"}
+                                th {"Profit"}
+                                th {"Time"}
+                                th {"Per hour"}
+                            }
+                        }
+                    }
+                    tbody {
+                        @for trade in trades {
+                            tr {
+                                td {
+                                    a
+                                        href={"/trade_goods/" (trade.trade_symbol.to_string())}
+                                        class="underline decoration-dotted"
+                                        up-layer="new"
+                                        up-history="false"
+                                    {(trade.trade_symbol.to_string())}
+                                }
+                                td {(trade.buy_at) " @ " (trade.purchase_price)}
+                                td {(trade.sell_at) " @ " (trade.sell_price)}
+                                td {(trade.units)}
+                                td {(trade.profit)}
+                                td {(humantime::format_duration(std::time::Duration::from_secs(trade.duration as u64)))}
+                                td {(format!("{:.0}", trade.profit_per_hour()))}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub fn shipyard_html(shipyard: Shipyard) -> Markup {
     let Some(ships) = shipyard.ships else {
         return html! {
@@ -185,12 +433,16 @@ pub fn shipyard_html(shipyard: Shipyard) -> Markup {
                     div {"Supply: " (ship.supply.to_string().to_lowercase())}
                     div {"Price: " (ship.purchase_price)}
                     div {"Fuel: " (ship.frame.fuel_capacity)}
-                    form
-                        method="POST"
-                        action={"/waypoints/" (shipyard.symbol) "/buy_ship/" (ship.r#type.unwrap().to_string())}
-                        up-layer="parent"
-                    {
-                        button type="submit" class="border rounded-md bg-gray-100 px-2 py-1" {"Buy"}
+                    @if let Some(ship_type) = ship.r#type {
+                        form
+                            method="POST"
+                            action={"/waypoints/" (shipyard.symbol) "/buy_ship/" (ship_type.to_string())}
+                            up-layer="parent"
+                            up-fail-target=".error"
+                        {
+                            button type="submit" class="border rounded-md bg-gray-100 px-2 py-1" {"Buy"}
+                            div class="error" {}
+                        }
                     }
                 }
             }
@@ -199,29 +451,196 @@ pub fn shipyard_html(shipyard: Shipyard) -> Markup {
     }
 }
 
+/// A list of goods, each linking to its price history.
+fn trade_goods_list_html(trade_goods: &[TradeGood]) -> Markup {
+    html! {
+        ul {
+            @for trade_good in trade_goods {
+                li title=(trade_good.description) {
+                    a
+                        href={"/trade_goods/" (trade_good.symbol.to_string())}
+                        class="underline decoration-dotted"
+                        up-layer="new"
+                        up-history="false"
+                    {(trade_good.name)}
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Everything a market deals in, its prices if we've seen them, and a form to
+ * buy or sell each good for every ship that's there. The forms' limits are
+ * only a convenience, `spacetraders::market_buy` and `market_sell` check
+ * again.
+ */
+pub fn market_html(
+    market: Market,
+    prices_seen_at: Option<chrono::DateTime<chrono::Utc>>,
+    ships: Vec<Ship>,
+    credits: i64,
+) -> Markup {
+    let trade_goods = market.trade_goods.unwrap_or_default();
+
+    html! {
+        div class="market" {
+            // Where the forms below report failures
+            div class="error" {}
+
+            div class="flex gap-x-8" {
+                div {
+                    header class="font-semibold" {"Imports"}
+                    (trade_goods_list_html(&market.imports))
+                }
+                div {
+                    header class="font-semibold" {"Exports"}
+                    (trade_goods_list_html(&market.exports))
+                }
+                div {
+                    header class="font-semibold" {"Exchange"}
+                    (trade_goods_list_html(&market.exchange))
+                }
+            }
+
+            @if trade_goods.is_empty() {
+                div {"Prices show up once one of your ships is here."}
+            } @else {
+                header class="mt-2 font-semibold" {
+                    "Prices"
+                    @if let Some(seen_at) = prices_seen_at {
+                        span class="text-sm text-gray-700" {
+                            " as of " (ago(seen_at)) " ago"
+                        }
+                    }
+                }
+                table class="[&_td]:px-2 [&_th]:px-2 text-left" {
+                    thead {
+                        tr {
+                            th {"Good"}
+                            th {"Type"}
+                            th {"Supply"}
+                            th {"Activity"}
+                            th {"Buy"}
+                            th {"Sell"}
+                            th {"Volume"}
+                        }
+                    }
+                    tbody {
+                        @for trade_good in &trade_goods {
+                            tr {
+                                td {(trade_good.symbol.to_string())}
+                                td {(format!("{:?}", trade_good.r#type).to_lowercase())}
+                                td {(trade_good.supply.to_string().to_lowercase())}
+                                td {
+                                    @if let Some(activity) = trade_good.activity {
+                                        (activity.to_string().to_lowercase())
+                                    }
+                                }
+                                td {(trade_good.purchase_price)}
+                                td {(trade_good.sell_price)}
+                                td {(trade_good.trade_volume)}
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Stale prices aren't worth trading on
+            @if prices_seen_at.is_none() {
+                @for ship in ships {
+                    (market_ship_html(&market.symbol, &trade_goods, ship, credits))
+                }
+            }
+        }
+    }
+}
+
+fn market_ship_html(
+    waypoint_symbol: &str,
+    trade_goods: &[MarketTradeGood],
+    ship: Ship,
+    credits: i64,
+) -> Markup {
+    let space = ship.cargo.capacity - ship.cargo.units;
+    let system_symbol = &ship.nav.system_symbol;
+    let action = |verb: &str| format!("/market/{system_symbol}/{waypoint_symbol}/{verb}");
+
+    html! {
+        details open class="mt-2" {
+            summary {(ship.symbol) " (cargo " (ship.cargo.units) "/" (ship.cargo.capacity) ")"}
+            table class="[&_td]:px-2 text-left" {
+                tbody {
+                    @for trade_good in trade_goods {
+                        @let affordable = credits / i64::from(trade_good.purchase_price.max(1));
+                        @let can_buy = trade_good
+                            .trade_volume
+                            .min(space)
+                            .min(affordable.min(i64::from(i32::MAX)) as i32);
+                        @let can_sell = trade_good.trade_volume.min(units_held(&ship, trade_good.symbol));
+                        tr {
+                            td {(trade_good.symbol.to_string())}
+                            td {
+                                @if can_buy > 0 {
+                                    form method="POST" action=(action("buy")) up-target=".market" up-fail-target=".error" {
+                                        input type="hidden" name="ship_symbol" value=(ship.symbol);
+                                        input type="hidden" name="trade_symbol" value=(trade_good.symbol.to_string());
+                                        input type="number" name="units" min="1" max=(can_buy) value="1" class="w-20 border";
+                                        button class="ml-2 underline" {"Buy"}
+                                    }
+                                }
+                            }
+                            td {
+                                @if can_sell > 0 {
+                                    form method="POST" action=(action("sell")) up-target=".market" up-fail-target=".error" {
+                                        input type="hidden" name="ship_symbol" value=(ship.symbol);
+                                        input type="hidden" name="trade_symbol" value=(trade_good.symbol.to_string());
+                                        input type="number" name="units" min="1" max=(can_sell) value=(can_sell) class="w-20 border";
+                                        button class="ml-2 underline" {"Sell"}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What a ship's just done, for its card to mention.
+pub enum ShipReport {
+    Extracted(ExtractionYield),
+    Surveyed(Vec<Survey>),
+    Sold(CargoSale),
+    Refined(ShipRefine201ResponseData),
+}
+
 pub fn ship_html(
     ship: Ship,
     ship_waypoint: ShipWaypoint,
-    extraction_yield: Option<ExtractionYield>,
+    route: Option<ActiveRoute>,
+    mining: Option<MiningJob>,
+    report: Option<ShipReport>,
 ) -> Markup {
+    let surveyor = can_survey(&ship);
+    let refinery = !refinery_products(&ship).is_empty();
+    let at_asteroid =
+        ship.nav.status != ShipNavStatus::InTransit && minable(&ship_waypoint.waypoint);
+    let can_start_mining = mining.is_none() && at_asteroid && can_mine(&ship);
+    // Ships that can mine are more use doing that
+    let can_start_hauling =
+        mining.is_none() && at_asteroid && !can_mine(&ship) && ship.cargo.capacity > 0;
     let on_cooldown = ship.cooldown.expiration;
-    let in_transit = ship.nav.status == ShipNavStatus::InTransit;
-
-    let poll = if on_cooldown.is_some() || in_transit {
-        Some(())
-    } else {
-        None
-    };
 
     html! {
         // The special class is necessary because unpoly needs some way to
-        // automatically target the element, otherwise the polling fails.
-        li
-            class={"ship ship-" (ship.symbol)}
-            up-poll[poll.is_some()]
-            up-interval=[poll.map(|_| "5000")]
-            up-source=[poll.map(|_| format!("/ship/{}", ship.symbol))]
-        {
+        // automatically target the element, both for the buttons below and
+        // for the updates fleet.js gets from /fleet/events.
+        li class={"ship ship-" (ship.symbol)} {
+            // Where the buttons below report failures
+            div class="error" {}
+
             div {
                 (format!(
                     "{} {} (Fuel {}/{}) {:?}",
@@ -241,13 +660,13 @@ pub fn ship_html(
                         (format!(" ETA: {}", from_now(ship.nav.route.arrival)))
                     },
                     ShipNavStatus::InOrbit => {
-                        button up-href={"/ship_nav/" (ship.symbol) "/dock"} up-method="post" up-target=".ship" {
+                        button up-href={"/ship_nav/" (ship.symbol) "/dock"} up-method="post" up-target=".ship" up-fail-target=".error" {
                             i class="bi-arrow-bar-down" {}
                         }
                     },
 
                     ShipNavStatus::Docked => {
-                        button up-href={"/ship_nav/" (ship.symbol) "/orbit"} up-method="post" up-target=".ship" {
+                        button up-href={"/ship_nav/" (ship.symbol) "/orbit"} up-method="post" up-target=".ship" up-fail-target=".error" {
                             i class="bi-arrow-bar-up" {}
                         }
                     },
@@ -267,29 +686,85 @@ pub fn ship_html(
                     up-href={"/ship_nav/" (ship.symbol) "/extract"}
                     up-method="post"
                     up-target=".ship"
+                    up-fail-target=".error"
                     {
                     i class="bi-minecart-loaded" {}
                 }
+
+                @if refinery {
+                    a
+                        href={"/ship_refine/" (ship.symbol)}
+                        up-layer="new"
+                        up-history="false"
+                        title="Refine"
+                    {
+                        i class="bi-fire" {}
+                    }
+                }
+
+                @if surveyor {
+                    button
+                        disabled[on_cooldown.is_some()]
+                        class=(on_cooldown.clone().map_or("", |_| "text-gray-400"))
+                        up-href={"/ship_nav/" (ship.symbol) "/survey"}
+                        up-method="post"
+                        up-target=".ship"
+                        up-fail-target=".error"
+                        title="Survey"
+                        {
+                        i class="bi-binoculars" {}
+                    }
+                }
+
+                @if can_start_mining {
+                    button
+                        up-href={"/ship_mining/" (ship.symbol) "/start"}
+                        up-method="post"
+                        up-target=".ship"
+                        up-fail-target=".error"
+                        title="Mine here until stopped"
+                        {
+                        i class="bi-play-circle" {}
+                    }
+                }
+
+                @if can_start_hauling {
+                    button
+                        up-href={"/ship_mining/" (ship.symbol) "/haul"}
+                        up-method="post"
+                        up-target=".ship"
+                        up-fail-target=".error"
+                        title="Take cargo off the miners here until stopped"
+                        {
+                        i class="bi-box-arrow-in-down" {}
+                    }
+                }
             }
 
             div class="flex gap-x-2" {
                 a
-                    href={"#" (ship.nav.waypoint_symbol)}
+                    href={"#" (ship_waypoint.waypoint.symbol)}
                     class="underline decoration-dotted"
 
-                {(ship.nav.waypoint_symbol)}
+                {(ship_waypoint.waypoint.symbol)}
 
                 @for feature in ship_waypoint.features.iter() {
                     @match feature {
                         WaypointFeatures::Marketplace => {
-                            i title="Marketplace" class="bi-shop" {}
+                            a
+                                href={"/market/" (ship_waypoint.waypoint.system_symbol) "/" (ship_waypoint.waypoint.symbol)}
+                                up-layer="new"
+                                up-history="false"
+                            {
+                                i title="Marketplace" class="bi-shop" {}
+                            }
                         },
                         WaypointFeatures::Shipyard => {
                             i title="Shipyard" class="bi-rocket" {}
                         },
                         WaypointFeatures::Fuel => {
                             @let ship_fuel_full = ship.fuel.current == ship.fuel.capacity;
-                            button up-href={"ship_nav/" (ship.symbol) "/refuel"} up-method="post" up-target=".ship" {
+                            button up-href={"ship_nav/" (ship.symbol) "/refuel"} up-method="post" up-target=".ship" up-fail-target=".error" {
                                 i title="Fuel" class={"bi-fuel-pump " (if ship_fuel_full {"text-gray-400"} else {""})}  {}
                             }
                         },
@@ -297,12 +772,106 @@ pub fn ship_html(
                 }
             }
 
+            @if let Some(route) = route {
+                @let leg = route.next_hop.min(route.plan.hops.len().saturating_sub(1)) + 1;
+                div class="text-sm" {
+                    "Route to " (route.destination().unwrap_or("nowhere"))
+                    ", leg " (leg) "/" (route.plan.hops.len())
+                    button
+                        up-href={"/ship_nav/" (ship.symbol) "/cancel"}
+                        up-method="post"
+                        up-target=".ship"
+                        up-fail-target=".error"
+                        class="ml-2"
+                        title="Cancel route"
+                    {
+                        i class="bi-x-circle" {}
+                    }
+                    @if let Some(error) = &route.error {
+                        div class="text-red-700" {"Stopped: " (error)}
+                    }
+                    ol class="ml-4 list-decimal" {
+                        @for (i, hop) in route.plan.hops.iter().enumerate() {
+                            li class=(if i < route.next_hop {"text-gray-400"} else {""}) {
+                                @if hop.refuel {"Refuel, then "}
+                                (hop.mode.to_string().to_lowercase()) " to " (hop.to)
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if let Some(job) = &mining {
+                div class="text-sm" {
+                    @match job.role {
+                        MiningRole::Miner => {"Mining "},
+                        MiningRole::Hauler => {"Hauling for "},
+                    }
+                    (job.asteroid) ": " (mining_stage(job))
+                    ", " (job.earned) " credits so far"
+                    button
+                        up-href={"/ship_mining/" (ship.symbol) "/stop"}
+                        up-method="post"
+                        up-target=".ship"
+                        up-fail-target=".error"
+                        class="ml-2"
+                        title="Stop mining"
+                    {
+                        i class="bi-stop-circle" {}
+                    }
+                    @if refinery {
+                        form
+                            method="POST"
+                            action={"/ship_mining/" (ship.symbol) "/refine"}
+                            up-target=".ship"
+                            up-fail-target=".error"
+                            class="inline ml-2"
+                        {
+                            input type="hidden" name="refine" value=(!job.refine);
+                            button class="underline" {
+                                "Refine before selling: " (if job.refine {"on"} else {"off"})
+                            }
+                        }
+                    }
+                    @if let Some(error) = &job.error {
+                        div class="text-red-700" {"Stopped: " (error)}
+                    }
+                }
+            }
+
+            @if let Some(trade_goods) = ship_waypoint.market.and_then(|m| m.trade_goods) {
+                details {
+                    summary {"Market"}
+                    ul class="flex flex-wrap gap-x-2" {
+                        @for trade_good in trade_goods {
+                            li {
+                                a
+                                    href={"/trade_goods/" (trade_good.symbol.to_string())}
+                                    class="underline decoration-dotted"
+                                    up-layer="new"
+                                    up-history="false"
+                                {(trade_good.symbol.to_string())}
+                                " " (trade_good.purchase_price) "/" (trade_good.sell_price)
+                            }
+                        }
+                    }
+                }
+            }
+
             @if ship.cargo.units > 0 {
                 details open {
                     summary {"Cargo (" (ship.cargo.units) "/" (ship.cargo.capacity) ")"}
                     ul class="flex gap-x-2" {
                         @for cargo_item in ship.cargo.inventory {
-                            li {(cargo_item.name) " " (cargo_item.units)}
+                            li {
+                                a
+                                    href={"/ship_cargo/" (ship.symbol) "/item/" (cargo_item.symbol.to_string())}
+                                    class="underline decoration-dotted"
+                                    up-layer="new"
+                                    up-history="false"
+                                {(cargo_item.name)}
+                                " " (cargo_item.units)
+                            }
                         }
                     }
 
@@ -311,6 +880,7 @@ pub fn ship_html(
                             up-href={"/ship_cargo/" (ship.symbol) "/dump"}
                             up-method="post"
                             up-target=".ship"
+                            up-fail-target=".error"
                             class="flex gap-x-1 items-baseline"
                         {
                             i class="bi-upload" {}
@@ -320,21 +890,510 @@ pub fn ship_html(
                 }
             }
 
-            @if let Some(r#yield) = extraction_yield {
-                div {
-                    (format!("Extracted {} {}", r#yield.units, r#yield.symbol.to_string()))
+            @match report {
+                Some(ShipReport::Extracted(r#yield)) => {
+                    div {
+                        (format!("Extracted {} {}", r#yield.units, r#yield.symbol.to_string()))
+                    }
+                }
+                Some(ShipReport::Surveyed(surveys)) => {
+                    details open {
+                        summary {
+                            "Found " (surveys.len()) " survey"
+                            @if surveys.len() != 1 {"s"}
+                        }
+                        ul class="text-sm" {
+                            @for survey in &surveys {
+                                li {(survey_deposits(survey))}
+                            }
+                        }
+                    }
+                }
+                Some(ShipReport::Sold(sale)) => {
+                    details open {
+                        summary {"Sold for " (sale.credits) " credits"}
+                        ul class="text-sm" {
+                            @for transaction in &sale.transactions {
+                                li {
+                                    (transaction.units) " " (transaction.trade_symbol)
+                                    " @ " (transaction.price_per_unit)
+                                    " = " (transaction.total_price)
+                                }
+                            }
+                            @for (trade_symbol, reason) in &sale.kept {
+                                li class="text-gray-700" {
+                                    "Kept the rest of the " (trade_symbol.to_string()) ": " (reason)
+                                }
+                            }
+                        }
+                    }
+                }
+                Some(ShipReport::Refined(refined)) => {
+                    div {
+                        "Refined "
+                        @for (i, consumed) in refined.consumed.iter().enumerate() {
+                            @if i > 0 {", "}
+                            (consumed.units) " " (consumed.trade_symbol)
+                        }
+                        " into "
+                        @for (i, produced) in refined.produced.iter().enumerate() {
+                            @if i > 0 {", "}
+                            (produced.units) " " (produced.trade_symbol)
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/**
+ * What the ship's refinery could make out of its cargo, at the going ratio,
+ * with what the ore and what it'd turn into are worth at the best prices
+ * we've seen.
+ */
+pub fn ship_refine_html(ship: &Ship, options: Vec<RefineOption>) -> Markup {
+    let on_cooldown = ship.cooldown.expiration.clone();
+    html! {
+        div class="flex flex-col gap-2" {
+            div class="error" {}
+
+            div {
+                (ship.symbol) "'s refinery turns " (REFINE_INPUT) " units of ore into "
+                (REFINE_OUTPUT) " units of refined goods."
+                @if let Some(expiration) = on_cooldown.clone() {
+                    " It's cooling down for another " (from_now(expiration)) "."
+                }
+            }
+
+            @if options.is_empty() {
+                div class="text-gray-700" {"Nothing in the hold it can refine."}
+            } @else {
+                table class="[&_td]:px-2 [&_th]:px-2 text-left" {
+                    thead {
+                        tr {
+                            th {"Makes"}
+                            th {"From"}
+                            th {"Held"}
+                            th {"Ore worth"}
+                            th {"Refined worth"}
+                            th {}
+                        }
+                    }
+                    tbody {
+                        @for option in options {
+                            @let disabled = option.runs() == 0 || on_cooldown.is_some();
+                            tr class=(if option.pays() {"font-semibold"} else {""}) {
+                                td {(REFINE_OUTPUT) " " (option.output.to_string())}
+                                td {(REFINE_INPUT) " " (option.input.to_string())}
+                                td {
+                                    (option.held) " (" (option.runs()) " run"
+                                    @if option.runs() != 1 {"s"}
+                                    ")"
+                                }
+                                td {(option.input_value.map_or("?".to_string(), |v| v.to_string()))}
+                                td {(option.output_value.map_or("?".to_string(), |v| v.to_string()))}
+                                td {
+                                    form
+                                        method="POST"
+                                        action={"/ship_refine/" (ship.symbol) "/" (option.output.to_string())}
+                                        up-layer="parent"
+                                        up-target={".ship-" (ship.symbol)}
+                                        up-fail-target=".error"
+                                    {
+                                        button
+                                            disabled[disabled]
+                                            class={"underline " (if disabled {"text-gray-400"} else {""})}
+                                        {"Refine"}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * What can be done with one good in a ship's hold: sell it if the market here
+ * buys it, throw it out, or hand it to another ship alongside. The forms
+ * update the ship's card back on the page underneath.
+ */
+pub fn ship_cargo_item_html(
+    ship: &Ship,
+    trade_symbol: TradeSymbol,
+    market: Option<&MarketTradeGood>,
+    targets: Vec<&Ship>,
+) -> Markup {
+    let held = units_held(ship, trade_symbol);
+    let action = |verb: &str| format!("/ship_cargo/{}/{verb}", ship.symbol);
+    let units = |max: i32| {
+        html! {
+            input type="hidden" name="trade_symbol" value=(trade_symbol.to_string());
+            input type="number" name="units" min="1" max=(max) value=(max) class="w-20 border";
+        }
+    };
+
+    html! {
+        div class="flex flex-col gap-2" {
+            div class="error" {}
+
+            div {(ship.symbol) " has " (held) " " (trade_symbol.to_string())}
+
+            @if let Some(trade_good) = market {
+                form method="POST" action=(action("sell")) up-layer="parent" up-target={".ship-" (ship.symbol)} up-fail-target=".error" {
+                    (units(held.min(trade_good.trade_volume)))
+                    button class="ml-2 underline" {"Sell @ " (trade_good.sell_price)}
+                }
+            }
+
+            form method="POST" action=(action("jettison")) up-layer="parent" up-target={".ship-" (ship.symbol)} up-fail-target=".error" {
+                (units(held))
+                button class="ml-2 underline" {"Jettison"}
+            }
+
+            @if targets.is_empty() {
+                div class="text-gray-700" {"None of your other ships here have room for it."}
+            } @else {
+                form method="POST" action=(action("transfer")) up-layer="parent" up-target={".ship-" (ship.symbol)} up-fail-target=".error" {
+                    (units(held))
+                    select name="to_ship_symbol" class="ml-2 border" {
+                        @for target in targets {
+                            @let space = target.cargo.capacity - target.cargo.units;
+                            option value=(target.symbol) {
+                                (target.symbol) " (" (space) " free)"
+                            }
+                        }
+                    }
+                    button class="ml-2 underline" {"Transfer"}
+                }
+            }
+        }
+    }
+}
+
+/// A survey's deposits, with repeats counted rather than listed.
+fn survey_deposits(survey: &Survey) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for deposit in &survey.deposits {
+        *counts.entry(&deposit.symbol).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(symbol, count)| match count {
+            1 => symbol.to_string(),
+            _ => format!("{count}x {symbol}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn mining_stage(job: &MiningJob) -> Markup {
+    html! {
+        @match (&job.stage, job.role) {
+            (MiningStage::Mining, MiningRole::Miner) => {"extracting"},
+            (MiningStage::Mining, MiningRole::Hauler) => {"waiting for cargo"},
+            (MiningStage::Selling { market }, _) => {"selling at " (market)},
+            (MiningStage::Returning, _) => {"heading back"},
+        }
+    }
+}
+
+/// Ships mining on their own, grouped by the asteroid they're working.
+pub fn mining_groups_html(jobs: Vec<(String, MiningJob)>, ships: &[Ship]) -> Markup {
+    let mut by_asteroid: BTreeMap<String, Vec<(String, MiningJob)>> = BTreeMap::new();
+    for (ship_symbol, job) in jobs {
+        by_asteroid
+            .entry(job.asteroid.clone())
+            .or_default()
+            .push((ship_symbol, job));
+    }
+    // Miners first
+    for jobs in by_asteroid.values_mut() {
+        jobs.sort_by_key(|(ship_symbol, j)| (j.role == MiningRole::Hauler, ship_symbol.clone()));
+    }
+
+    html! {
+        @if by_asteroid.is_empty() {
+            div {"Nobody's mining. Start a ship mining from its card at an asteroid."}
+        }
+        @for (asteroid, jobs) in by_asteroid {
+            @let earned: i64 = jobs.iter().map(|(_, j)| j.earned).sum();
+            details open {
+                summary {
+                    a href={"#" (asteroid)} class="underline decoration-dotted" {(asteroid)}
+                    " " (earned) " credits so far"
+                }
+                table class="[&_td]:px-2 [&_th]:px-2 text-left" {
+                    thead {
+                        tr {
+                            th {"Ship"}
+                            th {"Role"}
+                            th {"Doing"}
+                            th {"Cargo"}
+                            th {"Earned"}
+                        }
+                    }
+                    tbody {
+                        @for (ship_symbol, job) in &jobs {
+                            @let ship = ships.iter().find(|s| &s.symbol == ship_symbol);
+                            tr {
+                                td {(ship_symbol)}
+                                td {(format!("{:?}", job.role).to_lowercase())}
+                                td {
+                                    (mining_stage(job))
+                                    @if let Some(error) = &job.error {
+                                        div class="text-red-700" {"Stopped: " (error)}
+                                    }
+                                }
+                                td {
+                                    @if let Some(ship) = ship {
+                                        (ship.cargo.units) "/" (ship.cargo.capacity)
+                                    }
+                                }
+                                td {(job.earned)}
+                            }
+                        }
+                    }
                 }
             }
         }
     }
 }
 
-pub fn ships_html(ships: Vec<(Ship, ShipWaypoint)>) -> Markup {
+/// The surveys we can still extract with, grouped by asteroid, best first.
+pub fn surveys_html(surveys: Vec<(Survey, f64)>) -> Markup {
+    let mut by_waypoint: BTreeMap<String, Vec<(Survey, f64)>> = BTreeMap::new();
+    for (survey, value) in surveys {
+        by_waypoint
+            .entry(survey.symbol.clone())
+            .or_default()
+            .push((survey, value));
+    }
+
+    html! {
+        @if by_waypoint.is_empty() {
+            div {"No surveys. Surveyor ships can make some at an asteroid."}
+        }
+        @for (waypoint_symbol, surveys) in by_waypoint {
+            details open {
+                summary {
+                    a href={"#" (waypoint_symbol)} class="underline decoration-dotted" {(waypoint_symbol)}
+                }
+                table class="[&_td]:px-2 [&_th]:px-2 text-left" {
+                    thead {
+                        tr {
+                            th {"Size"}
+                            th {"Deposits"}
+                            th {"Worth"}
+                            th {"Expires in"}
+                        }
+                    }
+                    tbody {
+                        @for (survey, value) in surveys {
+                            tr title=(survey.signature) {
+                                td {(format!("{:?}", survey.size).to_lowercase())}
+                                td {(survey_deposits(&survey))}
+                                td {(format!("{value:.0}"))}
+                                td {(from_now(survey.expiration))}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn ships_html(
+    ships: Vec<(Ship, ShipWaypoint, Option<ActiveRoute>, Option<MiningJob>)>,
+) -> Markup {
     html! {
         ul class="ships [&>li]:mb-2" {
-            @for (ship, waypoint) in ships {
-                (ship_html(ship, waypoint, None))
+            @for (ship, waypoint, route, mining) in ships {
+                (ship_html(ship, waypoint, route, mining, None))
             }
         }
     }
 }
+
+const CHART_COLORS: [&str; 8] = [
+    "#2563eb", "#dc2626", "#16a34a", "#9333ea", "#ea580c", "#0891b2", "#ca8a04", "#db2777",
+];
+
+/**
+ * Plots buy (dashed) and sell (solid) prices for one good at every market
+ * we've seen it in. It's just an SVG, so it works without any more JS.
+ */
+pub fn price_history_html(
+    trade_symbol: TradeSymbol,
+    observations: Vec<PriceObservation>,
+) -> Markup {
+    if observations.is_empty() {
+        return html! {
+            div {"No prices recorded for " (trade_symbol.to_string()) " yet. Send a ship to a market that trades it."}
+        };
+    }
+
+    let mut by_market: BTreeMap<String, Vec<PriceObservation>> = BTreeMap::new();
+    for observation in observations {
+        by_market
+            .entry(observation.waypoint_symbol.clone())
+            .or_default()
+            .push(observation);
+    }
+
+    let all = by_market.values().flatten();
+    let start = all.clone().map(|o| o.observed_at).min().unwrap();
+    let end = all.clone().map(|o| o.observed_at).max().unwrap();
+    let min_price = all
+        .clone()
+        .map(|o| o.trade_good.purchase_price.min(o.trade_good.sell_price))
+        .min()
+        .unwrap();
+    let max_price = all
+        .map(|o| o.trade_good.purchase_price.max(o.trade_good.sell_price))
+        .max()
+        .unwrap();
+
+    let (width, height, pad) = (800.0, 300.0, 40.0);
+    let time_span = ((end - start).num_seconds() as f64).max(1.0);
+    let price_span = ((max_price - min_price) as f64).max(1.0);
+    let x = |o: &PriceObservation| {
+        pad + (o.observed_at - start).num_seconds() as f64 / time_span * (width - 2.0 * pad)
+    };
+    let y =
+        |price: i32| height - pad - (price - min_price) as f64 / price_span * (height - 2.0 * pad);
+    let points = |observations: &[PriceObservation], price: fn(&PriceObservation) -> i32| {
+        observations
+            .iter()
+            .map(|o| format!("{:.1},{:.1}", x(o), y(price(o))))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let (axis_y, axis_right, axis_bottom) = (height - 10.0, width - pad, height - pad);
+
+    html! {
+        svg viewBox={"0 0 " (width) " " (height)} class="w-full max-w-3xl border rounded-md" {
+            text x=(pad) y=(axis_y) font-size="12" {(start.format("%Y-%m-%d %H:%M"))}
+            text x=(axis_right) y=(axis_y) font-size="12" text-anchor="end" {(end.format("%Y-%m-%d %H:%M"))}
+            text x="4" y=(pad) font-size="12" {(max_price)}
+            text x="4" y=(axis_bottom) font-size="12" {(min_price)}
+
+            @for (i, observations) in by_market.values().enumerate() {
+                @let color = CHART_COLORS[i % CHART_COLORS.len()];
+                polyline
+                    points=(points(observations, |o| o.trade_good.sell_price))
+                    fill="none"
+                    stroke=(color)
+                    stroke-width="2" {}
+                polyline
+                    points=(points(observations, |o| o.trade_good.purchase_price))
+                    fill="none"
+                    stroke=(color)
+                    stroke-width="2"
+                    stroke-dasharray="4 4" {}
+                @for o in observations {
+                    circle cx=(format!("{:.1}", x(o))) cy=(format!("{:.1}", y(o.trade_good.sell_price))) r="3" fill=(color) {
+                        title {(o.waypoint_symbol) ": " (o.trade_good.sell_price) " at " (o.observed_at.format("%Y-%m-%d %H:%M"))}
+                    }
+                }
+            }
+        }
+
+        table class="mt-2 [&_td]:px-2 [&_th]:px-2 text-left" {
+            thead {
+                tr {
+                    th {"Market"}
+                    th {"Buy"}
+                    th {"Sell"}
+                    th {"Supply"}
+                    th {"Activity"}
+                    th {"Volume"}
+                    th {"Last seen"}
+                }
+            }
+            tbody {
+                @for (i, (waypoint_symbol, observations)) in by_market.iter().enumerate() {
+                    @let latest = observations.last().unwrap();
+                    tr {
+                        td {
+                            span style={"color: " (CHART_COLORS[i % CHART_COLORS.len()])} {"■ "}
+                            (waypoint_symbol)
+                        }
+                        td {(latest.trade_good.purchase_price)}
+                        td {(latest.trade_good.sell_price)}
+                        td class="capitalize" {(latest.trade_good.supply.to_string().to_lowercase())}
+                        td class="capitalize" {
+                            @if let Some(activity) = latest.trade_good.activity {
+                                (activity.to_string().to_lowercase())
+                            }
+                        }
+                        td {(latest.trade_good.trade_volume)}
+                        td {(humantime::format_duration(std::time::Duration::from_secs((chrono::Utc::now() - latest.observed_at).num_seconds().max(0) as u64))) " ago"}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Signing up a new agent, for when the server's been reset and we haven't
+ * been told who to register as. Only factions that are recruiting can be
+ * joined.
+ */
+pub fn register_html(agent_name: &str, factions: Vec<Faction>) -> Markup {
+    html! {
+        form method="POST" action="/register" class="flex flex-col gap-2 max-w-md" {
+            p {
+                "The server doesn't know " (agent_name) " any more, probably because it's been reset. "
+                "Register a new agent to carry on."
+            }
+            label {
+                div {"Symbol"}
+                input name="symbol" required minlength="3" maxlength="14" class="border rounded-md px-2 py-1 w-full";
+            }
+            label {
+                div {"Faction"}
+                select name="faction" class="border rounded-md px-2 py-1 w-full" {
+                    @for faction in factions.iter().filter(|f| f.is_recruiting) {
+                        option value=(faction.symbol.to_string()) {(faction.name)}
+                    }
+                }
+            }
+            label {
+                div {"Email " span class="text-sm text-gray-700" {"(optional, for reserved symbols)"}}
+                input name="email" type="email" class="border rounded-md px-2 py-1 w-full";
+            }
+            button type="submit" class="border rounded-md bg-gray-100 px-2 py-1" {"Register"}
+        }
+    }
+}
+
+/// The new token, which we'll keep using, but which is also the only way to
+/// log in as the agent anywhere else.
+pub fn registered_html(symbol: &str, token: &str) -> Markup {
+    html! {
+        div class="flex flex-col gap-2" {
+            p {"Registered " (symbol) ". Its token, in case you want it elsewhere:"}
+            textarea readonly rows="6" class="border rounded-md px-2 py-1 font-mono text-sm w-full" {(token)}
+            a href="/" class="underline" {"Carry on"}
+        }
+    }
+}
+
+/**
+ * Where a failed action says what went wrong. Ship cards keep an empty one,
+ * and their buttons point `up-fail-target` at `.error`, so unpoly swaps this
+ * in next to the button that was clicked rather than replacing the page.
+ */
+pub fn error_html(error: &AppError) -> Markup {
+    html! {
+        div class="error text-red-700" role="alert" {(error.message())}
+    }
+}
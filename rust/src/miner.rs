@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use spacedust::apis::api_error::ApiErrorKind;
+use spacedust::apis::configuration::Configuration;
+use spacedust::apis::fleet_api;
+use spacedust::models::{
+    JettisonRequest, Market, RefuelShipRequest, Ship, ShipNavStatus, TradeSymbol,
+    TransferCargoRequest,
+};
+use spacedust::rate_limit;
+
+use crate::error::AppError;
+use crate::executor::Executor;
+use crate::fleet::Fleet;
+use crate::nav;
+use crate::spacetraders::{self, ShipOrShipSymbol};
+use crate::store::Store;
+use crate::trade;
+
+/// How often to look in on a ship the executor's flying for us.
+const ROUTE_CHECK: Duration = Duration::from_secs(5);
+
+/// A ship mining an asteroid over and over, and what it's up to right now.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiningJob {
+    /// Where to mine, and where to come back to after selling
+    pub asteroid: String,
+    /// Jobs from before there were haulers are all miners
+    #[serde(default)]
+    pub role: MiningRole,
+    pub stage: MiningStage,
+    /// Whether to refine ore before selling it, when the refined goods fetch
+    /// more
+    #[serde(default)]
+    pub refine: bool,
+    /// Credits the cargo's fetched since we started
+    pub earned: i64,
+    /// Why we stopped, if we did. Like a route's error, it stays on the card
+    /// until someone stops the job.
+    pub error: Option<String>,
+}
+
+/**
+ * Ships mining the same asteroid make a group. Miners extract, and haulers
+ * wait around to take the cargo off them, so the miners don't have to leave.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum MiningRole {
+    #[default]
+    Miner,
+    Hauler,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MiningStage {
+    /// Extracting until the hold's full, or for a hauler, taking cargo from
+    /// the miners until its hold's nearly full
+    Mining,
+    /// Taking the cargo to `market`
+    Selling { market: String },
+    /// Heading back to the asteroid
+    Returning,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MiningSettings {
+    /// See `Config::sell_floor`
+    pub sell_floor: f64,
+    /// See `Config::jettison_below`
+    pub jettison_below: i32,
+}
+
+/**
+ * Keeps ships mining without anyone clicking extract after every cooldown.
+ * A ship extracts until its hold is full, throws out whatever isn't worth
+ * carrying, sells the rest at whichever market in the system pays best,
+ * refuels and heads back. The executor does the flying.
+ *
+ * With haulers stationed at the asteroid, a full miner hands its cargo to
+ * one of them instead, and only goes to sell itself if none of them has room.
+ * A hauler goes off to sell once it couldn't take another full hold from
+ * the miner that just filled it, leaving the others to take its place.
+ * Ships with a refinery can refine their ore on the way out, if the prices
+ * we know say that pays.
+ *
+ * Like routes, jobs are saved as they go, so `resume` picks them back up
+ * after a restart.
+ */
+#[derive(Clone)]
+pub struct Miner {
+    conf: Configuration,
+    store: Store,
+    fleet: Fleet,
+    executor: Executor,
+    pub settings: MiningSettings,
+    tasks: Arc<Mutex<Tasks>>,
+    /// Held while a miner hands off cargo, so two miners don't both count on
+    /// the same room in a hauler
+    hand_offs: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Each ship's task, with a number telling it apart from any task that mined
+/// with the ship before.
+type Tasks = HashMap<String, (u64, JoinHandle<()>)>;
+
+static NEXT_TASK: AtomicU64 = AtomicU64::new(0);
+
+impl Miner {
+    pub fn new(
+        conf: Configuration,
+        store: Store,
+        fleet: Fleet,
+        executor: Executor,
+        settings: MiningSettings,
+    ) -> Miner {
+        Miner {
+            conf,
+            store,
+            fleet,
+            executor,
+            settings,
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            hand_offs: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Restarts every job that hadn't stopped when we last shut down.
+    pub async fn resume(&self) -> anyhow::Result<()> {
+        for (ship_symbol, job) in self.store.mining_jobs().await? {
+            if job.error.is_none() {
+                self.spawn(ship_symbol);
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the ship to work at `asteroid`, where it has to be already.
+    pub async fn start(
+        &self,
+        ship_symbol: &str,
+        asteroid: &str,
+        role: MiningRole,
+    ) -> anyhow::Result<()> {
+        self.abort(ship_symbol);
+        let job = MiningJob {
+            asteroid: asteroid.to_string(),
+            role,
+            stage: MiningStage::Mining,
+            refine: false,
+            earned: 0,
+            error: None,
+        };
+        self.store.put_mining_job(ship_symbol, &job).await?;
+        self.spawn(ship_symbol.to_string());
+        Ok(())
+    }
+
+    /// Turns refining before selling on or off for the ship's job.
+    pub async fn set_refine(&self, ship_symbol: &str, refine: bool) -> anyhow::Result<()> {
+        let Some(mut job) = self.store.mining_job(ship_symbol).await? else {
+            anyhow::bail!("{ship_symbol} isn't mining");
+        };
+        // The running job would save over the change otherwise. It picks up
+        // from the saved job like after a restart.
+        self.abort(ship_symbol);
+        job.refine = refine;
+        self.store.put_mining_job(ship_symbol, &job).await?;
+        if job.error.is_none() {
+            self.spawn(ship_symbol.to_string());
+        }
+        Ok(())
+    }
+
+    /// Stops the ship mining. If it's on its way somewhere, it carries on
+    /// there.
+    pub async fn cancel(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        self.abort(ship_symbol);
+        self.store.delete_mining_job(ship_symbol).await
+    }
+
+    /// Stops every job, but leaves them saved so another miner can `resume`
+    /// them.
+    pub fn stop(&self) {
+        for (_, (_, task)) in self.tasks.lock().drain() {
+            task.abort();
+        }
+    }
+
+    fn abort(&self, ship_symbol: &str) {
+        if let Some((_, task)) = self.tasks.lock().remove(ship_symbol) {
+            task.abort();
+        }
+    }
+
+    fn spawn(&self, ship_symbol: String) {
+        // Held until the handle's in, so a job that fails straight away can't
+        // clear its entry before there is one
+        let mut tasks = self.tasks.lock();
+        let id = NEXT_TASK.fetch_add(1, Ordering::Relaxed);
+        let miner = self.clone();
+        let task_ship_symbol = ship_symbol.clone();
+        let task = tokio::spawn(async move {
+            let ship_symbol = task_ship_symbol;
+            let result = rate_limit::background(miner.run(&ship_symbol)).await;
+            if let Err(err) = result {
+                println!("Mining with {ship_symbol} failed: {err:#}");
+                if let Ok(Some(mut job)) = miner.store.mining_job(&ship_symbol).await {
+                    job.error = Some(format!("{err:#}"));
+                    let _ = miner.store.put_mining_job(&ship_symbol, &job).await;
+                }
+            }
+            {
+                // A restarted job, e.g. after `set_refine`, clears its own
+                let mut tasks = miner.tasks.lock();
+                if tasks
+                    .get(&ship_symbol)
+                    .is_some_and(|(task_id, _)| *task_id == id)
+                {
+                    tasks.remove(&ship_symbol);
+                }
+            }
+            let _ = miner.fleet.refresh(&ship_symbol).await;
+        });
+        tasks.insert(ship_symbol, (id, task));
+    }
+
+    async fn run(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        loop {
+            let Some(mut job) = self.store.mining_job(ship_symbol).await? else {
+                return Ok(());
+            };
+            let ship = spacetraders::get_ship(&self.conf, ship_symbol).await?;
+
+            match job.stage.clone() {
+                MiningStage::Mining if job.role == MiningRole::Hauler => {
+                    if !at(&ship, &job.asteroid) {
+                        job.stage = MiningStage::Returning;
+                    } else if ship.cargo.units >= ship.cargo.capacity {
+                        job.stage = MiningStage::Selling {
+                            market: self.best_market(&ship).await?,
+                        };
+                    } else {
+                        self.wait_for_miners(ship_symbol).await?;
+                        continue;
+                    }
+                }
+                MiningStage::Mining => {
+                    if !at(&ship, &job.asteroid) {
+                        job.stage = MiningStage::Returning;
+                    } else if self.jettison(&ship).await? {
+                        // Look again at what's left
+                        continue;
+                    } else if ship.cargo.units >= ship.cargo.capacity {
+                        if self.hand_off(&ship, &job.asteroid).await? {
+                            continue;
+                        }
+                        job.stage = MiningStage::Selling {
+                            market: self.best_market(&ship).await?,
+                        };
+                    } else {
+                        self.extract(ship).await?;
+                        continue;
+                    }
+                }
+                MiningStage::Selling { market } => {
+                    if job.refine && self.refine(&ship).await? {
+                        // With different cargo, somewhere else might pay
+                        // better
+                        let ship = spacetraders::get_ship(&self.conf, ship_symbol).await?;
+                        let market = self.best_market(&ship).await?;
+                        if job.stage
+                            == (MiningStage::Selling {
+                                market: market.clone(),
+                            })
+                        {
+                            continue;
+                        }
+                        job.stage = MiningStage::Selling { market };
+                    } else if !self.go_to(&ship, &market).await? {
+                        continue;
+                    } else {
+                        job.earned += self.sell(ship).await?;
+                        job.stage = MiningStage::Returning;
+                    }
+                }
+                MiningStage::Returning => {
+                    if !self.go_to(&ship, &job.asteroid).await? {
+                        continue;
+                    }
+                    job.stage = MiningStage::Mining;
+                }
+            }
+
+            self.store.put_mining_job(ship_symbol, &job).await?;
+            // The card lagging behind is no reason to stop mining
+            if let Err(err) = self.fleet.refresh(ship_symbol).await {
+                println!("Couldn't refresh {ship_symbol}: {err}");
+            }
+        }
+    }
+
+    /// Waits out the cooldown, then extracts once, with the best survey we've
+    /// got.
+    async fn extract(&self, ship: Ship) -> anyhow::Result<()> {
+        let conf = &self.conf;
+        wait_for_cooldown(&ship).await;
+        if ship.nav.status == ShipNavStatus::Docked {
+            fleet_api::orbit_ship(conf, &ship.symbol).await?;
+        }
+
+        let ship_symbol = ship.symbol.clone();
+        match spacetraders::ship_extract(conf, ShipOrShipSymbol::Ship(ship), &self.store).await {
+            Ok(_) => {}
+            // Someone beat us to it by hand, so wait out the new cooldown
+            Err(err) if on_cooldown(&err) => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.fleet.refresh(&ship_symbol).await?;
+        Ok(())
+    }
+
+    /**
+     * Refines one run of whatever sells for more refined than as ore, going
+     * by the prices we know, if the ship has a refinery and enough ore for
+     * it. Returns whether there was anything to refine.
+     */
+    async fn refine(&self, ship: &Ship) -> anyhow::Result<bool> {
+        if ship.nav.status == ShipNavStatus::InTransit {
+            return Ok(false);
+        }
+        let options = spacetraders::refine_options(ship, &self.store).await?;
+        let Some(option) = options.into_iter().find(|o| o.runs() > 0 && o.pays()) else {
+            return Ok(false);
+        };
+
+        wait_for_cooldown(ship).await;
+        match spacetraders::ship_refine(&self.conf, &ship.symbol, option.produce).await {
+            Ok(_) => {}
+            Err(err) if on_cooldown(&err) => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.fleet.refresh(&ship.symbol).await?;
+        Ok(true)
+    }
+
+    /**
+     * Moves as much of the miner's cargo as fits into a hauler waiting at
+     * `asteroid`, and sends the hauler off to sell if it couldn't take
+     * another load like it. Returns whether there was a hauler to take
+     * anything.
+     */
+    async fn hand_off(&self, ship: &Ship, asteroid: &str) -> anyhow::Result<bool> {
+        let conf = &self.conf;
+        let _hand_off = self.hand_offs.lock().await;
+
+        for (hauler_symbol, mut job) in self.store.mining_jobs().await? {
+            let waiting = job.role == MiningRole::Hauler
+                && job.asteroid == asteroid
+                && job.stage == MiningStage::Mining
+                && job.error.is_none();
+            if !waiting {
+                continue;
+            }
+            let hauler = spacetraders::get_ship(conf, &hauler_symbol).await?;
+            if spacetraders::transfer_targets(ship, std::slice::from_ref(&hauler)).is_empty() {
+                continue;
+            }
+
+            let mut space = hauler.cargo.capacity - hauler.cargo.units;
+            for item in &ship.cargo.inventory {
+                let units = item.units.min(space);
+                if units == 0 {
+                    break;
+                }
+                let request = TransferCargoRequest::new(item.symbol, units, hauler_symbol.clone());
+                fleet_api::transfer_cargo(conf, &ship.symbol, Some(request)).await?;
+                space -= units;
+            }
+
+            if space < ship.cargo.capacity {
+                let hauler = spacetraders::get_ship(conf, &hauler_symbol).await?;
+                job.stage = MiningStage::Selling {
+                    market: self.best_market(&hauler).await?,
+                };
+                self.store.put_mining_job(&hauler_symbol, &job).await?;
+            }
+            self.fleet.refresh(&hauler_symbol).await?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Waits for a miner to fill the hauler up and send it off, or for
+    /// someone to stop it.
+    async fn wait_for_miners(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        loop {
+            tokio::time::sleep(ROUTE_CHECK).await;
+            match self.store.mining_job(ship_symbol).await? {
+                Some(job) if job.stage == MiningStage::Mining => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /**
+     * Throws out anything no market we know of pays at least
+     * `jettison_below` for, so it doesn't take up room. Goods a market
+     * trades but we haven't seen a price for are kept. Returns whether
+     * anything went.
+     */
+    async fn jettison(&self, ship: &Ship) -> anyhow::Result<bool> {
+        let markets = self.store.system_markets(&ship.nav.system_symbol).await?;
+        let prices = trade::best_sell_prices(&markets);
+        let mut jettisoned = false;
+        for item in &ship.cargo.inventory {
+            let keep = match prices.get(&item.symbol.to_string()) {
+                Some(price) => *price >= self.settings.jettison_below,
+                None => markets.iter().any(|m| buys(m, item.symbol)),
+            };
+            if keep {
+                continue;
+            }
+            let request = JettisonRequest::new(item.symbol, item.units);
+            fleet_api::jettison(&self.conf, &ship.symbol, Some(request)).await?;
+            jettisoned = true;
+        }
+        Ok(jettisoned)
+    }
+
+    /// The market in the system the ship can reach that'll pay most for its
+    /// cargo.
+    async fn best_market(&self, ship: &Ship) -> anyhow::Result<String> {
+        let conf = &self.conf;
+        let store = &self.store;
+        let system_symbol = &ship.nav.system_symbol;
+        let waypoints = spacetraders::system_waypoints(conf, system_symbol.clone(), store).await?;
+        let fuel_stations = spacetraders::fuel_stations(conf, &waypoints, store).await?;
+        let plans = nav::plan_routes(ship.into(), &waypoints, &fuel_stations);
+
+        store
+            .system_markets(system_symbol)
+            .await?
+            .iter()
+            .filter(|m| m.symbol == ship.nav.waypoint_symbol || plans.contains_key(&m.symbol))
+            .map(|m| (trade::cargo_value(&ship.cargo.inventory, m), &m.symbol))
+            .filter(|(value, _)| *value > 0)
+            .max()
+            .map(|(_, symbol)| symbol.clone())
+            .ok_or_else(|| {
+                anyhow::anyhow!("no market we can get to buys what {} mined", ship.symbol)
+            })
+    }
+
+    /**
+     * Whether the ship's at `destination`. If it isn't, gets the executor
+     * flying it there, or waits on the route it's already flying, and
+     * returns once that's done.
+     */
+    async fn go_to(&self, ship: &Ship, destination: &str) -> anyhow::Result<bool> {
+        if at(ship, destination) {
+            return Ok(true);
+        }
+
+        let heading_there = self
+            .store
+            .ship_route(&ship.symbol)
+            .await?
+            .is_some_and(|r| r.destination() == Some(destination));
+        if !heading_there {
+            if ship.nav.status == ShipNavStatus::InTransit {
+                // Let it land before planning from where it ends up
+                tokio::time::sleep(ROUTE_CHECK).await;
+                return Ok(false);
+            }
+            let conf = &self.conf;
+            let waypoints =
+                spacetraders::system_waypoints(conf, ship.nav.system_symbol.clone(), &self.store)
+                    .await?;
+            let fuel_stations = spacetraders::fuel_stations(conf, &waypoints, &self.store).await?;
+            let Some(plan) =
+                nav::plan_routes(ship.into(), &waypoints, &fuel_stations).remove(destination)
+            else {
+                anyhow::bail!(
+                    "no route from {} to {destination}",
+                    ship.nav.waypoint_symbol
+                );
+            };
+            self.executor.start(&ship.symbol, plan).await?;
+        }
+
+        // The executor forgets the route once it's flown it
+        while let Some(route) = self.store.ship_route(&ship.symbol).await? {
+            if let Some(err) = route.error {
+                anyhow::bail!("couldn't get to {destination}: {err}");
+            }
+            tokio::time::sleep(ROUTE_CHECK).await;
+        }
+        Ok(false)
+    }
+
+    /// Sells what the market takes and fills up the tank, if it sells fuel.
+    /// Returns what the cargo fetched.
+    async fn sell(&self, ship: Ship) -> anyhow::Result<i64> {
+        let conf = &self.conf;
+        if ship.nav.status != ShipNavStatus::Docked {
+            fleet_api::dock_ship(conf, &ship.symbol).await?;
+        }
+
+        let ship_symbol = ship.symbol.clone();
+        let waypoint_symbol = ship.nav.waypoint_symbol.clone();
+        let (units, capacity) = (ship.cargo.units, ship.cargo.capacity);
+        let (fuel, fuel_capacity) = (ship.fuel.current, ship.fuel.capacity);
+        let sale = spacetraders::ship_cargo_dump(
+            conf,
+            ShipOrShipSymbol::Ship(ship),
+            &self.store,
+            self.settings.sell_floor,
+        )
+        .await?;
+
+        let sold: i32 = sale.transactions.iter().map(|t| t.units).sum();
+        if units - sold >= capacity {
+            anyhow::bail!("couldn't sell enough to make room in the hold");
+        }
+
+        let sells_fuel = match self.store.market(&waypoint_symbol).await? {
+            Some(market) => !nav::fuel_stations(&[market.data]).is_empty(),
+            None => false,
+        };
+        if sells_fuel && fuel < fuel_capacity {
+            fleet_api::refuel_ship(conf, &ship_symbol, Some(RefuelShipRequest::new())).await?;
+        }
+        Ok(sale.credits)
+    }
+}
+
+async fn wait_for_cooldown(ship: &Ship) {
+    if ship.cooldown.remaining_seconds > 0 {
+        let wait = Duration::from_secs(ship.cooldown.remaining_seconds as u64);
+        // The server sometimes takes a moment to agree it's over
+        tokio::time::sleep(wait + Duration::from_secs(1)).await;
+    }
+}
+
+fn on_cooldown(err: &AppError) -> bool {
+    matches!(err, AppError::Game { kind, .. } if matches!(**kind, ApiErrorKind::Cooldown(_)))
+}
+
+/// Whether the ship's sitting at `waypoint_symbol`, rather than flying
+/// through.
+fn at(ship: &Ship, waypoint_symbol: &str) -> bool {
+    ship.nav.status != ShipNavStatus::InTransit && ship.nav.waypoint_symbol == waypoint_symbol
+}
+
+fn buys(market: &Market, trade_symbol: TradeSymbol) -> bool {
+    market
+        .imports
+        .iter()
+        .chain(&market.exchange)
+        .any(|g| g.symbol == trade_symbol)
+}
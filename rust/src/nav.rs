@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use spacedust::models::{Market, Ship, ShipNavFlightMode, TradeSymbol, Waypoint};
+
+/**
+ * The game's published travel formulas. Distances are in-system map units,
+ * and the API rounds them before doing anything else, so we do too.
+ */
+pub fn distance(from: &Waypoint, to: &Waypoint) -> f64 {
+    (((to.x - from.x).pow(2) + (to.y - from.y).pow(2)) as f64).sqrt()
+}
+
+fn rounded_distance(distance: f64) -> i32 {
+    distance.round().max(1.0) as i32
+}
+
+/// Fuel burned flying `distance` in `mode`. Going nowhere is free.
+pub fn fuel_cost(distance: f64, mode: ShipNavFlightMode) -> i32 {
+    if distance == 0.0 {
+        return 0;
+    }
+    let distance = rounded_distance(distance);
+    match mode {
+        ShipNavFlightMode::Drift => 1,
+        ShipNavFlightMode::Stealth | ShipNavFlightMode::Cruise => distance,
+        ShipNavFlightMode::Burn => distance * 2,
+    }
+}
+
+/// Seconds it takes a ship with the given engine speed to fly `distance`.
+pub fn travel_time(distance: f64, mode: ShipNavFlightMode, engine_speed: i32) -> i64 {
+    if distance == 0.0 {
+        return 0;
+    }
+    let multiplier = match mode {
+        ShipNavFlightMode::Drift => 250.0,
+        ShipNavFlightMode::Stealth => 30.0,
+        ShipNavFlightMode::Cruise => 25.0,
+        ShipNavFlightMode::Burn => 12.5,
+    };
+    let speed = engine_speed.max(1) as f64;
+    (rounded_distance(distance) as f64 * (multiplier / speed) + 15.0).round() as i64
+}
+
+/// One leg of a route. The ship refuels at `from` first if `refuel` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hop {
+    pub from: String,
+    pub to: String,
+    pub mode: ShipNavFlightMode,
+    pub refuel: bool,
+    pub fuel: i32,
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutePlan {
+    pub hops: Vec<Hop>,
+}
+
+impl RoutePlan {
+    pub fn duration(&self) -> i64 {
+        self.hops.iter().map(|h| h.duration).sum()
+    }
+
+    pub fn fuel(&self) -> i32 {
+        self.hops.iter().map(|h| h.fuel).sum()
+    }
+
+    pub fn refuel_stops(&self) -> usize {
+        self.hops.iter().filter(|h| h.refuel).count()
+    }
+}
+
+/// What the planner needs to know about the ship that's flying.
+pub struct NavShip<'a> {
+    pub location: &'a str,
+    pub fuel: i32,
+    pub fuel_capacity: i32,
+    pub engine_speed: i32,
+}
+
+impl<'a> From<&'a Ship> for NavShip<'a> {
+    fn from(ship: &'a Ship) -> Self {
+        NavShip {
+            location: &ship.nav.waypoint_symbol,
+            fuel: ship.fuel.current,
+            fuel_capacity: ship.fuel.capacity,
+            engine_speed: ship.engine.speed,
+        }
+    }
+}
+
+/// Waypoints whose marketplace sells fuel.
+pub fn fuel_stations(markets: &[Market]) -> HashSet<String> {
+    markets
+        .iter()
+        .filter(|m| m.exchange.iter().any(|e| e.symbol == TradeSymbol::Fuel))
+        .map(|m| m.symbol.clone())
+        .collect()
+}
+
+/**
+ * The quickest way to fly one leg with `fuel` in the tank: burn if we can
+ * afford it, then cruise, otherwise drift. The ship fills up at every stop,
+ * so there's no saving fuel for later. Ships without a tank always cruise.
+ */
+fn fly(
+    ship: &NavShip,
+    from: &Waypoint,
+    to: &Waypoint,
+    fuel: i32,
+) -> Option<(ShipNavFlightMode, i32, i64)> {
+    let distance = distance(from, to);
+    if ship.fuel_capacity == 0 {
+        let mode = ShipNavFlightMode::Cruise;
+        return Some((mode, 0, travel_time(distance, mode, ship.engine_speed)));
+    }
+
+    [
+        ShipNavFlightMode::Burn,
+        ShipNavFlightMode::Cruise,
+        ShipNavFlightMode::Drift,
+    ]
+    .into_iter()
+    .find_map(|mode| {
+        let cost = fuel_cost(distance, mode);
+        (cost <= fuel).then(|| (mode, cost, travel_time(distance, mode, ship.engine_speed)))
+    })
+}
+
+/**
+ * Plans the fastest route from the ship's location to every other waypoint
+ * in the system.
+ *
+ * The only reason to stop on the way is to refuel, so the graph is just the
+ * ship's starting point plus every fuel station, and the ship fills up to
+ * capacity at each station it stops at. Dijkstra over that gets us the best
+ * way to reach each station, and then each destination is one more leg from
+ * whichever of those is quickest.
+ */
+pub fn plan_routes(
+    ship: NavShip,
+    waypoints: &[Waypoint],
+    fuel_stations: &HashSet<String>,
+) -> HashMap<String, RoutePlan> {
+    let Some(start) = waypoints.iter().position(|w| w.symbol == ship.location) else {
+        return HashMap::new();
+    };
+
+    // Node 0 is the start, the rest are fuel stations
+    let mut nodes = vec![start];
+    nodes.extend(
+        waypoints
+            .iter()
+            .enumerate()
+            .filter(|(i, w)| *i != start && fuel_stations.contains(&w.symbol))
+            .map(|(i, _)| i),
+    );
+
+    let start_refuels = fuel_stations.contains(ship.location) && ship.fuel < ship.fuel_capacity;
+    let fuel_leaving = |node: usize| {
+        if node == 0 && !start_refuels {
+            ship.fuel
+        } else {
+            ship.fuel_capacity
+        }
+    };
+    let refuels_at = |node: usize| node != 0 || start_refuels;
+
+    let mut best: Vec<Option<i64>> = vec![None; nodes.len()];
+    let mut previous: Vec<Option<(usize, Hop)>> = vec![None; nodes.len()];
+    let mut done = vec![false; nodes.len()];
+    best[0] = Some(0);
+
+    while let Some(node) = (0..nodes.len())
+        .filter(|&n| !done[n] && best[n].is_some())
+        .min_by_key(|&n| best[n])
+    {
+        done[node] = true;
+        let from = &waypoints[nodes[node]];
+        for next in 0..nodes.len() {
+            if done[next] {
+                continue;
+            }
+            let to = &waypoints[nodes[next]];
+            let Some((mode, fuel, duration)) = fly(&ship, from, to, fuel_leaving(node)) else {
+                continue;
+            };
+            let time = best[node].unwrap() + duration;
+            if best[next].is_none_or(|b| time < b) {
+                best[next] = Some(time);
+                previous[next] = Some((
+                    node,
+                    Hop {
+                        from: from.symbol.clone(),
+                        to: to.symbol.clone(),
+                        mode,
+                        refuel: refuels_at(node),
+                        fuel,
+                        duration,
+                    },
+                ));
+            }
+        }
+    }
+
+    let hops_to = |mut node: usize| {
+        let mut hops = vec![];
+        while let Some((prev, hop)) = &previous[node] {
+            hops.push(hop.clone());
+            node = *prev;
+        }
+        hops.reverse();
+        hops
+    };
+
+    let mut plans = HashMap::new();
+    for (i, destination) in waypoints.iter().enumerate() {
+        if i == start {
+            continue;
+        }
+        if let Some(node) = nodes.iter().position(|&n| n == i) {
+            if best[node].is_some() {
+                plans.insert(
+                    destination.symbol.clone(),
+                    RoutePlan {
+                        hops: hops_to(node),
+                    },
+                );
+            }
+            continue;
+        }
+
+        let last_leg = (0..nodes.len())
+            .filter(|&n| best[n].is_some())
+            .filter_map(|n| {
+                let (mode, fuel, duration) =
+                    fly(&ship, &waypoints[nodes[n]], destination, fuel_leaving(n))?;
+                Some((best[n].unwrap() + duration, n, mode, fuel, duration))
+            })
+            .min_by_key(|(time, ..)| *time);
+
+        if let Some((_, node, mode, fuel, duration)) = last_leg {
+            let mut hops = hops_to(node);
+            hops.push(Hop {
+                from: waypoints[nodes[node]].symbol.clone(),
+                to: destination.symbol.clone(),
+                mode,
+                refuel: refuels_at(node),
+                fuel,
+                duration,
+            });
+            plans.insert(destination.symbol.clone(), RoutePlan { hops });
+        }
+    }
+
+    plans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::waypoint;
+
+    fn ship(fuel: i32, fuel_capacity: i32) -> NavShip<'static> {
+        NavShip {
+            location: "X1-TEST-A",
+            fuel,
+            fuel_capacity,
+            engine_speed: 30,
+        }
+    }
+
+    fn stations(symbols: &[&str]) -> HashSet<String> {
+        symbols.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn direct_hop() {
+        let waypoints = [waypoint("X1-TEST-A", 0), waypoint("X1-TEST-B", 30)];
+        let plans = plan_routes(ship(40, 100), &waypoints, &stations(&[]));
+
+        let hops = &plans["X1-TEST-B"].hops;
+        assert_eq!(hops.len(), 1);
+        assert_eq!(hops[0].mode, ShipNavFlightMode::Cruise);
+        assert_eq!(hops[0].fuel, 30);
+        assert!(!hops[0].refuel);
+        assert!(!plans.contains_key("X1-TEST-A"));
+    }
+
+    #[test]
+    fn burns_when_the_tank_allows() {
+        let waypoints = [waypoint("X1-TEST-A", 0), waypoint("X1-TEST-B", 30)];
+        let plans = plan_routes(ship(60, 100), &waypoints, &stations(&[]));
+
+        let hop = &plans["X1-TEST-B"].hops[0];
+        assert_eq!(hop.mode, ShipNavFlightMode::Burn);
+        assert_eq!(hop.fuel, 60);
+        assert_eq!(hop.duration, 28);
+    }
+
+    #[test]
+    fn stops_to_refuel_rather_than_drift() {
+        let waypoints = [
+            waypoint("X1-TEST-A", 0),
+            waypoint("X1-TEST-S", 60),
+            waypoint("X1-TEST-C", 120),
+        ];
+        let plans = plan_routes(ship(80, 80), &waypoints, &stations(&["X1-TEST-S"]));
+
+        let plan = &plans["X1-TEST-C"];
+        let legs: Vec<_> = plan
+            .hops
+            .iter()
+            .map(|h| (h.from.as_str(), h.to.as_str(), h.mode, h.refuel))
+            .collect();
+        assert_eq!(
+            legs,
+            vec![
+                ("X1-TEST-A", "X1-TEST-S", ShipNavFlightMode::Cruise, false),
+                ("X1-TEST-S", "X1-TEST-C", ShipNavFlightMode::Cruise, true),
+            ]
+        );
+        assert_eq!(plan.refuel_stops(), 1);
+        assert_eq!(plan.fuel(), 120);
+    }
+
+    #[test]
+    fn unreachable_without_fuel() {
+        let waypoints = [waypoint("X1-TEST-A", 0), waypoint("X1-TEST-B", 10)];
+        let plans = plan_routes(ship(0, 100), &waypoints, &stations(&[]));
+        assert!(!plans.contains_key("X1-TEST-B"));
+    }
+
+    #[test]
+    fn tankless_ships_go_anywhere() {
+        let waypoints = [waypoint("X1-TEST-A", 0), waypoint("X1-TEST-B", 500)];
+        let plans = plan_routes(ship(0, 0), &waypoints, &stations(&[]));
+        assert_eq!(plans["X1-TEST-B"].fuel(), 0);
+    }
+}
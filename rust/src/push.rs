@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use web_push::{
+    ContentEncoding, HyperWebPushClient, PartialVapidSignatureBuilder, SubscriptionInfo,
+    VapidSignatureBuilder, WebPushClient, WebPushError, WebPushMessageBuilder,
+};
+
+use spacedust::rate_limit;
+
+use crate::config::WebPushConfig;
+use crate::spacetraders;
+use crate::store::Store;
+use crate::AppStateShared;
+
+/// How often to look for contracts running out of time
+const CONTRACT_CHECK: Duration = Duration::from_secs(5 * 60);
+
+/// What sw.js shows, and where clicking on it goes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub url: String,
+}
+
+/**
+ * Signs notifications with our VAPID key and hands them to the browsers' push
+ * services. There's one for the whole app, since there's only the one key,
+ * and the page subscribes with its public half.
+ */
+pub struct WebPush {
+    key: PartialVapidSignatureBuilder,
+    subject: Option<String>,
+    pub contract_warning: Duration,
+    client: HyperWebPushClient,
+}
+
+impl WebPush {
+    pub fn new(config: &WebPushConfig) -> anyhow::Result<WebPush> {
+        let key = VapidSignatureBuilder::from_base64_no_sub(
+            &config.private_key,
+            web_push::URL_SAFE_NO_PAD,
+        )
+        .map_err(|e| anyhow::anyhow!("The web push private key isn't usable: {e}"))?;
+        Ok(WebPush {
+            key,
+            subject: config.subject.clone(),
+            contract_warning: config.contract_warning,
+            client: HyperWebPushClient::new(),
+        })
+    }
+
+    /// What the page passes to `pushManager.subscribe`.
+    pub fn public_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.key.get_public_key())
+    }
+
+    async fn send(
+        &self,
+        subscription: &SubscriptionInfo,
+        notification: &Notification,
+    ) -> Result<(), WebPushError> {
+        let mut signature = self.key.clone().add_sub_info(subscription);
+        if let Some(subject) = &self.subject {
+            signature.add_claim("sub", subject.as_str());
+        }
+
+        let payload =
+            serde_json::to_vec(notification).map_err(|e| WebPushError::Other(e.to_string()))?;
+        let mut message = WebPushMessageBuilder::new(subscription);
+        message.set_payload(ContentEncoding::Aes128Gcm, &payload);
+        message.set_vapid_signature(signature.build()?);
+        // Nobody needs to hear a ship landed a day late
+        message.set_ttl(60 * 60);
+        self.client.send(message.build()?).await
+    }
+}
+
+/**
+ * Sends one agent's notifications to every browser that's subscribed to it.
+ * Without web push configured, notifications go nowhere.
+ */
+#[derive(Clone)]
+pub struct Notifier {
+    web_push: Option<Arc<WebPush>>,
+    store: Store,
+}
+
+impl Notifier {
+    pub fn new(web_push: Option<Arc<WebPush>>, store: Store) -> Notifier {
+        Notifier { web_push, store }
+    }
+
+    /// Sends in the background, since nothing should wait on push services.
+    pub fn notify(&self, notification: Notification) {
+        let Some(web_push) = self.web_push.clone() else {
+            return;
+        };
+        let store = self.store.clone();
+        tokio::spawn(async move {
+            let subscriptions = match store.web_push_subscriptions().await {
+                Ok(subscriptions) => subscriptions,
+                Err(err) => {
+                    println!("Couldn't load web push subscriptions: {err:#}");
+                    return;
+                }
+            };
+            for subscription in subscriptions {
+                match web_push.send(&subscription, &notification).await {
+                    Ok(()) => {}
+                    // The browser unsubscribed, or the subscription expired
+                    Err(WebPushError::EndpointNotValid | WebPushError::EndpointNotFound) => {
+                        let _ = store
+                            .delete_web_push_subscription(&subscription.endpoint)
+                            .await;
+                    }
+                    Err(err) => println!("Couldn't send a notification: {err}"),
+                }
+            }
+        });
+    }
+}
+
+/**
+ * Warns `name`'s subscribers about accepted contracts that are about to run
+ * out of time. Each contract only gets the one warning (per run of the app,
+ * anyway).
+ */
+pub fn watch_contracts(app: AppStateShared, name: String, web_push: Arc<WebPush>) {
+    tokio::spawn(async move {
+        let mut warned: HashSet<String> = HashSet::new();
+        let mut interval = tokio::time::interval(CONTRACT_CHECK);
+        loop {
+            interval.tick().await;
+            let Some(agent) = app.agent(&name) else {
+                return;
+            };
+            // A cassette only has the requests it was recorded with
+            if agent.config.replay.is_some() {
+                return;
+            }
+
+            let contracts =
+                match rate_limit::background(spacetraders::get_my_contracts(&agent.conf)).await {
+                    Ok(contracts) => contracts,
+                    Err(err) => {
+                        println!("Couldn't check {name}'s contracts: {err}");
+                        continue;
+                    }
+                };
+
+            let now = Utc::now();
+            for contract in contracts {
+                if !contract.accepted || contract.fulfilled || warned.contains(&contract.id) {
+                    continue;
+                }
+                let Ok(deadline) = DateTime::parse_from_rfc3339(&contract.terms.deadline) else {
+                    continue;
+                };
+                let Ok(left) = (deadline.with_timezone(&Utc) - now).to_std() else {
+                    continue;
+                };
+                if left > web_push.contract_warning {
+                    continue;
+                }
+
+                warned.insert(contract.id.clone());
+                agent.notifier.notify(Notification {
+                    title: format!("Contract {} is nearly due", contract.id),
+                    body: format!(
+                        "{} left to fulfill it",
+                        humantime::format_duration(Duration::from_secs(left.as_secs()))
+                    ),
+                    url: "/".to_string(),
+                });
+            }
+        }
+    });
+}
@@ -1,6 +1,28 @@
 use maud::{html, Markup, PreEscaped, DOCTYPE};
 
-pub fn page(content: Markup, scripts: Option<Markup>) -> Markup {
+/// The agents someone can flip between, for the page header.
+pub struct AgentSwitcher {
+    pub current: String,
+    pub agents: Vec<String>,
+}
+
+fn agent_switcher_html(switcher: AgentSwitcher) -> Markup {
+    html! {
+        li class="flex gap-x-1 items-center" {
+            @for agent in switcher.agents {
+                @if agent == switcher.current {
+                    span class="px-2 rounded bg-gray-700 text-white" aria-current="true" {(agent)}
+                } @else {
+                    form method="post" action=(format!("/agents/{agent}/select")) {
+                        button class="px-2 rounded border border-gray-400" {(agent)}
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn page(agents: AgentSwitcher, content: Markup, scripts: Option<Markup>) -> Markup {
     html! {
         (DOCTYPE)
         html lang="en" class="no-js" hx-ext="morph" {
@@ -51,6 +73,10 @@ pub fn page(content: Markup, scripts: Option<Markup>) -> Markup {
                 a href="/" {"Space Traders"}
               }
 
+              @if agents.agents.len() > 1 {
+                (agent_switcher_html(agents))
+              }
+
             /*
               @match user {
                 None => (anonymous_menu()),
@@ -85,6 +111,7 @@ pub fn page(content: Markup, scripts: Option<Markup>) -> Markup {
             script src="https://unpkg.com/cytoscape-cola/cytoscape-cola.js" {}
             script src="https://unpkg.com/cytoscape-elk/cytoscape-elk.js" {}
             script src="/map.js" {}
+            script src="/fleet.js" {}
 
             script{(PreEscaped(r#"
             document.body.addEventListener('htmx:responseError', function (evt) {
@@ -121,10 +148,15 @@ pub fn page(content: Markup, scripts: Option<Markup>) -> Markup {
                 navigator.serviceWorker.register('/sw.js', {
                   scope: '/',
                 });
+                // Nothing to subscribe to if the server can't send pushes
+                const key = await fetch('/web_push/public_key');
+                if (!key.ok) {
+                  return;
+                }
                 const registration = await navigator.serviceWorker.ready;
                 const subscription = await registration.pushManager.subscribe({
                   userVisibleOnly: true,
-                  applicationServerKey: urlBase64ToUint8Array('BLx-q_uJpcwePUvIXEiJbqUPXpHWd41ow-oAkHcUmjq5JWTbHNylWCi5KNoDhts-WJRhNpMnIuqmZn0_vjwGIb8')
+                  applicationServerKey: urlBase64ToUint8Array(await key.text())
                 });
                 await fetch(
                   '/web_push/subscribe',
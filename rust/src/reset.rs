@@ -0,0 +1,134 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use spacedust::apis::api_error::ApiErrorKind;
+use spacedust::apis::{agents_api, default_api, Error};
+use spacedust::models::{FactionSymbol, RegisterRequest};
+
+use crate::agents::AgentState;
+use crate::error::AppResult;
+use crate::store::{SETTING_RESET_DATE, SETTING_TOKEN};
+use crate::AppStateShared;
+
+/**
+ * Keeps an eye on the server for `name`'s sake. The universe is wiped every
+ * so often, which takes our token and everything we've cached with it, so
+ * every `reset_check` we ask the server when it last reset. If that's
+ * changed, we throw out the cache and get ourselves a new token, either by
+ * registering the agent configured for it or by asking someone to fill in
+ * the registration form.
+ */
+pub fn watch(app: AppStateShared, name: String) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(app.config.reset_check);
+        loop {
+            interval.tick().await;
+            let Some(agent) = app.agent(&name) else {
+                return;
+            };
+            // A cassette only has the requests it was recorded with
+            if agent.config.replay.is_some() {
+                return;
+            }
+            if let Err(err) = check(&app, agent).await {
+                println!("Couldn't check {name} for a server reset: {err:#}");
+            }
+        }
+    });
+}
+
+async fn check(app: &AppStateShared, agent: Arc<AgentState>) -> anyhow::Result<()> {
+    let status = default_api::get_status(&agent.conf).await?;
+    let last_reset = agent.store.setting(SETTING_RESET_DATE).await?;
+    if last_reset.as_ref().is_some_and(|r| *r != status.reset_date) {
+        println!(
+            "The server reset on {}, forgetting everything {} knew",
+            status.reset_date, agent.name
+        );
+        agent.executor.stop();
+        agent.miner.stop();
+        agent.store.wipe().await?;
+    }
+    agent
+        .store
+        .put_setting(SETTING_RESET_DATE, &status.reset_date)
+        .await?;
+
+    if authorized(&agent).await? {
+        agent.registration_needed.store(false, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    // Maybe we registered since the config was written
+    if let Some(token) = agent.store.setting(SETTING_TOKEN).await? {
+        if agent.conf.bearer_access_token.as_ref() != Some(&token) {
+            let candidate = agent.with_token(token);
+            if authorized(&candidate).await? {
+                app.replace_agent(candidate).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    match &agent.config.register {
+        Some(registration) => {
+            register(
+                app,
+                &agent,
+                registration.symbol.clone(),
+                registration.faction,
+                registration.email.clone(),
+            )
+            .await?;
+        }
+        None => {
+            if !agent.registration_needed.swap(true, Ordering::Relaxed) {
+                println!("{} needs registering", agent.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the server still recognizes the agent's token.
+async fn authorized(agent: &AgentState) -> anyhow::Result<bool> {
+    if agent.conf.bearer_access_token.is_none() {
+        return Ok(false);
+    }
+    match agents_api::get_my_agent(&agent.conf).await {
+        Ok(_) => Ok(true),
+        Err(err) if err.api_error_kind() == Some(ApiErrorKind::Unauthorized) => Ok(false),
+        Err(Error::ResponseError(response)) if response.status.as_u16() == 401 => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/**
+ * Signs `agent` up as a new agent in the game, and switches it over to the
+ * token that gets us. The token's saved too, so a restart doesn't need to
+ * register again. Returns the token, since it's the only way to log in to the
+ * game anywhere else.
+ */
+pub async fn register(
+    app: &AppStateShared,
+    agent: &AgentState,
+    symbol: String,
+    faction: FactionSymbol,
+    email: Option<String>,
+) -> AppResult<String> {
+    // Registering is the one request that mustn't carry a token
+    let mut conf = agent.conf.clone();
+    conf.bearer_access_token = None;
+    let mut request = RegisterRequest::new(faction, symbol);
+    request.email = email;
+    let response = default_api::register(&conf, Some(request)).await?;
+    let token = response.data.token;
+    println!(
+        "Registered {} as {}",
+        agent.name, response.data.agent.symbol
+    );
+
+    agent.store.put_setting(SETTING_TOKEN, &token).await?;
+    app.replace_agent(agent.with_token(token.clone())).await?;
+    Ok(token)
+}
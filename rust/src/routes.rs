@@ -1,70 +1,104 @@
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+
+use futures::{Stream, TryStreamExt};
 use maud::{html, Markup};
+use tokio::sync::broadcast::error::RecvError;
 
-use spacedust::apis::fleet_api;
-use spacedust::models::{NavigateShipRequest, Ship, ShipNavStatus, ShipType};
+use spacedust::apis::{fleet_api, pagination};
+use spacedust::models::ship_refine_request::Produce;
+use spacedust::models::{
+    FactionSymbol, JumpGate, Ship, ShipNavStatus, ShipType, TradeSymbol, WaypointType,
+};
+use spacedust::rate_limit;
 
 use axum::debug_handler;
-use axum::extract::{Path, State};
+use axum::extract::{Form, Json, Path, State};
 use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Redirect, Response};
 
 use serde::Deserialize;
+use web_push::SubscriptionInfo;
 
+use crate::executor::ActiveRoute;
+use crate::miner::{MiningJob, MiningRole};
+use crate::nav;
 use crate::render::page;
+use crate::reset;
 use crate::spacetraders::{self, ShipOrShipSymbol, ShipWaypoint};
+use crate::trade::TradeRoute;
 
-use crate::fragments;
+use crate::agents::{AgentState, CurrentAgent, AGENT_COOKIE};
+use crate::error::AppError;
+use crate::fragments::{self, ShipReport};
 use crate::AppStateShared;
 
-pub struct AppError(anyhow::Error);
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
-        )
-            .into_response()
+#[debug_handler]
+pub async fn index(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+) -> Result<Response, AppError> {
+    // Nothing below works without a token the server knows
+    if state.registration_needed.load(Ordering::Relaxed) {
+        return Ok(Redirect::to("/register").into_response());
     }
-}
 
-// This enables using `?` on functions that return `Result<_, anyhow::Error>` to turn them into
-// `Result<_, AppError>`. That way you don't need to do that manually.
-impl<E> From<E> for AppError
-where
-    E: Into<anyhow::Error>,
-{
-    fn from(err: E) -> Self {
-        Self(err.into())
-    }
-}
-
-#[debug_handler]
-pub async fn index(State(state): State<AppStateShared>) -> Result<Markup, AppError> {
     let conf = &state.conf;
-    let agent = spacetraders::agent(conf).await;
+    let agent = spacetraders::agent(conf).await?;
 
-    let contracts = spacetraders::get_my_contracts(conf).await;
+    let contracts = spacetraders::get_my_contracts(conf).await?;
 
-    let system_symbol = spacetraders::agent_system(conf).await;
+    let system_symbol = spacetraders::agent_system(conf).await?;
+    let system = spacetraders::get_system(conf, &system_symbol, &state.store).await?;
     let waypoints =
-        spacetraders::system_waypoints(conf, system_symbol, state.waypoints_cache.clone()).await;
+        spacetraders::system_waypoints(conf, system_symbol.clone(), &state.store).await?;
+
+    let mut jump_gates: Vec<(String, JumpGate)> = vec![];
+    for waypoint in waypoints
+        .iter()
+        .filter(|w| w.r#type == WaypointType::JumpGate)
+    {
+        if let Some(jump_gate) = spacetraders::get_jump_gate(conf, waypoint, &state.store).await? {
+            jump_gates.push((waypoint.symbol.clone(), jump_gate));
+        }
+    }
 
-    let ships = spacetraders::get_my_ships(conf).await;
+    let ships = spacetraders::get_my_ships(conf).await?;
+    let ships_at_factions = spacetraders::ships_at_factions(conf, &ships, &state.store).await?;
+    let contracts = fragments::contracts_html(contracts, &ships, &ships_at_factions);
+
+    let surveys = state.store.system_surveys(&system_symbol).await?;
+    let mining = fragments::mining_groups_html(state.store.mining_jobs().await?, &ships);
+    let surveys = spacetraders::rank_surveys(&system_symbol, surveys, &state.store).await?;
 
     let map_json = spacetraders::map_data(waypoints.clone(), ships.clone());
 
-    let mut ships_with_waypoints: Vec<(Ship, ShipWaypoint)> = vec![];
+    let mut best_trades: Vec<(String, Vec<TradeRoute>)> = vec![];
+    for ship in ships.iter().filter(|s| s.cargo.capacity > 0) {
+        let mut trades = spacetraders::best_trades(ship, &waypoints, &state.store).await?;
+        trades.truncate(5);
+        best_trades.push((ship.symbol.clone(), trades));
+    }
+
+    let mut ships_with_waypoints: Vec<(
+        Ship,
+        ShipWaypoint,
+        Option<ActiveRoute>,
+        Option<MiningJob>,
+    )> = vec![];
     for ship in ships {
-        let ship_waypoint = spacetraders::get_ship_with_waypoint(
-            conf,
-            ShipOrShipSymbol::Ship(ship),
-            &state.waypoints_cache,
-        )
-        .await;
-        ships_with_waypoints.push(ship_waypoint);
+        state.fleet.track(&ship);
+        let route = state.store.ship_route(&ship.symbol).await?;
+        let mining = state.store.mining_job(&ship.symbol).await?;
+        let (ship, ship_waypoint) =
+            spacetraders::get_ship_with_waypoint(conf, ShipOrShipSymbol::Ship(ship), &state.store)
+                .await?;
+        ships_with_waypoints.push((ship, ship_waypoint, route, mining));
     }
 
     Ok(page(
+        app.agent_switcher(&state),
         html! {
             div class="map w-full h-96 border-2 border-gray-700 rounded-lg" up-data=(map_json) {}
 
@@ -78,23 +112,42 @@ pub async fn index(State(state): State<AppStateShared>) -> Result<Markup, AppErr
                 (fragments::ships_html(ships_with_waypoints))
             }
 
+            div {
+                header class="text-lg font-semibold" {"Best trades"}
+                (fragments::best_trades_html(best_trades))
+            }
+
             div {
                 header class="text-lg font-semibold" {"Contracts"}
-                @if contracts.is_empty() {
-                    div {"You have no contracts."}
-                }
-                @for contract in contracts {
-                    (fragments::contract_html(contract))
-                }
+                (contracts)
+            }
+
+            div {
+                header class="text-lg font-semibold" {"Mining"}
+                (mining)
+            }
+
+            div {
+                header class="text-lg font-semibold" {"Surveys"}
+                (fragments::surveys_html(surveys))
             }
 
             div {
-                header class="text-lg font-semibold" {"Waypoints"}
+                header class="text-lg font-semibold" {"Jump gates"}
+                (fragments::jump_gates_html(jump_gates))
+            }
+
+            div {
+                header class="text-lg font-semibold" {
+                    "Waypoints in " (system.symbol) " "
+                    span class="text-sm text-gray-700 capitalize" {(system.r#type.to_string().to_lowercase())}
+                }
                 (fragments::waypoints_html(waypoints))
             }
         },
         None,
-    ))
+    )
+    .into_response())
 }
 
 #[derive(Deserialize, Debug)]
@@ -104,14 +157,17 @@ pub struct ShipyardParams {
 }
 #[debug_handler]
 pub async fn shipyard(
-    State(state): State<AppStateShared>,
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipyardParams>,
 ) -> Result<Markup, AppError> {
     let conf = &state.conf;
-    let shipyard = spacetraders::get_shipyard(conf, &params.system, &params.waypoint).await;
+    let shipyard =
+        spacetraders::get_shipyard(conf, &params.system, &params.waypoint, &state.store).await?;
     //println!("Shipyard: {:?}", shipyard);
 
     Ok(page(
+        app.agent_switcher(&state),
         html! {
             div {"Shipyard " (shipyard.symbol.to_string())}
             (fragments::shipyard_html(shipyard))
@@ -120,18 +176,139 @@ pub async fn shipyard(
     ))
 }
 
+#[derive(Deserialize, Debug)]
+pub struct MarketParams {
+    system: String,
+    waypoint: String,
+}
+#[debug_handler]
+pub async fn market(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<MarketParams>,
+) -> Result<Markup, AppError> {
+    Ok(page(
+        app.agent_switcher(&state),
+        html! {
+            header class="text-lg font-semibold" {"Market " (params.waypoint)}
+            (market_html(&state, &params).await?)
+        },
+        None,
+    ))
+}
+
+/**
+ * The market's live prices need one of our ships there. Without one, the
+ * last prices we saw are better than nothing, as long as we say how old they
+ * are.
+ */
+async fn market_html(state: &AgentState, params: &MarketParams) -> Result<Markup, AppError> {
+    let conf = &state.conf;
+    let mut market =
+        spacetraders::get_market(conf, &params.system, &params.waypoint, &state.store).await?;
+    let mut prices_seen_at = None;
+    if market.trade_goods.is_none() {
+        if let Some(record) = state.store.market(&params.waypoint).await? {
+            market.trade_goods = record.data.trade_goods;
+            prices_seen_at = Some(record.fetched_at);
+        }
+    }
+
+    let credits = spacetraders::agent(conf).await?.credits;
+    let ships = spacetraders::get_my_ships(conf)
+        .await?
+        .into_iter()
+        .filter(|s| {
+            s.nav.waypoint_symbol == params.waypoint
+                && s.nav.status != ShipNavStatus::InTransit
+                && s.cargo.capacity > 0
+        })
+        .collect();
+
+    Ok(fragments::market_html(
+        market,
+        prices_seen_at,
+        ships,
+        credits,
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MarketTradeForm {
+    ship_symbol: String,
+    trade_symbol: TradeSymbol,
+    units: i32,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn market_buy(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<MarketParams>,
+    Form(form): Form<MarketTradeForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::market_buy(
+        &state.conf,
+        &form.ship_symbol,
+        form.trade_symbol,
+        form.units,
+        &state.store,
+    )
+    .await?;
+    state.fleet.refresh(&form.ship_symbol).await?;
+    market_html(&state, &params).await
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn market_sell(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<MarketParams>,
+    Form(form): Form<MarketTradeForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::market_sell(
+        &state.conf,
+        &form.ship_symbol,
+        form.trade_symbol,
+        form.units,
+        &state.store,
+    )
+    .await?;
+    state.fleet.refresh(&form.ship_symbol).await?;
+    market_html(&state, &params).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TradeGoodParams {
+    trade_symbol: TradeSymbol,
+}
+#[debug_handler]
+pub async fn trade_good(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<TradeGoodParams>,
+) -> Result<Markup, AppError> {
+    let observations = state.store.price_history(params.trade_symbol).await?;
+
+    Ok(page(
+        app.agent_switcher(&state),
+        html! {
+            header class="text-lg font-semibold" {(params.trade_symbol.to_string()) " prices"}
+            (fragments::price_history_html(params.trade_symbol, observations))
+        },
+        None,
+    ))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ShipBuyParams {
     waypoint: String,
     ship_type: ShipType,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_buy(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipBuyParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
-    spacetraders::ship_buy(conf, params.ship_type, params.waypoint).await;
+    spacetraders::ship_buy(conf, params.ship_type, params.waypoint).await?;
 
     Ok(Redirect::to("/").into_response())
 }
@@ -140,17 +317,27 @@ pub async fn ship_buy(
 pub struct ShipParams {
     ship_symbol: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
     let symbol = ShipOrShipSymbol::Symbol(params.ship_symbol);
-    let (ship, waypoint) =
-        spacetraders::get_ship_with_waypoint(conf, symbol, &state.waypoints_cache).await;
+    // Nobody's waiting on this in particular, so let anything the user
+    // clicked on go first.
+    let (ship, waypoint) = rate_limit::background(spacetraders::get_ship_with_waypoint(
+        conf,
+        symbol,
+        &state.store,
+    ))
+    .await?;
+
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
 
-    Ok(fragments::ship_html(ship, waypoint, None))
+    Ok(fragments::ship_html(ship, waypoint, route, mining, None))
 }
 
 #[derive(Deserialize, Debug)]
@@ -159,23 +346,28 @@ pub struct ShipNavChoicesParams {
 }
 #[debug_handler]
 pub async fn ship_nav_choices(
-    State(state): State<AppStateShared>,
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipNavChoicesParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
 
-    let ship = ShipOrShipSymbol::Symbol(params.ship_symbol).get(conf).await;
+    let ship = ShipOrShipSymbol::Symbol(params.ship_symbol)
+        .get(conf)
+        .await?;
 
-    let system_symbol = spacetraders::agent_system(conf).await;
+    let system_symbol = spacetraders::agent_system(conf).await?;
 
-    let waypoints =
-        spacetraders::system_waypoints(conf, system_symbol, state.waypoints_cache.clone()).await;
-    let waypoints = spacetraders::get_ship_nav_choices(&ship, waypoints).await;
+    let waypoints = spacetraders::system_waypoints(conf, system_symbol, &state.store).await?;
+    let fuel_stations = spacetraders::fuel_stations(conf, &waypoints, &state.store).await?;
+    let waypoints = spacetraders::get_ship_nav_choices(&ship, waypoints, &fuel_stations).await?;
 
     Ok(page(
+        app.agent_switcher(&state),
         html! {
-            @for (waypoint, dist) in waypoints {
+            @for (waypoint, dist, plan) in waypoints {
                 (fragments::waypoint_html(waypoint, Some((&ship, dist))))
+                (fragments::route_plan_html(plan))
             }
         },
         None,
@@ -187,122 +379,335 @@ pub struct ShipGoParams {
     ship_symbol: String,
     waypoint: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_nav_go(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipGoParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
-    let ship = fleet_api::get_my_ship(conf, params.ship_symbol.as_str())
-        .await
-        .unwrap()
-        .data;
-
-    if ship.nav.status == ShipNavStatus::Docked {
-        fleet_api::orbit_ship(conf, ship.symbol.as_str())
-            .await
-            .unwrap();
-    }
+    let ship = spacetraders::get_ship(conf, &params.ship_symbol).await?;
+
+    let waypoints =
+        spacetraders::system_waypoints(conf, ship.nav.system_symbol.clone(), &state.store).await?;
+    let fuel_stations = spacetraders::fuel_stations(conf, &waypoints, &state.store).await?;
+    let Some(plan) =
+        nav::plan_routes((&ship).into(), &waypoints, &fuel_stations).remove(&params.waypoint)
+    else {
+        return Err(AppError::Invalid(format!(
+            "No route from {} to {}",
+            ship.nav.waypoint_symbol, params.waypoint
+        )));
+    };
 
-    fleet_api::navigate_ship(
+    state.executor.start(&ship.symbol, plan).await?;
+
+    Ok(Redirect::to("/").into_response())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipNavCancelParams {
+    ship_symbol: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_nav_cancel(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipNavCancelParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let conf = &state.conf;
+
+    state.executor.cancel(&params.ship_symbol).await?;
+
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
         conf,
-        ship.symbol.as_str(),
-        Some(NavigateShipRequest::new(params.waypoint)),
+        ShipOrShipSymbol::Symbol(params.ship_symbol),
+        &state.store,
     )
-    .await
-    .unwrap();
+    .await?;
+    state.fleet.track(&ship);
+    let mining = state.store.mining_job(&ship.symbol).await?;
 
-    Ok(Redirect::to("/").into_response())
+    Ok(fragments::ship_html(ship, waypoint, None, mining, None))
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ShipDockParams {
     ship_symbol: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_dock(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipDockParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
 
-    fleet_api::dock_ship(conf, params.ship_symbol.as_str())
-        .await
-        .unwrap();
+    fleet_api::dock_ship(conf, params.ship_symbol.as_str()).await?;
 
     let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
         conf,
         ShipOrShipSymbol::Symbol(params.ship_symbol),
-        &state.waypoints_cache,
+        &state.store,
     )
-    .await;
+    .await?;
 
-    Ok(fragments::ship_html(ship, waypoint, None))
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(ship, waypoint, route, mining, None))
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ShipOrbitParams {
     ship_symbol: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_orbit(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipOrbitParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
 
-    fleet_api::orbit_ship(conf, params.ship_symbol.as_str())
-        .await
-        .unwrap();
+    fleet_api::orbit_ship(conf, params.ship_symbol.as_str()).await?;
 
     let (ship, ship_waypoint) = spacetraders::get_ship_with_waypoint(
         conf,
         ShipOrShipSymbol::Symbol(params.ship_symbol),
-        &state.waypoints_cache,
+        &state.store,
     )
-    .await;
+    .await?;
 
-    Ok(fragments::ship_html(ship, ship_waypoint, None))
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(
+        ship,
+        ship_waypoint,
+        route,
+        mining,
+        None,
+    ))
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ShipRefuelParams {
     ship_symbol: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_refuel(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipRefuelParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
-    let ship = spacetraders::ship_refuel(conf, ShipOrShipSymbol::Symbol(params.ship_symbol)).await;
-    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
-        conf,
-        ShipOrShipSymbol::Ship(ship),
-        &state.waypoints_cache,
-    )
-    .await;
+    let ship =
+        spacetraders::ship_refuel(conf, ShipOrShipSymbol::Symbol(params.ship_symbol)).await?;
+    let (ship, waypoint) =
+        spacetraders::get_ship_with_waypoint(conf, ShipOrShipSymbol::Ship(ship), &state.store)
+            .await?;
+
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
 
-    Ok(fragments::ship_html(ship, waypoint, None))
+    Ok(fragments::ship_html(ship, waypoint, route, mining, None))
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ShipExtractParams {
     ship_symbol: String,
 }
-#[debug_handler]
+#[debug_handler(state = AppStateShared)]
 pub async fn ship_extract(
-    State(state): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipExtractParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
     let symbol = ShipOrShipSymbol::Symbol(params.ship_symbol);
-    let r#yield = spacetraders::ship_extract(conf, symbol.clone()).await;
+    let r#yield = spacetraders::ship_extract(conf, symbol.clone(), &state.store).await?;
 
-    let (ship, waypoint) =
-        spacetraders::get_ship_with_waypoint(conf, symbol, &state.waypoints_cache).await;
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(conf, symbol, &state.store).await?;
 
-    Ok(fragments::ship_html(ship, waypoint, Some(r#yield)))
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(
+        ship,
+        waypoint,
+        route,
+        mining,
+        Some(ShipReport::Extracted(r#yield)),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipSurveyParams {
+    ship_symbol: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_survey(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipSurveyParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let conf = &state.conf;
+    let surveys = spacetraders::ship_survey(conf, &params.ship_symbol, &state.store).await?;
+
+    let symbol = ShipOrShipSymbol::Symbol(params.ship_symbol);
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(conf, symbol, &state.store).await?;
+
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(
+        ship,
+        waypoint,
+        route,
+        mining,
+        Some(ShipReport::Surveyed(surveys)),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipMiningParams {
+    ship_symbol: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_mining_start(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipMiningParams>,
+) -> Result<Markup, AppError> {
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
+        &state.conf,
+        ShipOrShipSymbol::Symbol(params.ship_symbol.clone()),
+        &state.store,
+    )
+    .await?;
+    if !spacetraders::can_mine(&ship) {
+        return Err(AppError::Invalid(format!(
+            "{} has no mining laser",
+            ship.symbol
+        )));
+    }
+    if ship.nav.status == ShipNavStatus::InTransit || !spacetraders::minable(&waypoint.waypoint) {
+        return Err(AppError::Invalid(format!(
+            "{} has to be at an asteroid to mine",
+            ship.symbol
+        )));
+    }
+
+    state
+        .miner
+        .start(&ship.symbol, &ship.nav.waypoint_symbol, MiningRole::Miner)
+        .await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_mining_haul(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipMiningParams>,
+) -> Result<Markup, AppError> {
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
+        &state.conf,
+        ShipOrShipSymbol::Symbol(params.ship_symbol.clone()),
+        &state.store,
+    )
+    .await?;
+    if ship.cargo.capacity == 0 {
+        return Err(AppError::Invalid(format!(
+            "{} has no cargo hold",
+            ship.symbol
+        )));
+    }
+    if ship.nav.status == ShipNavStatus::InTransit || !spacetraders::minable(&waypoint.waypoint) {
+        return Err(AppError::Invalid(format!(
+            "{} has to be at an asteroid to haul for its miners",
+            ship.symbol
+        )));
+    }
+
+    state
+        .miner
+        .start(&ship.symbol, &ship.nav.waypoint_symbol, MiningRole::Hauler)
+        .await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipMiningRefineForm {
+    refine: bool,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_mining_refine(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipMiningParams>,
+    Form(form): Form<ShipMiningRefineForm>,
+) -> Result<Markup, AppError> {
+    state
+        .miner
+        .set_refine(&params.ship_symbol, form.refine)
+        .await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_mining_stop(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipMiningParams>,
+) -> Result<Markup, AppError> {
+    state.miner.cancel(&params.ship_symbol).await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipRefineChoicesParams {
+    ship_symbol: String,
+}
+#[debug_handler]
+pub async fn ship_refine_choices(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipRefineChoicesParams>,
+) -> Result<Markup, AppError> {
+    let ship = spacetraders::get_ship(&state.conf, &params.ship_symbol).await?;
+    let options = spacetraders::refine_options(&ship, &state.store).await?;
+
+    Ok(page(
+        app.agent_switcher(&state),
+        fragments::ship_refine_html(&ship, options),
+        None,
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipRefineParams {
+    ship_symbol: String,
+    produce: Produce,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_refine(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipRefineParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let conf = &state.conf;
+    let refined = spacetraders::ship_refine(conf, &params.ship_symbol, params.produce).await?;
+
+    let symbol = ShipOrShipSymbol::Symbol(params.ship_symbol);
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(conf, symbol, &state.store).await?;
+
+    // So the card hears when the refinery's ready again
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(
+        ship,
+        waypoint,
+        route,
+        mining,
+        Some(ShipReport::Refined(refined)),
+    ))
 }
 
 #[derive(Deserialize, Debug)]
@@ -311,16 +716,333 @@ pub struct ShipCargoDumpParams {
 }
 #[debug_handler]
 pub async fn ship_cargo_dump(
-    State(state): State<AppStateShared>,
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
     Path(params): Path<ShipCargoDumpParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let conf = &state.conf;
 
     let symbol = ShipOrShipSymbol::Symbol(params.ship_symbol);
-    spacetraders::ship_cargo_dump(conf, symbol.clone()).await;
+    let sale =
+        spacetraders::ship_cargo_dump(conf, symbol.clone(), &state.store, app.config.sell_floor)
+            .await?;
 
-    let (ship, waypoint) =
-        spacetraders::get_ship_with_waypoint(conf, symbol, &state.waypoints_cache).await;
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(conf, symbol, &state.store).await?;
+
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+
+    Ok(fragments::ship_html(
+        ship,
+        waypoint,
+        route,
+        mining,
+        Some(ShipReport::Sold(sale)),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipCargoItemParams {
+    ship_symbol: String,
+    trade_symbol: TradeSymbol,
+}
+#[debug_handler]
+pub async fn ship_cargo_item(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipCargoItemParams>,
+) -> Result<Markup, AppError> {
+    let conf = &state.conf;
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
+        conf,
+        ShipOrShipSymbol::Symbol(params.ship_symbol),
+        &state.store,
+    )
+    .await?;
+    let ships = spacetraders::get_my_ships(conf).await?;
+    let targets = spacetraders::transfer_targets(&ship, &ships);
+
+    // Only worth offering to sell if the market here buys it
+    let market = waypoint.market.filter(|m| {
+        m.imports
+            .iter()
+            .chain(&m.exchange)
+            .any(|g| g.symbol == params.trade_symbol)
+    });
+    let trade_good = market
+        .as_ref()
+        .and_then(|m| m.trade_goods.as_ref())
+        .and_then(|t| t.iter().find(|t| t.symbol == params.trade_symbol));
+
+    Ok(page(
+        app.agent_switcher(&state),
+        fragments::ship_cargo_item_html(&ship, params.trade_symbol, trade_good, targets),
+        None,
+    ))
+}
+
+/// The ship's card as it is now, after one of the cargo or mining forms.
+async fn ship_card(state: &AgentState, ship_symbol: String) -> Result<Markup, AppError> {
+    let (ship, waypoint) = spacetraders::get_ship_with_waypoint(
+        &state.conf,
+        ShipOrShipSymbol::Symbol(ship_symbol),
+        &state.store,
+    )
+    .await?;
+    state.fleet.track(&ship);
+    let route = state.store.ship_route(&ship.symbol).await?;
+    let mining = state.store.mining_job(&ship.symbol).await?;
+    Ok(fragments::ship_html(ship, waypoint, route, mining, None))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipCargoParams {
+    ship_symbol: String,
+}
+#[derive(Deserialize, Debug)]
+pub struct ShipCargoForm {
+    trade_symbol: TradeSymbol,
+    units: i32,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_cargo_sell(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipCargoParams>,
+    Form(form): Form<ShipCargoForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::market_sell(
+        &state.conf,
+        &params.ship_symbol,
+        form.trade_symbol,
+        form.units,
+        &state.store,
+    )
+    .await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_cargo_jettison(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipCargoParams>,
+    Form(form): Form<ShipCargoForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::ship_jettison(
+        &state.conf,
+        &params.ship_symbol,
+        form.trade_symbol,
+        form.units,
+    )
+    .await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipCargoTransferForm {
+    trade_symbol: TradeSymbol,
+    units: i32,
+    to_ship_symbol: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_cargo_transfer(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipCargoParams>,
+    Form(form): Form<ShipCargoTransferForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::ship_transfer(
+        &state.conf,
+        &params.ship_symbol,
+        &form.to_ship_symbol,
+        form.trade_symbol,
+        form.units,
+    )
+    .await?;
+    // The other ship's card is on the page too
+    state.fleet.refresh(&form.to_ship_symbol).await?;
+    ship_card(&state, params.ship_symbol).await
+}
+
+/// The contracts section again, after one of its buttons has changed it.
+async fn contracts_html(state: &AgentState) -> Result<Markup, AppError> {
+    let conf = &state.conf;
+    let contracts = spacetraders::get_my_contracts(conf).await?;
+    let ships = spacetraders::get_my_ships(conf).await?;
+    let ships_at_factions = spacetraders::ships_at_factions(conf, &ships, &state.store).await?;
+    Ok(fragments::contracts_html(
+        contracts,
+        &ships,
+        &ships_at_factions,
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContractParams {
+    contract_id: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn contract_accept(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ContractParams>,
+) -> Result<Markup, AppError> {
+    spacetraders::accept_contract(&state.conf, &params.contract_id).await?;
+    contracts_html(&state).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContractDeliverForm {
+    ship_symbol: String,
+    trade_symbol: TradeSymbol,
+    units: i32,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn contract_deliver(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ContractParams>,
+    Form(form): Form<ContractDeliverForm>,
+) -> Result<Markup, AppError> {
+    spacetraders::deliver_contract(
+        &state.conf,
+        &params.contract_id,
+        form.ship_symbol.clone(),
+        form.trade_symbol,
+        form.units,
+    )
+    .await?;
+    // The ship's card still shows the cargo it's just handed over
+    state.fleet.refresh(&form.ship_symbol).await?;
+    contracts_html(&state).await
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn contract_fulfill(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ContractParams>,
+) -> Result<Markup, AppError> {
+    spacetraders::fulfill_contract(&state.conf, &params.contract_id).await?;
+    contracts_html(&state).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShipContractNegotiateParams {
+    ship_symbol: String,
+}
+#[debug_handler(state = AppStateShared)]
+pub async fn ship_contract_negotiate(
+    CurrentAgent(state): CurrentAgent,
+    Path(params): Path<ShipContractNegotiateParams>,
+) -> Result<Markup, AppError> {
+    spacetraders::negotiate_contract(&state.conf, &params.ship_symbol).await?;
+    contracts_html(&state).await
+}
+
+/**
+ * Ship cards as they change, for the fleet on the index page to swap in.
+ * Each event is a whole `fragments::ship_html`, or a `resync` if we fell
+ * behind and dropped some.
+ */
+#[debug_handler(state = AppStateShared)]
+pub async fn fleet_events(
+    CurrentAgent(state): CurrentAgent,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let updates = state.fleet.subscribe();
+    let events = futures::stream::unfold(updates, |mut updates| async move {
+        let event = match updates.recv().await {
+            Ok(html) => Event::default().event("ship").data(html),
+            // Some cards are gone for good, so the page has to fetch the
+            // whole fleet again
+            Err(RecvError::Lagged(_)) => Event::default().event("resync").data(""),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), updates))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AgentSelectParams {
+    name: String,
+}
+#[debug_handler]
+pub async fn agent_select(
+    State(app): State<AppStateShared>,
+    Path(params): Path<AgentSelectParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(agent) = app.agent(&params.name) else {
+        return Err(AppError::Invalid(format!(
+            "There's no agent called {}",
+            params.name
+        )));
+    };
+
+    let cookie = format!(
+        "{AGENT_COOKIE}={}; Path=/; SameSite=Lax; Max-Age=31536000",
+        agent.name
+    );
+    Ok(([("set-cookie", cookie)], Redirect::to("/")).into_response())
+}
+
+#[debug_handler]
+pub async fn register_form(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+) -> Result<Markup, AppError> {
+    // Our token's probably the reason we're here, so don't send it
+    let mut conf = state.conf.clone();
+    conf.bearer_access_token = None;
+    let factions: Vec<_> = pagination::all_factions(&conf).try_collect().await?;
+
+    Ok(page(
+        app.agent_switcher(&state),
+        html! {
+            header class="text-lg font-semibold" {"Register " (state.name)}
+            (fragments::register_html(&state.name, factions))
+        },
+        None,
+    ))
+}
 
-    Ok(fragments::ship_html(ship, waypoint, None))
+#[derive(Deserialize, Debug)]
+pub struct RegisterParams {
+    symbol: String,
+    faction: FactionSymbol,
+    email: String,
+}
+#[debug_handler]
+pub async fn register(
+    State(app): State<AppStateShared>,
+    CurrentAgent(state): CurrentAgent,
+    Form(params): Form<RegisterParams>,
+) -> Result<Markup, AppError> {
+    let email = Some(params.email).filter(|e| !e.is_empty());
+    let token = reset::register(&app, &state, params.symbol.clone(), params.faction, email).await?;
+
+    Ok(page(
+        app.agent_switcher(&state),
+        html! {
+            header class="text-lg font-semibold" {"Register " (state.name)}
+            (fragments::registered_html(&params.symbol, &token))
+        },
+        None,
+    ))
+}
+
+/// The key the browser needs to subscribe with, if we can send pushes at all.
+#[debug_handler]
+pub async fn web_push_public_key(State(app): State<AppStateShared>) -> Result<String, AppError> {
+    match &app.web_push {
+        Some(web_push) => Ok(web_push.public_key()),
+        None => Err(AppError::Invalid(
+            "Web push isn't configured, see the [web_push] section of config.toml".to_string(),
+        )),
+    }
+}
+
+#[debug_handler(state = AppStateShared)]
+pub async fn web_push_subscribe(
+    CurrentAgent(state): CurrentAgent,
+    Json(subscription): Json<SubscriptionInfo>,
+) -> Result<impl IntoResponse, AppError> {
+    state.store.put_web_push_subscription(&subscription).await?;
+    Ok(StatusCode::CREATED)
 }
@@ -1,14 +1,26 @@
 use spacedust::apis::agents_api::get_my_agent;
+use spacedust::apis::api_error::ApiErrorKind;
 use spacedust::apis::configuration::Configuration;
-use spacedust::apis::{contracts_api, fleet_api, systems_api};
+use spacedust::apis::{contracts_api, fleet_api, pagination, systems_api};
+use spacedust::models::ship_refine_request::Produce;
 use spacedust::models::{
-    Agent, Contract, ExtractResourcesRequest, ExtractionYield, Market, PurchaseShipRequest,
-    RefuelShipRequest, SellCargoRequest, Ship, ShipType, Shipyard, TradeSymbol, Waypoint,
-    WaypointTraitSymbol,
+    ship_module, ship_mount, Agent, Contract, DeliverContractRequest, ExtractResourcesRequest,
+    ExtractionYield, JettisonRequest, JumpGate, Market, MarketTradeGood, PurchaseCargoRequest,
+    PurchaseShipRequest, RefuelShipRequest, SellCargoRequest, Ship, ShipNavStatus,
+    ShipRefine201ResponseData, ShipRefineRequest, ShipType, Shipyard, Survey, System, TradeSymbol,
+    TransferCargoRequest, Waypoint, WaypointTraitSymbol, WaypointType,
 };
 
+use std::collections::HashSet;
+
+use futures::TryStreamExt;
 use serde_json::{json, Value as JsonValue};
 
+use crate::error::{AppError, AppResult};
+use crate::nav::{self, RoutePlan};
+use crate::store::Store;
+use crate::trade::{self, CargoSale, RefineOption, TradeRoute};
+
 #[derive(Debug, Clone)]
 pub enum ShipOrShipSymbol {
     Ship(Ship),
@@ -16,9 +28,9 @@ pub enum ShipOrShipSymbol {
 }
 
 impl ShipOrShipSymbol {
-    pub async fn get(&self, conf: &Configuration) -> Ship {
+    pub async fn get(&self, conf: &Configuration) -> AppResult<Ship> {
         match self {
-            ShipOrShipSymbol::Ship(ship) => ship.clone(),
+            ShipOrShipSymbol::Ship(ship) => Ok(ship.clone()),
             ShipOrShipSymbol::Symbol(ship_symbol) => get_ship(conf, ship_symbol.as_str()).await,
         }
     }
@@ -44,140 +56,286 @@ pub struct ShipWaypoint {
     pub market: Option<Market>,
 }
 
-pub async fn agent(conf: &Configuration) -> Agent {
-    let agent = get_my_agent(conf).await.expect("Idunno, a test error?");
-    *agent.data
+pub async fn agent(conf: &Configuration) -> AppResult<Agent> {
+    Ok(*get_my_agent(conf).await?.data)
 }
 
-pub async fn get_my_contracts(conf: &Configuration) -> Vec<Contract> {
-    contracts_api::get_contracts(conf, None, None)
-        .await
-        .expect("Idunno, a test error?")
+pub async fn get_my_contracts(conf: &Configuration) -> AppResult<Vec<Contract>> {
+    Ok(pagination::all_contracts(conf).try_collect().await?)
+}
+
+pub async fn accept_contract(conf: &Configuration, contract_id: &str) -> AppResult<Contract> {
+    Ok(*contracts_api::accept_contract(conf, contract_id)
+        .await?
+        .data
+        .contract)
+}
+
+pub async fn deliver_contract(
+    conf: &Configuration,
+    contract_id: &str,
+    ship_symbol: String,
+    trade_symbol: TradeSymbol,
+    units: i32,
+) -> AppResult<Contract> {
+    let request = DeliverContractRequest::new(ship_symbol, trade_symbol.to_string(), units);
+    Ok(
+        *contracts_api::deliver_contract(conf, contract_id, Some(request))
+            .await?
+            .data
+            .contract,
+    )
+}
+
+pub async fn fulfill_contract(conf: &Configuration, contract_id: &str) -> AppResult<Contract> {
+    Ok(*contracts_api::fulfill_contract(conf, contract_id)
+        .await?
+        .data
+        .contract)
+}
+
+pub async fn negotiate_contract(conf: &Configuration, ship_symbol: &str) -> AppResult<Contract> {
+    Ok(*fleet_api::negotiate_contract(conf, ship_symbol)
+        .await?
         .data
+        .contract)
 }
 
-pub async fn agent_system(conf: &Configuration) -> String {
-    let agent = agent(conf).await;
-    agent
+/// Ships that are somewhere a faction could give them a contract.
+pub async fn ships_at_factions(
+    conf: &Configuration,
+    ships: &[Ship],
+    store: &Store,
+) -> AppResult<HashSet<String>> {
+    let mut at_factions = HashSet::new();
+    for ship in ships
+        .iter()
+        .filter(|s| s.nav.status != ShipNavStatus::InTransit)
+    {
+        let waypoints = system_waypoints(conf, ship.nav.system_symbol.clone(), store).await?;
+        let waypoint = find_waypoint(&waypoints, &ship.nav.waypoint_symbol)?;
+        if waypoint.faction.is_some() {
+            at_factions.insert(ship.symbol.clone());
+        }
+    }
+    Ok(at_factions)
+}
+
+pub async fn agent_system(conf: &Configuration) -> AppResult<String> {
+    let agent = agent(conf).await?;
+    Ok(agent
         .headquarters
         .split('-')
         .take(2)
         .collect::<Vec<&str>>()
-        .join("-")
+        .join("-"))
 }
 
 pub async fn system_waypoints(
     conf: &Configuration,
     system_symbol: String,
-    waypoints_cache: crate::WaypointsCache,
-) -> Vec<Waypoint> {
-    let waypoints = waypoints_cache
-        .try_get_with::<_, ()>(system_symbol.clone(), async {
-            let mut waypoints: Vec<Waypoint> = vec![];
-            // API is 1-indexed
-            let mut page = 1;
-            loop {
-                let response = systems_api::get_system_waypoints(
-                    conf,
-                    system_symbol.as_str(),
-                    Some(page),
-                    Some(20),
-                )
-                .await
-                .unwrap();
-                waypoints.extend(response.data);
-                if response.meta.total <= waypoints.len() as i32 {
-                    break;
-                }
-                page += 1;
-            }
-            waypoints.sort_by_key(|w| w.r#type);
-            Ok(waypoints)
-        })
-        .await
-        .unwrap();
+    store: &Store,
+) -> AppResult<Vec<Waypoint>> {
+    let cached = store
+        .system_waypoints(&system_symbol)
+        .await?
+        .filter(|record| record.fresh(store.ttls.waypoints));
+    let mut waypoints = match cached {
+        Some(record) => record.data,
+        None => {
+            let waypoints: Vec<Waypoint> =
+                pagination::all_system_waypoints(conf, system_symbol.as_str(), None, None)
+                    .try_collect()
+                    .await?;
+            store
+                .put_system_waypoints(&system_symbol, &waypoints)
+                .await?;
+            waypoints
+        }
+    };
 
-    // Not strictly necessary, but cache metadata is kinda eventually consistent
-    // unless we call this, and it goofs up my ability to debug stuff if I can't
-    // trust e.g., how many entries are in the cache.
-    waypoints_cache.run_pending_tasks().await;
+    waypoints.sort_by_key(|w| w.r#type);
+    Ok(waypoints)
+}
 
-    waypoints
+pub async fn get_system(
+    conf: &Configuration,
+    system_symbol: &str,
+    store: &Store,
+) -> AppResult<System> {
+    if let Some(record) = store.system(system_symbol).await? {
+        return Ok(record.data);
+    }
+
+    let system = *systems_api::get_system(conf, system_symbol).await?.data;
+    store.put_system(&system).await?;
+    Ok(system)
+}
+
+/**
+ * Jump gate connections never change within a reset, so once we've seen them
+ * we never ask again. Uncharted gates, and waypoints that aren't gates at
+ * all, won't tell us anything, hence the Option. Anything else going wrong
+ * is an error, so we ask again next time.
+ */
+pub async fn get_jump_gate(
+    conf: &Configuration,
+    waypoint: &Waypoint,
+    store: &Store,
+) -> AppResult<Option<JumpGate>> {
+    if let Some(record) = store.jump_gate(&waypoint.symbol).await? {
+        return Ok(Some(record.data));
+    }
+
+    let response =
+        match systems_api::get_jump_gate(conf, &waypoint.system_symbol, &waypoint.symbol).await {
+            Ok(response) => response,
+            Err(err) if err.api_error_kind() == Some(ApiErrorKind::WaypointNoAccess) => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+    store
+        .put_jump_gate(&waypoint.symbol, &response.data)
+        .await?;
+    Ok(Some(*response.data))
+}
+
+/// Fetches a market's current state and records it as the latest snapshot.
+pub async fn get_market(
+    conf: &Configuration,
+    system_symbol: &str,
+    waypoint_symbol: &str,
+    store: &Store,
+) -> AppResult<Market> {
+    let market = *systems_api::get_market(conf, system_symbol, waypoint_symbol)
+        .await?
+        .data;
+    store.put_market(&market).await?;
+    Ok(market)
 }
 
 pub async fn get_shipyard(
     conf: &Configuration,
     system_symbol: &str,
     waypoint_symbol: &str,
-) -> Shipyard {
-    let response = systems_api::get_shipyard(conf, system_symbol, waypoint_symbol)
-        .await
-        .unwrap();
-    *response.data
+    store: &Store,
+) -> AppResult<Shipyard> {
+    // Prices move, but not so fast that we need to ask every time someone
+    // opens the page.
+    if let Some(record) = store.shipyard(waypoint_symbol).await? {
+        if record.fresh(Some(store.ttls.shipyard)) {
+            return Ok(record.data);
+        }
+    }
+
+    let response = systems_api::get_shipyard(conf, system_symbol, waypoint_symbol).await?;
+    store.put_shipyard(&response.data).await?;
+    Ok(*response.data)
 }
 
-pub async fn ship_buy(conf: &Configuration, ship_type: ShipType, waypoint: String) {
-    fleet_api::purchase_ship(conf, Some(PurchaseShipRequest::new(ship_type, waypoint)))
-        .await
-        .unwrap();
+/// The most profitable trades `ship` could run between the markets we've
+/// seen prices for in its system.
+pub async fn best_trades(
+    ship: &Ship,
+    waypoints: &[Waypoint],
+    store: &Store,
+) -> AppResult<Vec<TradeRoute>> {
+    let markets = store.system_markets(&ship.nav.system_symbol).await?;
+    Ok(trade::best_trades(ship.into(), waypoints, &markets))
 }
 
-pub async fn get_my_ships(conf: &Configuration) -> Vec<Ship> {
-    let response = fleet_api::get_my_ships(conf, None, None).await.unwrap();
-    response.data
+pub async fn ship_buy(
+    conf: &Configuration,
+    ship_type: ShipType,
+    waypoint: String,
+) -> AppResult<()> {
+    fleet_api::purchase_ship(conf, Some(PurchaseShipRequest::new(ship_type, waypoint))).await?;
+    Ok(())
 }
 
-pub async fn get_ship_nav_choices(ship: &Ship, waypoints: Vec<Waypoint>) -> Vec<(Waypoint, f64)> {
-    let ship_waypoint = &ship.nav.waypoint_symbol;
-    let ship_location = waypoints
-        .iter()
-        .find(|w| w.symbol == *ship_waypoint)
-        .expect("Ship not at waypoint")
-        .clone();
+pub async fn get_my_ships(conf: &Configuration) -> AppResult<Vec<Ship>> {
+    Ok(pagination::all_my_ships(conf).try_collect().await?)
+}
+
+/**
+ * A market's exports, imports and exchange lists don't change within a reset,
+ * so any snapshot we've already got will do for working out what it trades,
+ * unless it's older than the configured TTL.
+ */
+pub async fn get_market_listing(
+    conf: &Configuration,
+    waypoint: &Waypoint,
+    store: &Store,
+) -> AppResult<Market> {
+    if let Some(record) = store.market(&waypoint.symbol).await? {
+        if record.fresh(store.ttls.market_listing) {
+            return Ok(record.data);
+        }
+    }
+    get_market(conf, &waypoint.system_symbol, &waypoint.symbol, store).await
+}
+
+pub async fn fuel_stations(
+    conf: &Configuration,
+    waypoints: &[Waypoint],
+    store: &Store,
+) -> AppResult<HashSet<String>> {
+    let mut markets = vec![];
+    for waypoint in waypoints.iter().filter(|w| {
+        w.traits
+            .iter()
+            .any(|t| t.symbol == WaypointTraitSymbol::Marketplace)
+    }) {
+        markets.push(get_market_listing(conf, waypoint, store).await?);
+    }
+    Ok(nav::fuel_stations(&markets))
+}
+
+pub async fn get_ship_nav_choices(
+    ship: &Ship,
+    waypoints: Vec<Waypoint>,
+    fuel_stations: &HashSet<String>,
+) -> AppResult<Vec<(Waypoint, f64, Option<RoutePlan>)>> {
+    let mut plans = nav::plan_routes(ship.into(), &waypoints, fuel_stations);
+
+    let ship_location = find_waypoint(&waypoints, &ship.nav.waypoint_symbol)?.clone();
 
     let mut distances = waypoints
         .into_iter()
         .map(|w| {
-            let dist =
-                (((w.x - ship_location.x).pow(2) + (w.y - ship_location.y).pow(2)) as f64).sqrt();
-            (w, dist)
+            let dist = nav::distance(&ship_location, &w);
+            let plan = plans.remove(&w.symbol);
+            (w, dist, plan)
         })
-        .collect::<Vec<(Waypoint, f64)>>();
+        .collect::<Vec<(Waypoint, f64, Option<RoutePlan>)>>();
 
-    distances.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-    distances
+    distances.sort_by(|(_, a, _), (_, b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(distances)
 }
 
-pub async fn get_ship(conf: &Configuration, ship_symbol: &str) -> Ship {
-    *fleet_api::get_my_ship(conf, ship_symbol)
-        .await
-        .unwrap()
-        .data
+/// Ships should only ever be at waypoints we know about, but our cache of
+/// them can be out of date.
+fn find_waypoint<'a>(waypoints: &'a [Waypoint], symbol: &str) -> AppResult<&'a Waypoint> {
+    waypoints
+        .iter()
+        .find(|w| w.symbol == symbol)
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Don't know of a waypoint {symbol}")))
+}
+
+pub async fn get_ship(conf: &Configuration, ship_symbol: &str) -> AppResult<Ship> {
+    Ok(*fleet_api::get_my_ship(conf, ship_symbol).await?.data)
 }
 
 pub async fn get_ship_with_waypoint(
     conf: &Configuration,
     ship: ShipOrShipSymbol,
-    waypoints_cache: &crate::WaypointsCache,
-) -> (Ship, ShipWaypoint) {
-    let ship = ship.get(conf).await;
-
-    println!(
-        "Waypoint symbol: {}, cache entry count: {}",
-        ship.nav.system_symbol,
-        waypoints_cache.entry_count()
-    );
-    let waypoints = system_waypoints(
-        conf,
-        ship.nav.system_symbol.clone(),
-        waypoints_cache.clone(),
-    )
-    .await;
-    let waypoint = waypoints
-        .iter()
-        .find(|w| w.symbol == ship.nav.waypoint_symbol)
-        .unwrap()
-        .clone();
+    store: &Store,
+) -> AppResult<(Ship, ShipWaypoint)> {
+    let ship = ship.get(conf).await?;
+
+    let waypoints = system_waypoints(conf, ship.nav.system_symbol.clone(), store).await?;
+    let waypoint = find_waypoint(&waypoints, &ship.nav.waypoint_symbol)?.clone();
 
     let mut waypoint_features: Vec<WaypointFeatures> = vec![];
     waypoint
@@ -195,10 +353,7 @@ pub async fn get_ship_with_waypoint(
 
     let mut market: Option<Market> = None;
     if waypoint_features.contains(&WaypointFeatures::Marketplace) {
-        let market_ = *systems_api::get_market(conf, &waypoint.system_symbol, &waypoint.symbol)
-            .await
-            .unwrap()
-            .data;
+        let market_ = get_market(conf, &waypoint.system_symbol, &waypoint.symbol, store).await?;
 
         if market_
             .exchange
@@ -211,67 +366,457 @@ pub async fn get_ship_with_waypoint(
         market = Some(market_);
     }
 
-    (
+    Ok((
         ship,
         ShipWaypoint {
             waypoint,
             features: waypoint_features,
             market,
         },
+    ))
+}
+
+/// What the market where `ship` is will trade `trade_symbol` for right now.
+async fn market_trade_good(
+    conf: &Configuration,
+    ship: &Ship,
+    trade_symbol: TradeSymbol,
+    store: &Store,
+) -> AppResult<MarketTradeGood> {
+    if ship.nav.status == ShipNavStatus::InTransit {
+        return Err(AppError::Invalid(format!(
+            "{} is still on its way to {}",
+            ship.symbol, ship.nav.waypoint_symbol
+        )));
+    }
+    let market = get_market(
+        conf,
+        &ship.nav.system_symbol,
+        &ship.nav.waypoint_symbol,
+        store,
     )
+    .await?;
+    market
+        .trade_goods
+        .unwrap_or_default()
+        .into_iter()
+        .find(|t| t.symbol == trade_symbol)
+        .ok_or_else(|| {
+            AppError::Invalid(format!(
+                "There's no market for {} at {}",
+                trade_symbol.to_string(),
+                ship.nav.waypoint_symbol
+            ))
+        })
 }
 
-pub async fn ship_refuel(conf: &Configuration, symbol: ShipOrShipSymbol) -> Ship {
-    fleet_api::refuel_ship(conf, &symbol.symbol(), Some(RefuelShipRequest::new()))
-        .await
-        .unwrap();
+/// Markets only deal in so many units at once.
+fn check_trade_volume(units: i32, trade_good: &MarketTradeGood) -> AppResult<()> {
+    if units <= 0 {
+        return Err(AppError::Invalid("Pick at least one unit".to_string()));
+    }
+    if units > trade_good.trade_volume {
+        return Err(AppError::Invalid(format!(
+            "The market only trades {} {} at a time",
+            trade_good.trade_volume,
+            trade_good.symbol.to_string()
+        )));
+    }
+    Ok(())
+}
 
-    symbol.get(conf).await
+/**
+ * Buys cargo for the ship where it is. We check it'll fit and that we can
+ * afford it first, since the game's own answer to a typo in the quantity is
+ * much less helpful.
+ */
+pub async fn market_buy(
+    conf: &Configuration,
+    ship_symbol: &str,
+    trade_symbol: TradeSymbol,
+    units: i32,
+    store: &Store,
+) -> AppResult<()> {
+    let ship = get_ship(conf, ship_symbol).await?;
+    let trade_good = market_trade_good(conf, &ship, trade_symbol, store).await?;
+    check_trade_volume(units, &trade_good)?;
+
+    let space = ship.cargo.capacity - ship.cargo.units;
+    if units > space {
+        return Err(AppError::Invalid(format!(
+            "{ship_symbol} only has room for {space} more units"
+        )));
+    }
+    let credits = agent(conf).await?.credits;
+    let cost = i64::from(units) * i64::from(trade_good.purchase_price);
+    if cost > credits {
+        return Err(AppError::Invalid(format!(
+            "That costs {cost}, but there's only {credits} credits to spend"
+        )));
+    }
+
+    let request = PurchaseCargoRequest::new(trade_symbol, units);
+    fleet_api::purchase_cargo(conf, ship_symbol, Some(request)).await?;
+    Ok(())
 }
 
-pub async fn ship_extract(conf: &Configuration, ship: ShipOrShipSymbol) -> ExtractionYield {
-    let result =
-        *fleet_api::extract_resources(conf, &ship.symbol(), Some(ExtractResourcesRequest::new()))
-            .await
-            .unwrap()
-            .data;
+pub async fn market_sell(
+    conf: &Configuration,
+    ship_symbol: &str,
+    trade_symbol: TradeSymbol,
+    units: i32,
+    store: &Store,
+) -> AppResult<()> {
+    let ship = get_ship(conf, ship_symbol).await?;
+    let trade_good = market_trade_good(conf, &ship, trade_symbol, store).await?;
+    check_trade_volume(units, &trade_good)?;
+
+    check_held(&ship, trade_symbol, units)?;
+
+    let request = SellCargoRequest::new(trade_symbol, units);
+    fleet_api::sell_cargo(conf, ship_symbol, Some(request)).await?;
+    Ok(())
+}
+
+pub fn units_held(ship: &Ship, trade_symbol: TradeSymbol) -> i32 {
+    ship.cargo
+        .inventory
+        .iter()
+        .find(|i| i.symbol == trade_symbol)
+        .map_or(0, |i| i.units)
+}
 
-    *result.extraction.r#yield
+fn check_held(ship: &Ship, trade_symbol: TradeSymbol, units: i32) -> AppResult<()> {
+    if units <= 0 {
+        return Err(AppError::Invalid("Pick at least one unit".to_string()));
+    }
+    let held = units_held(ship, trade_symbol);
+    if units > held {
+        return Err(AppError::Invalid(format!(
+            "{} only has {held} {}",
+            ship.symbol,
+            trade_symbol.to_string()
+        )));
+    }
+    Ok(())
 }
 
-pub async fn ship_cargo_dump(conf: &Configuration, ship: ShipOrShipSymbol) {
-    let ship = ship.get(conf).await;
+pub async fn ship_jettison(
+    conf: &Configuration,
+    ship_symbol: &str,
+    trade_symbol: TradeSymbol,
+    units: i32,
+) -> AppResult<()> {
+    let ship = get_ship(conf, ship_symbol).await?;
+    check_held(&ship, trade_symbol, units)?;
+
+    let request = JettisonRequest::new(trade_symbol, units);
+    fleet_api::jettison(conf, ship_symbol, Some(request)).await?;
+    Ok(())
+}
 
-    // Marketplaces only buy certain goods, and API calls will fail if we try to
-    // sell anything else
-    let marketplace =
-        *systems_api::get_market(conf, &ship.nav.system_symbol, &ship.nav.waypoint_symbol)
-            .await
-            .unwrap()
-            .data;
-    let goods_sellable = marketplace
-        .imports
+/**
+ * Our other ships that `ship` could hand cargo over to right now. They have to
+ * be at the same waypoint, and the game won't pass cargo between a docked
+ * ship and one in orbit.
+ */
+pub fn transfer_targets<'a>(ship: &Ship, ships: &'a [Ship]) -> Vec<&'a Ship> {
+    ships
         .iter()
-        .map(|i| i.symbol)
-        .collect::<Vec<TradeSymbol>>();
+        .filter(|s| {
+            s.symbol != ship.symbol
+                && s.nav.status != ShipNavStatus::InTransit
+                && s.nav.status == ship.nav.status
+                && s.nav.waypoint_symbol == ship.nav.waypoint_symbol
+                && s.cargo.units < s.cargo.capacity
+        })
+        .collect()
+}
+
+pub async fn ship_transfer(
+    conf: &Configuration,
+    ship_symbol: &str,
+    to_ship_symbol: &str,
+    trade_symbol: TradeSymbol,
+    units: i32,
+) -> AppResult<()> {
+    let ship = get_ship(conf, ship_symbol).await?;
+    check_held(&ship, trade_symbol, units)?;
+
+    let to_ship = get_ship(conf, to_ship_symbol).await?;
+    if transfer_targets(&ship, std::slice::from_ref(&to_ship)).is_empty() {
+        return Err(AppError::Invalid(format!(
+            "{to_ship_symbol} can't take cargo from {ship_symbol} right now"
+        )));
+    }
+    let space = to_ship.cargo.capacity - to_ship.cargo.units;
+    if units > space {
+        return Err(AppError::Invalid(format!(
+            "{to_ship_symbol} only has room for {space} more units"
+        )));
+    }
+
+    let request = TransferCargoRequest::new(trade_symbol, units, to_ship_symbol.to_string());
+    fleet_api::transfer_cargo(conf, ship_symbol, Some(request)).await?;
+    Ok(())
+}
+
+pub async fn ship_refuel(conf: &Configuration, symbol: ShipOrShipSymbol) -> AppResult<Ship> {
+    fleet_api::refuel_ship(conf, &symbol.symbol(), Some(RefuelShipRequest::new())).await?;
+
+    symbol.get(conf).await
+}
+
+pub fn can_survey(ship: &Ship) -> bool {
+    ship.mounts.iter().any(|m| {
+        matches!(
+            m.symbol,
+            ship_mount::Symbol::SurveyorI
+                | ship_mount::Symbol::SurveyorIi
+                | ship_mount::Symbol::SurveyorIii
+        )
+    })
+}
+
+pub fn can_mine(ship: &Ship) -> bool {
+    ship.mounts.iter().any(|m| {
+        matches!(
+            m.symbol,
+            ship_mount::Symbol::MiningLaserI
+                | ship_mount::Symbol::MiningLaserIi
+                | ship_mount::Symbol::MiningLaserIii
+        )
+    })
+}
+
+/// Whether there's anything at the waypoint for a mining laser.
+pub fn minable(waypoint: &Waypoint) -> bool {
+    matches!(
+        waypoint.r#type,
+        WaypointType::Asteroid | WaypointType::AsteroidField | WaypointType::EngineeredAsteroid
+    )
+}
+
+/// What the ship's refinery modules can make, if it has any.
+pub fn refinery_products(ship: &Ship) -> Vec<Produce> {
+    let mut products = vec![];
+    for module in &ship.modules {
+        match module.symbol {
+            ship_module::Symbol::OreRefineryI => products.extend([
+                Produce::Iron,
+                Produce::Copper,
+                Produce::Silver,
+                Produce::Gold,
+                Produce::Aluminum,
+                Produce::Platinum,
+                Produce::Uranite,
+                Produce::Meritium,
+            ]),
+            ship_module::Symbol::FuelRefineryI => products.push(Produce::Fuel),
+            _ => {}
+        }
+    }
+    products.sort();
+    products.dedup();
+    products
+}
+
+/// What the ship could refine from its cargo, valued at the markets we know
+/// in its system.
+pub async fn refine_options(ship: &Ship, store: &Store) -> AppResult<Vec<RefineOption>> {
+    let markets = store.system_markets(&ship.nav.system_symbol).await?;
+    Ok(trade::refine_options(
+        &ship.cargo.inventory,
+        &refinery_products(ship),
+        &markets,
+    ))
+}
+
+pub async fn ship_refine(
+    conf: &Configuration,
+    ship_symbol: &str,
+    produce: Produce,
+) -> AppResult<ShipRefine201ResponseData> {
+    let ship = get_ship(conf, ship_symbol).await?;
+    let (input, output) = trade::refine_goods(produce);
+    if !refinery_products(&ship).contains(&produce) {
+        return Err(AppError::Invalid(format!(
+            "{ship_symbol} has no refinery for {}",
+            output.to_string()
+        )));
+    }
+    let held = units_held(&ship, input);
+    if held < trade::REFINE_INPUT {
+        return Err(AppError::Invalid(format!(
+            "Refining {} takes {} {}, {ship_symbol} has {held}",
+            output.to_string(),
+            trade::REFINE_INPUT,
+            input.to_string()
+        )));
+    }
+
+    let request = ShipRefineRequest::new(produce);
+    Ok(*fleet_api::ship_refine(conf, ship_symbol, Some(request))
+        .await?
+        .data)
+}
+
+/// Surveys the waypoint the ship's at, and keeps the surveys for extracting
+/// with later.
+pub async fn ship_survey(
+    conf: &Configuration,
+    ship_symbol: &str,
+    store: &Store,
+) -> AppResult<Vec<Survey>> {
+    let surveys = fleet_api::create_survey(conf, ship_symbol)
+        .await?
+        .data
+        .surveys;
+    store.put_surveys(&surveys).await?;
+    Ok(surveys)
+}
+
+/// Surveys with what they're worth at the markets we know in the system.
+pub async fn rank_surveys(
+    system_symbol: &str,
+    surveys: Vec<Survey>,
+    store: &Store,
+) -> AppResult<Vec<(Survey, f64)>> {
+    let markets = store.system_markets(system_symbol).await?;
+    Ok(trade::rank_surveys(surveys, &markets))
+}
+
+/**
+ * Extracts with the best survey we've got for where the ship is, or without
+ * one if there aren't any. Surveys the game won't take anymore are forgotten
+ * and the next best tried instead.
+ */
+pub async fn ship_extract(
+    conf: &Configuration,
+    ship: ShipOrShipSymbol,
+    store: &Store,
+) -> AppResult<ExtractionYield> {
+    let ship = ship.get(conf).await?;
+    let surveys = store.surveys(&ship.nav.waypoint_symbol).await?;
+    let surveys = rank_surveys(&ship.nav.system_symbol, surveys, store).await?;
+
+    for (survey, _) in surveys {
+        let signature = survey.signature.clone();
+        match fleet_api::extract_resources_with_survey(conf, &ship.symbol, Some(survey)).await {
+            Ok(response) => return Ok(*response.data.extraction.r#yield),
+            Err(err) => match err.api_error_kind() {
+                Some(
+                    ApiErrorKind::SurveyExhausted
+                    | ApiErrorKind::SurveyExpired
+                    | ApiErrorKind::SurveyInvalid,
+                ) => {
+                    store.delete_survey(&signature).await?;
+                }
+                _ => return Err(err.into()),
+            },
+        }
+    }
 
-    let inventory = ship.cargo.inventory;
+    let result =
+        *fleet_api::extract_resources(conf, &ship.symbol, Some(ExtractResourcesRequest::new()))
+            .await?
+            .data;
+    Ok(*result.extraction.r#yield)
+}
 
-    for item in inventory {
-        println!("Checking if {:?} is sellable", item.symbol);
-        if !goods_sellable.contains(&item.symbol) {
+/**
+ * Sells everything the market where the ship is will take. Each good goes a
+ * chunk at a time (see `trade::next_sale`), with a fresh look at the price in
+ * between, and we stop selling it once the price has fallen below
+ * `sell_floor` of what the first chunk fetched. A failed sale, or a failed
+ * look at the price, only stops that good, so the rest of the hold still gets
+ * sold.
+ */
+pub async fn ship_cargo_dump(
+    conf: &Configuration,
+    ship: ShipOrShipSymbol,
+    store: &Store,
+    sell_floor: f64,
+) -> AppResult<CargoSale> {
+    let ship = ship.get(conf).await?;
+    let system_symbol = &ship.nav.system_symbol;
+    let waypoint_symbol = &ship.nav.waypoint_symbol;
+
+    let mut market = get_market(conf, system_symbol, waypoint_symbol, store).await?;
+    let mut sale = CargoSale::default();
+
+    for item in ship.cargo.inventory {
+        // Marketplaces only buy certain goods, and API calls will fail if we
+        // try to sell anything else
+        let Some(first) = sellable(&market, item.symbol) else {
             continue;
+        };
+        let floor = (f64::from(first.sell_price) * sell_floor).ceil() as i32;
+
+        let mut units = item.units;
+        while let Some(trade_good) = sellable(&market, item.symbol) {
+            let Some(chunk) = trade::next_sale(units, &trade_good, floor) else {
+                if units > 0 {
+                    sale.kept.push((
+                        item.symbol,
+                        format!("the price fell to {}", trade_good.sell_price),
+                    ));
+                }
+                break;
+            };
+
+            let request = SellCargoRequest::new(item.symbol, chunk);
+            match fleet_api::sell_cargo(conf, &ship.symbol, Some(request)).await {
+                Ok(response) => {
+                    let transaction = *response.data.transaction;
+                    // Selling nothing would have us asking forever
+                    if transaction.units <= 0 {
+                        sale.kept
+                            .push((item.symbol, "the market took none of it".to_string()));
+                        break;
+                    }
+                    units -= transaction.units;
+                    sale.credits += i64::from(transaction.total_price);
+                    sale.transactions.push(transaction);
+                }
+                Err(err) => {
+                    sale.kept.push((item.symbol, AppError::from(err).message()));
+                    break;
+                }
+            }
+
+            if units > 0 {
+                // Without a fresh price we can't tell whether to go on, but
+                // what's sold so far still counts
+                match get_market(conf, system_symbol, waypoint_symbol, store).await {
+                    Ok(fresh) => market = fresh,
+                    Err(err) => {
+                        sale.kept.push((item.symbol, err.message()));
+                        break;
+                    }
+                }
+            }
         }
-        println!("Selling {} units of {:?}", item.units, item.symbol);
+    }
+    Ok(sale)
+}
 
-        fleet_api::sell_cargo(
-            conf,
-            &ship.symbol,
-            Some(SellCargoRequest::new(item.symbol, item.units)),
-        )
-        .await
-        .unwrap();
+/// The market's going rate for a good, if it'll buy it from us.
+fn sellable(market: &Market, trade_symbol: TradeSymbol) -> Option<MarketTradeGood> {
+    let buys = market
+        .imports
+        .iter()
+        .chain(&market.exchange)
+        .any(|g| g.symbol == trade_symbol);
+    if !buys {
+        return None;
     }
+    market
+        .trade_goods
+        .as_ref()?
+        .iter()
+        .find(|t| t.symbol == trade_symbol)
+        .cloned()
 }
 
 pub fn map_data(waypoints: Vec<Waypoint>, ships: Vec<Ship>) -> String {
@@ -304,11 +849,10 @@ pub fn map_data(waypoints: Vec<Waypoint>, ships: Vec<Ship>) -> String {
 
     ships.iter().for_each(|ship| {
         let ship_waypoint_symbol = &ship.nav.waypoint_symbol;
-        let ship_waypoint = waypoints
-            .iter()
-            .find(|w| w.symbol == *ship_waypoint_symbol)
-            .expect("Ship not at waypoint")
-            .clone();
+        // Leave off ships somewhere we don't have on the map
+        let Ok(ship_waypoint) = find_waypoint(&waypoints, ship_waypoint_symbol) else {
+            return;
+        };
 
         nodes.push(json!({
             "data": {
@@ -337,3 +881,86 @@ pub fn map_data(waypoints: Vec<Waypoint>, ships: Vec<Ship>) -> String {
     }))
     .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use spacedust::models::{NavigateShipRequest, PurchaseCargoRequest};
+    use spacetraders_mock::templates;
+
+    use super::*;
+    use crate::test_util;
+
+    #[tokio::test]
+    async fn cargo_dump_keeps_sales_when_the_price_check_fails() {
+        let mock = test_util::mock().await;
+        let conf = &mock.conf;
+        let ship_symbol = "MOCK_AGENT-1";
+
+        // Machinery's made at the headquarters and bought at the asteroid ten
+        // units at a time, so selling 20 takes two goes
+        fleet_api::dock_ship(conf, ship_symbol).await.unwrap();
+        let request = PurchaseCargoRequest::new(TradeSymbol::Machinery, 20);
+        fleet_api::purchase_cargo(conf, ship_symbol, Some(request))
+            .await
+            .unwrap();
+        fleet_api::orbit_ship(conf, ship_symbol).await.unwrap();
+        let request = NavigateShipRequest::new("X1-MOCK-B1".to_string());
+        fleet_api::navigate_ship(conf, ship_symbol, Some(request))
+            .await
+            .unwrap();
+        mock.universe.lock().skip_waits(ship_symbol).unwrap();
+        fleet_api::dock_ship(conf, ship_symbol).await.unwrap();
+
+        // Enough for the first look at the price, but not the one after
+        mock.markets_left.store(1, Ordering::SeqCst);
+        let store = test_util::store();
+        let sale = ship_cargo_dump(
+            conf,
+            ShipOrShipSymbol::Symbol(ship_symbol.to_string()),
+            &store,
+            0.0,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sale.transactions.len(), 1);
+        assert_eq!(sale.transactions[0].units, 10);
+        assert_eq!(sale.credits, i64::from(sale.transactions[0].total_price));
+        assert_eq!(sale.kept.len(), 1);
+        assert_eq!(sale.kept[0].0, TradeSymbol::Machinery);
+    }
+
+    #[tokio::test]
+    async fn jump_gates_only_missing_when_the_game_says_so() {
+        let mock = test_util::mock().await;
+        let conf = &mock.conf;
+        let store = test_util::store();
+        let waypoint = |symbol: &str| {
+            let mut waypoint = test_util::waypoint(symbol, 0);
+            waypoint.system_symbol = templates::SYSTEM.to_string();
+            waypoint
+        };
+
+        let gate = get_jump_gate(conf, &waypoint("X1-MOCK-E1"), &store)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(gate.connections, vec!["X1-MOCK2-G1".to_string()]);
+        assert!(store.jump_gate("X1-MOCK-E1").await.unwrap().is_some());
+
+        let headquarters = waypoint(templates::HEADQUARTERS);
+        assert!(get_jump_gate(conf, &headquarters, &store)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Not hearing back isn't the same as there being no gate
+        let mut unreachable = conf.clone();
+        unreachable.base_path = "http://127.0.0.1:9/v2".to_string();
+        assert!(get_jump_gate(&unreachable, &headquarters, &store)
+            .await
+            .is_err());
+    }
+}
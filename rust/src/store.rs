@@ -0,0 +1,810 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use spacedust::models::{
+    JumpGate, Market, MarketTradeGood, Shipyard, Survey, System, TradeSymbol, Waypoint,
+};
+
+use web_push::SubscriptionInfo;
+
+use crate::config::CacheTtls;
+use crate::executor::ActiveRoute;
+use crate::miner::MiningJob;
+
+/**
+ * Everything we know about the universe that doesn't change between server
+ * resets (and a few things that do, like market prices), kept on disk so we
+ * don't re-download it on every restart.
+ *
+ * Each record remembers when we fetched it, so callers can decide for
+ * themselves whether it's fresh enough, going by the configured `ttls`.
+ */
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+    pub ttls: CacheTtls,
+}
+
+#[derive(Debug, Clone)]
+pub struct Record<T> {
+    pub data: T,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// One sighting of a good's prices at a market.
+#[derive(Debug, Clone)]
+pub struct PriceObservation {
+    pub waypoint_symbol: String,
+    pub trade_good: MarketTradeGood,
+    pub observed_at: DateTime<Utc>,
+}
+
+/**
+ * Ship cards re-fetch their market every few seconds while they're polling,
+ * and most of those fetches see exactly the same prices. Only write a new
+ * row when something changed, or often enough that the chart still shows we
+ * were looking.
+ */
+const PRICE_HISTORY_INTERVAL_MINUTES: i64 = 15;
+
+impl<T> Record<T> {
+    pub fn age(&self) -> chrono::Duration {
+        Utc::now() - self.fetched_at
+    }
+
+    /// Whether the record is younger than `ttl`. No TTL means it's good
+    /// forever.
+    pub fn fresh(&self, ttl: Option<std::time::Duration>) -> bool {
+        ttl.is_none_or(|ttl| self.age().to_std().is_ok_and(|age| age < ttl))
+    }
+}
+
+const MIGRATIONS: &str = "
+    CREATE TABLE IF NOT EXISTS systems (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS waypoints (
+        symbol TEXT PRIMARY KEY,
+        system_symbol TEXT NOT NULL,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS waypoints_system ON waypoints (system_symbol);
+
+    -- A system's waypoints are only usable from the store once we've fetched
+    -- the whole list, otherwise we'd think a half-fetched system was complete.
+    CREATE TABLE IF NOT EXISTS system_waypoints_fetched (
+        system_symbol TEXT PRIMARY KEY,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS jump_gates (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS shipyards (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS markets (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS ship_routes (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    -- Ships mining on their own, and how far through the round trip they are.
+    CREATE TABLE IF NOT EXISTS mining_jobs (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS price_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        waypoint_symbol TEXT NOT NULL,
+        trade_symbol TEXT NOT NULL,
+        data TEXT NOT NULL,
+        observed_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS price_history_trade_symbol ON price_history (trade_symbol, observed_at);
+    CREATE INDEX IF NOT EXISTS price_history_waypoint ON price_history (waypoint_symbol, trade_symbol, observed_at);
+
+    -- Surveys our ships have made, until they expire or the asteroid they're
+    -- for runs dry. Expiry is kept as UTC RFC 3339 to the millisecond, so it
+    -- compares as text.
+    CREATE TABLE IF NOT EXISTS surveys (
+        signature TEXT PRIMARY KEY,
+        waypoint_symbol TEXT NOT NULL,
+        system_symbol TEXT NOT NULL,
+        data TEXT NOT NULL,
+        expires_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS surveys_waypoint ON surveys (waypoint_symbol);
+    CREATE INDEX IF NOT EXISTS surveys_system ON surveys (system_symbol);
+
+    -- Odds and ends about the agent itself, like which reset we last saw and
+    -- the token we got when we registered.
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+
+    -- Browsers that want notifications about this agent. Each is a
+    -- PushSubscription as the browser gave it to us, keyed by its endpoint.
+    -- These outlive resets, since they belong to the browser, not the game.
+    CREATE TABLE IF NOT EXISTS web_push_subscriptions (
+        endpoint TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+";
+
+fn survey_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Everything that stops being true when the server resets.
+const RESET_TABLES: &[&str] = &[
+    "systems",
+    "waypoints",
+    "system_waypoints_fetched",
+    "jump_gates",
+    "shipyards",
+    "markets",
+    "ship_routes",
+    "mining_jobs",
+    "price_history",
+    "surveys",
+];
+
+pub const SETTING_RESET_DATE: &str = "reset_date";
+pub const SETTING_TOKEN: &str = "token";
+
+fn to_json<T: Serialize>(data: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(data).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn conversion_err<E: std::error::Error + Send + Sync + 'static>(e: E) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+}
+
+fn parse_timestamp(timestamp: &str) -> rusqlite::Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(timestamp)
+        .map_err(conversion_err)?
+        .with_timezone(&Utc))
+}
+
+fn from_row<T: DeserializeOwned>(data: String, fetched_at: String) -> rusqlite::Result<Record<T>> {
+    Ok(Record {
+        data: serde_json::from_str(&data).map_err(conversion_err)?,
+        fetched_at: parse_timestamp(&fetched_at)?,
+    })
+}
+
+impl Store {
+    pub fn open(path: &str, ttls: CacheTtls) -> anyhow::Result<Store> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(MIGRATIONS)?;
+        Ok(Store {
+            conn: Arc::new(Mutex::new(conn)),
+            ttls,
+        })
+    }
+
+    /// SQLite calls block, so keep them off the async workers.
+    async fn with_conn<R, F>(&self, f: F) -> anyhow::Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<R> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        Ok(tokio::task::spawn_blocking(move || f(&mut conn.lock())).await??)
+    }
+
+    /// Reads a single record from one of the tables keyed by symbol. Shipyards,
+    /// markets and jump gates are keyed by their waypoint's symbol.
+    async fn get<T>(&self, table: &'static str, key: &str) -> anyhow::Result<Option<Record<T>>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let key = key.to_string();
+        self.with_conn(move |conn| {
+            let row = conn
+                .query_row(
+                    &format!("SELECT data, fetched_at FROM {table} WHERE symbol = ?1"),
+                    params![key],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?;
+            row.map(|(data, fetched_at)| from_row(data, fetched_at))
+                .transpose()
+        })
+        .await
+    }
+
+    async fn put<T: Serialize>(
+        &self,
+        table: &'static str,
+        key: &str,
+        data: &T,
+    ) -> anyhow::Result<()> {
+        let key = key.to_string();
+        let data = to_json(data)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {table} (symbol, data, fetched_at) VALUES (?1, ?2, ?3)"
+                ),
+                params![key, data, Utc::now().to_rfc3339()],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, table: &'static str, key: &str) -> anyhow::Result<()> {
+        let key = key.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!("DELETE FROM {table} WHERE symbol = ?1"),
+                params![key],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn system(&self, symbol: &str) -> anyhow::Result<Option<Record<System>>> {
+        self.get("systems", symbol).await
+    }
+
+    pub async fn put_system(&self, system: &System) -> anyhow::Result<()> {
+        self.put("systems", &system.symbol, system).await
+    }
+
+    /// All of a system's waypoints, or `None` if we've never fetched the
+    /// complete list.
+    pub async fn system_waypoints(
+        &self,
+        system_symbol: &str,
+    ) -> anyhow::Result<Option<Record<Vec<Waypoint>>>> {
+        let system_symbol = system_symbol.to_string();
+        self.with_conn(move |conn| {
+            let fetched_at: Option<String> = conn
+                .query_row(
+                    "SELECT fetched_at FROM system_waypoints_fetched WHERE system_symbol = ?1",
+                    params![system_symbol],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(fetched_at) = fetched_at else {
+                return Ok(None);
+            };
+
+            let mut stmt = conn.prepare(
+                "SELECT data, fetched_at FROM waypoints WHERE system_symbol = ?1 ORDER BY symbol",
+            )?;
+            let waypoints = stmt
+                .query_map(params![system_symbol], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .map(|row| {
+                    let (data, fetched_at) = row?;
+                    Ok(from_row::<Waypoint>(data, fetched_at)?.data)
+                })
+                .collect::<rusqlite::Result<Vec<Waypoint>>>()?;
+
+            Ok(Some(Record {
+                data: waypoints,
+                fetched_at: parse_timestamp(&fetched_at)?,
+            }))
+        })
+        .await
+    }
+
+    pub async fn put_system_waypoints(
+        &self,
+        system_symbol: &str,
+        waypoints: &[Waypoint],
+    ) -> anyhow::Result<()> {
+        let system_symbol = system_symbol.to_string();
+        let rows = waypoints
+            .iter()
+            .map(|w| Ok((w.symbol.clone(), to_json(w)?)))
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+
+        self.with_conn(move |conn| {
+            let now = Utc::now().to_rfc3339();
+            let tx = conn.transaction()?;
+            for (symbol, data) in rows {
+                tx.execute(
+                    "INSERT OR REPLACE INTO waypoints (symbol, system_symbol, data, fetched_at) VALUES (?1, ?2, ?3, ?4)",
+                    params![symbol, system_symbol, data, now],
+                )?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO system_waypoints_fetched (system_symbol, fetched_at) VALUES (?1, ?2)",
+                params![system_symbol, now],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    pub async fn jump_gate(
+        &self,
+        waypoint_symbol: &str,
+    ) -> anyhow::Result<Option<Record<JumpGate>>> {
+        self.get("jump_gates", waypoint_symbol).await
+    }
+
+    pub async fn put_jump_gate(
+        &self,
+        waypoint_symbol: &str,
+        jump_gate: &JumpGate,
+    ) -> anyhow::Result<()> {
+        self.put("jump_gates", waypoint_symbol, jump_gate).await
+    }
+
+    pub async fn shipyard(
+        &self,
+        waypoint_symbol: &str,
+    ) -> anyhow::Result<Option<Record<Shipyard>>> {
+        self.get("shipyards", waypoint_symbol).await
+    }
+
+    pub async fn put_shipyard(&self, shipyard: &Shipyard) -> anyhow::Result<()> {
+        self.put("shipyards", &shipyard.symbol, shipyard).await
+    }
+
+    pub async fn market(&self, waypoint_symbol: &str) -> anyhow::Result<Option<Record<Market>>> {
+        self.get("markets", waypoint_symbol).await
+    }
+
+    /// The latest snapshot of every market we know of in a system.
+    pub async fn system_markets(&self, system_symbol: &str) -> anyhow::Result<Vec<Market>> {
+        let pattern = format!("{system_symbol}-%");
+        self.with_conn(move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT data, fetched_at FROM markets WHERE symbol LIKE ?1")?;
+            let markets = stmt
+                .query_map(params![pattern], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .map(|row| {
+                    let (data, fetched_at) = row?;
+                    Ok(from_row::<Market>(data, fetched_at)?.data)
+                })
+                .collect::<rusqlite::Result<Vec<Market>>>()?;
+            Ok(markets)
+        })
+        .await
+    }
+
+    /**
+     * Prices are only visible while one of our ships is at the market. If
+     * this snapshot was taken from afar, hang on to the last prices we saw
+     * rather than forgetting them.
+     */
+    pub async fn put_market(&self, market: &Market) -> anyhow::Result<()> {
+        let mut market = market.clone();
+        match &market.trade_goods {
+            Some(trade_goods) => {
+                self.record_prices(&market.symbol, trade_goods).await?;
+            }
+            None => {
+                if let Some(previous) = self.market(&market.symbol).await? {
+                    market.trade_goods = previous.data.trade_goods;
+                    market.transactions = previous.data.transactions;
+                }
+            }
+        }
+        self.put("markets", &market.symbol.clone(), &market).await
+    }
+
+    /// Appends the market's current prices to the price history.
+    pub async fn record_prices(
+        &self,
+        waypoint_symbol: &str,
+        trade_goods: &[MarketTradeGood],
+    ) -> anyhow::Result<()> {
+        let waypoint_symbol = waypoint_symbol.to_string();
+        let trade_goods = trade_goods.to_vec();
+
+        self.with_conn(move |conn| {
+            let now = Utc::now();
+            let tx = conn.transaction()?;
+            for trade_good in trade_goods {
+                let trade_symbol = trade_good.symbol.to_string();
+                let previous: Option<(String, String)> = tx
+                    .query_row(
+                        "SELECT data, observed_at FROM price_history
+                        WHERE waypoint_symbol = ?1 AND trade_symbol = ?2
+                        ORDER BY observed_at DESC LIMIT 1",
+                        params![waypoint_symbol, trade_symbol],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+
+                if let Some((data, observed_at)) = previous {
+                    let previous = from_row::<MarketTradeGood>(data, observed_at)?;
+                    if previous.data == trade_good && previous.age() < chrono::Duration::minutes(PRICE_HISTORY_INTERVAL_MINUTES)
+                    {
+                        continue;
+                    }
+                }
+
+                tx.execute(
+                    "INSERT INTO price_history (waypoint_symbol, trade_symbol, data, observed_at) VALUES (?1, ?2, ?3, ?4)",
+                    params![waypoint_symbol, trade_symbol, to_json(&trade_good)?, now.to_rfc3339()],
+                )?;
+            }
+            tx.commit()
+        })
+        .await
+    }
+
+    /// Every recorded price for a good, across all markets, oldest first.
+    pub async fn price_history(
+        &self,
+        trade_symbol: TradeSymbol,
+    ) -> anyhow::Result<Vec<PriceObservation>> {
+        let trade_symbol = trade_symbol.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT waypoint_symbol, data, observed_at FROM price_history
+                WHERE trade_symbol = ?1
+                ORDER BY observed_at",
+            )?;
+            let observations = stmt
+                .query_map(params![trade_symbol], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .map(|row| {
+                    let (waypoint_symbol, data, observed_at) = row?;
+                    let record = from_row::<MarketTradeGood>(data, observed_at)?;
+                    Ok(PriceObservation {
+                        waypoint_symbol,
+                        trade_good: record.data,
+                        observed_at: record.fetched_at,
+                    })
+                })
+                .collect::<rusqlite::Result<Vec<PriceObservation>>>()?;
+            Ok(observations)
+        })
+        .await
+    }
+
+    pub async fn ship_route(&self, ship_symbol: &str) -> anyhow::Result<Option<ActiveRoute>> {
+        Ok(self
+            .get("ship_routes", ship_symbol)
+            .await?
+            .map(|record| record.data))
+    }
+
+    pub async fn ship_routes(&self) -> anyhow::Result<Vec<(String, ActiveRoute)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT symbol, data, fetched_at FROM ship_routes")?;
+            let routes = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .map(|row| {
+                    let (ship_symbol, data, updated_at) = row?;
+                    Ok((ship_symbol, from_row::<ActiveRoute>(data, updated_at)?.data))
+                })
+                .collect::<rusqlite::Result<Vec<(String, ActiveRoute)>>>()?;
+            Ok(routes)
+        })
+        .await
+    }
+
+    pub async fn put_ship_route(
+        &self,
+        ship_symbol: &str,
+        route: &ActiveRoute,
+    ) -> anyhow::Result<()> {
+        self.put("ship_routes", ship_symbol, route).await
+    }
+
+    pub async fn delete_ship_route(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        self.delete("ship_routes", ship_symbol).await
+    }
+
+    pub async fn mining_job(&self, ship_symbol: &str) -> anyhow::Result<Option<MiningJob>> {
+        Ok(self
+            .get("mining_jobs", ship_symbol)
+            .await?
+            .map(|record| record.data))
+    }
+
+    pub async fn mining_jobs(&self) -> anyhow::Result<Vec<(String, MiningJob)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT symbol, data, fetched_at FROM mining_jobs")?;
+            let jobs = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })?
+                .map(|row| {
+                    let (ship_symbol, data, updated_at) = row?;
+                    Ok((ship_symbol, from_row::<MiningJob>(data, updated_at)?.data))
+                })
+                .collect::<rusqlite::Result<Vec<(String, MiningJob)>>>()?;
+            Ok(jobs)
+        })
+        .await
+    }
+
+    pub async fn put_mining_job(&self, ship_symbol: &str, job: &MiningJob) -> anyhow::Result<()> {
+        self.put("mining_jobs", ship_symbol, job).await
+    }
+
+    pub async fn delete_mining_job(&self, ship_symbol: &str) -> anyhow::Result<()> {
+        self.delete("mining_jobs", ship_symbol).await
+    }
+
+    pub async fn setting(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let key = key.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .await
+    }
+
+    pub async fn put_setting(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        let (key, value) = (key.to_string(), value.to_string());
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn put_surveys(&self, surveys: &[Survey]) -> anyhow::Result<()> {
+        let mut rows = vec![];
+        for survey in surveys {
+            let expires_at = parse_timestamp(&survey.expiration)?;
+            let system_symbol = survey.symbol.rsplit_once('-').map_or("", |(s, _)| s);
+            rows.push((
+                survey.signature.clone(),
+                survey.symbol.clone(),
+                system_symbol.to_string(),
+                to_json(survey)?,
+                survey_time(expires_at),
+            ));
+        }
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            for row in rows {
+                tx.execute(
+                    "INSERT OR REPLACE INTO surveys (signature, waypoint_symbol, system_symbol, data, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![row.0, row.1, row.2, row.3, row.4],
+                )?;
+            }
+            tx.commit()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Surveys of the waypoint that are still good.
+    pub async fn surveys(&self, waypoint_symbol: &str) -> anyhow::Result<Vec<Survey>> {
+        self.active_surveys("waypoint_symbol", waypoint_symbol)
+            .await
+    }
+
+    /// Surveys of anywhere in the system that are still good.
+    pub async fn system_surveys(&self, system_symbol: &str) -> anyhow::Result<Vec<Survey>> {
+        self.active_surveys("system_symbol", system_symbol).await
+    }
+
+    async fn active_surveys(
+        &self,
+        column: &'static str,
+        symbol: &str,
+    ) -> anyhow::Result<Vec<Survey>> {
+        let symbol = symbol.to_string();
+        self.with_conn(move |conn| {
+            let now = survey_time(Utc::now());
+            conn.execute("DELETE FROM surveys WHERE expires_at <= ?1", params![now])?;
+            conn.prepare(&format!(
+                "SELECT data FROM surveys WHERE {column} = ?1 ORDER BY expires_at"
+            ))?
+            .query_map(params![symbol], |row| row.get::<_, String>(0))?
+            .map(|data| serde_json::from_str(&data?).map_err(conversion_err))
+            .collect()
+        })
+        .await
+    }
+
+    /// Forgets a survey the game won't take anymore.
+    pub async fn delete_survey(&self, signature: &str) -> anyhow::Result<()> {
+        let signature = signature.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM surveys WHERE signature = ?1",
+                params![signature],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn web_push_subscriptions(&self) -> anyhow::Result<Vec<SubscriptionInfo>> {
+        self.with_conn(|conn| {
+            conn.prepare("SELECT data FROM web_push_subscriptions")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .map(|data| serde_json::from_str(&data?).map_err(conversion_err))
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn put_web_push_subscription(
+        &self,
+        subscription: &SubscriptionInfo,
+    ) -> anyhow::Result<()> {
+        let endpoint = subscription.endpoint.clone();
+        let data = to_json(subscription)?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO web_push_subscriptions (endpoint, data, created_at) VALUES (?1, ?2, ?3)",
+                params![endpoint, data, Utc::now().to_rfc3339()],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_web_push_subscription(&self, endpoint: &str) -> anyhow::Result<()> {
+        let endpoint = endpoint.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM web_push_subscriptions WHERE endpoint = ?1",
+                params![endpoint],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    /**
+     * Forgets everything from before a server reset: the universe, prices,
+     * routes, and the token we registered (which the server has forgotten
+     * too).
+     */
+    pub async fn wipe(&self) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            for table in RESET_TABLES {
+                tx.execute(&format!("DELETE FROM {table}"), [])?;
+            }
+            tx.execute(
+                "DELETE FROM settings WHERE key = ?1",
+                params![SETTING_TOKEN],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{good, market, store, waypoint};
+
+    #[tokio::test]
+    async fn system_waypoints_only_once_the_whole_list_is_in() {
+        let store = store();
+        assert!(store.system_waypoints("X1-TEST").await.unwrap().is_none());
+
+        let waypoints = [waypoint("X1-TEST-B", 10), waypoint("X1-TEST-A", 0)];
+        store
+            .put_system_waypoints("X1-TEST", &waypoints)
+            .await
+            .unwrap();
+        let stored = store.system_waypoints("X1-TEST").await.unwrap().unwrap();
+        let symbols: Vec<_> = stored.data.iter().map(|w| w.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["X1-TEST-A", "X1-TEST-B"]);
+        assert!(stored.fresh(Some(std::time::Duration::from_secs(60))));
+
+        assert!(store.system_waypoints("X1-OTHER").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn jump_gates_round_trip() {
+        let store = store();
+        assert!(store.jump_gate("X1-TEST-G").await.unwrap().is_none());
+
+        let gate = JumpGate::new(vec!["X1-OTHER-G".to_string()]);
+        store.put_jump_gate("X1-TEST-G", &gate).await.unwrap();
+        let stored = store.jump_gate("X1-TEST-G").await.unwrap().unwrap();
+        assert_eq!(stored.data, gate);
+    }
+
+    #[tokio::test]
+    async fn markets_keep_prices_seen_up_close() {
+        let store = store();
+        let iron = good(TradeSymbol::IronOre, 20, 12, 10);
+        store
+            .put_market(&market("X1-TEST-A", vec![iron.clone()], &[]))
+            .await
+            .unwrap();
+
+        // From afar, with no prices
+        let mut distant = market("X1-TEST-A", vec![], &[]);
+        distant.trade_goods = None;
+        store.put_market(&distant).await.unwrap();
+
+        let stored = store.market("X1-TEST-A").await.unwrap().unwrap();
+        assert_eq!(stored.data.trade_goods, Some(vec![iron.clone()]));
+        assert_eq!(store.system_markets("X1-TEST").await.unwrap().len(), 1);
+        assert!(store.system_markets("X1-OTHER").await.unwrap().is_empty());
+
+        // The same prices again aren't worth another row
+        store.record_prices("X1-TEST-A", &[iron]).await.unwrap();
+        let history = store.price_history(TradeSymbol::IronOre).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].waypoint_symbol, "X1-TEST-A");
+    }
+
+    #[tokio::test]
+    async fn wipe_forgets_the_universe_and_the_token() {
+        let store = store();
+        store
+            .put_market(&market("X1-TEST-A", vec![], &[]))
+            .await
+            .unwrap();
+        store.put_setting(SETTING_TOKEN, "token").await.unwrap();
+        store
+            .put_setting(SETTING_RESET_DATE, "2026-10-18")
+            .await
+            .unwrap();
+
+        store.wipe().await.unwrap();
+        assert!(store.market("X1-TEST-A").await.unwrap().is_none());
+        assert!(store.setting(SETTING_TOKEN).await.unwrap().is_none());
+        assert_eq!(
+            store.setting(SETTING_RESET_DATE).await.unwrap().as_deref(),
+            Some("2026-10-18")
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Fixtures shared by the unit tests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use parking_lot::Mutex;
+
+use spacedust::apis::configuration::Configuration;
+use spacedust::models::market_trade_good::Type;
+use spacedust::models::{
+    Market, MarketTradeGood, SupplyLevel, TradeGood, TradeSymbol, Waypoint, WaypointType,
+};
+use spacetraders_mock::universe::Universe;
+use spacetraders_mock::UniverseShared;
+
+use crate::config::CacheTtls;
+use crate::store::Store;
+
+/// A planet at `x` on the x axis of a made-up system.
+pub fn waypoint(symbol: &str, x: i32) -> Waypoint {
+    Waypoint::new(
+        symbol.to_string(),
+        WaypointType::Planet,
+        "X1-TEST".to_string(),
+        x,
+        0,
+        vec![],
+        vec![],
+        false,
+    )
+}
+
+/// An exchange good with middling supply.
+pub fn good(symbol: TradeSymbol, trade_volume: i32, purchase: i32, sell: i32) -> MarketTradeGood {
+    MarketTradeGood::new(
+        symbol,
+        Type::Exchange,
+        trade_volume,
+        SupplyLevel::Moderate,
+        purchase,
+        sell,
+    )
+}
+
+/// A market that trades `goods` at the given prices, and takes `unpriced`
+/// without our having seen what for.
+pub fn market(symbol: &str, goods: Vec<MarketTradeGood>, unpriced: &[TradeSymbol]) -> Market {
+    let trade_good = |symbol: TradeSymbol| TradeGood::new(symbol, String::new(), String::new());
+    let mut market = Market::new(
+        symbol.to_string(),
+        vec![],
+        unpriced.iter().copied().map(trade_good).collect(),
+        goods.iter().map(|g| trade_good(g.symbol)).collect(),
+    );
+    market.trade_goods = Some(goods);
+    market
+}
+
+/// A store that's gone when the test is.
+pub fn store() -> Store {
+    Store::open(":memory:", CacheTtls::default()).unwrap()
+}
+
+/// The mock server, running on a spare port for one test.
+pub struct Mock {
+    /// Logged in as the universe's agent
+    pub conf: Configuration,
+    /// For skipping flights and cooldowns
+    pub universe: UniverseShared,
+    /// How many more market fetches go through before the rest fail
+    pub markets_left: Arc<AtomicUsize>,
+}
+
+pub async fn mock() -> Mock {
+    let universe: UniverseShared = Arc::new(Mutex::new(Universe::default()));
+    let markets_left = Arc::new(AtomicUsize::new(usize::MAX));
+    let app = spacetraders_mock::router(universe.clone()).layer(middleware::from_fn_with_state(
+        markets_left.clone(),
+        market_outage,
+    ));
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let mut conf = Configuration::new();
+    conf.base_path = format!("http://{addr}/v2");
+    conf.bearer_access_token = Some(spacetraders_mock::default_token());
+    Mock {
+        conf,
+        universe,
+        markets_left,
+    }
+}
+
+async fn market_outage(
+    State(markets_left): State<Arc<AtomicUsize>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if request.uri().path().ends_with("/market") {
+        let left =
+            markets_left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if left.is_err() {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+    next.run(request).await
+}
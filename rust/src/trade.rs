@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+
+use spacedust::models::ship_refine_request::Produce;
+use spacedust::models::{
+    Market, MarketTradeGood, MarketTransaction, Ship, ShipCargoItem, ShipNavFlightMode, Survey,
+    TradeSymbol, Waypoint,
+};
+
+use crate::nav;
+
+/**
+ * What we pay per market unit of fuel when we haven't seen a fuel price yet.
+ * A market unit fills 100 units of a ship's tank.
+ */
+const FUEL_PRICE_GUESS: i32 = 72;
+const FUEL_PER_MARKET_UNIT: f64 = 100.0;
+
+/// Buy `units` of a good at one market and sell them at another.
+#[derive(Debug, Clone)]
+pub struct TradeRoute {
+    pub trade_symbol: TradeSymbol,
+    pub buy_at: String,
+    pub sell_at: String,
+    pub units: i32,
+    pub purchase_price: i32,
+    pub sell_price: i32,
+    /// Credits left after paying for the goods and the fuel
+    pub profit: i64,
+    /// Seconds from where the ship is now until the goods are sold
+    pub duration: i64,
+}
+
+impl TradeRoute {
+    pub fn profit_per_hour(&self) -> f64 {
+        self.profit as f64 / (self.duration.max(1) as f64 / 3600.0)
+    }
+}
+
+/// How selling off a ship's cargo went.
+#[derive(Debug, Clone, Default)]
+pub struct CargoSale {
+    pub transactions: Vec<MarketTransaction>,
+    pub credits: i64,
+    /// Goods we didn't sell all of, and why
+    pub kept: Vec<(TradeSymbol, String)>,
+}
+
+/**
+ * How many units to sell in the next transaction, out of `units` we're still
+ * holding. Markets take at most `trade_volume` at a time, and the price drops
+ * as we sell, so we go a chunk at a time and stop once the price is below
+ * `floor`.
+ */
+pub fn next_sale(units: i32, trade_good: &MarketTradeGood, floor: i32) -> Option<i32> {
+    if units <= 0 || trade_good.sell_price < floor {
+        return None;
+    }
+    Some(units.min(trade_good.trade_volume.max(1)))
+}
+
+/// The most any of `markets` pays for each good, as far as we've seen.
+pub fn best_sell_prices(markets: &[Market]) -> HashMap<String, i32> {
+    let mut best: HashMap<String, i32> = HashMap::new();
+    for trade_good in markets
+        .iter()
+        .filter_map(|m| m.trade_goods.as_ref())
+        .flatten()
+    {
+        let price = best.entry(trade_good.symbol.to_string()).or_default();
+        *price = (*price).max(trade_good.sell_price);
+    }
+    best
+}
+
+/**
+ * Roughly what selling `cargo` at `market` would fetch. Goods it buys but we
+ * haven't seen a price for yet count for a credit a unit, so a market that
+ * takes them still beats one that doesn't.
+ */
+pub fn cargo_value(cargo: &[ShipCargoItem], market: &Market) -> i64 {
+    cargo
+        .iter()
+        .filter(|item| {
+            market
+                .imports
+                .iter()
+                .chain(&market.exchange)
+                .any(|g| g.symbol == item.symbol)
+        })
+        .map(|item| {
+            let price = market
+                .trade_goods
+                .iter()
+                .flatten()
+                .find(|t| t.symbol == item.symbol)
+                .map_or(1, |t| t.sell_price);
+            i64::from(item.units) * i64::from(price)
+        })
+        .sum()
+}
+
+/// A refinery turns this many units of ore into `REFINE_OUTPUT` units of
+/// what it's refined into.
+pub const REFINE_INPUT: i32 = 30;
+pub const REFINE_OUTPUT: i32 = 10;
+
+/// What a refinery needs to make `produce`, and the good that comes out.
+pub fn refine_goods(produce: Produce) -> (TradeSymbol, TradeSymbol) {
+    match produce {
+        Produce::Iron => (TradeSymbol::IronOre, TradeSymbol::Iron),
+        Produce::Copper => (TradeSymbol::CopperOre, TradeSymbol::Copper),
+        Produce::Silver => (TradeSymbol::SilverOre, TradeSymbol::Silver),
+        Produce::Gold => (TradeSymbol::GoldOre, TradeSymbol::Gold),
+        Produce::Aluminum => (TradeSymbol::AluminumOre, TradeSymbol::Aluminum),
+        Produce::Platinum => (TradeSymbol::PlatinumOre, TradeSymbol::Platinum),
+        Produce::Uranite => (TradeSymbol::UraniteOre, TradeSymbol::Uranite),
+        Produce::Meritium => (TradeSymbol::MeritiumOre, TradeSymbol::Meritium),
+        Produce::Fuel => (TradeSymbol::Hydrocarbon, TradeSymbol::Fuel),
+    }
+}
+
+/// Something a ship could refine out of what it's carrying.
+#[derive(Debug, Clone)]
+pub struct RefineOption {
+    pub produce: Produce,
+    pub input: TradeSymbol,
+    pub output: TradeSymbol,
+    /// Units of `input` in the hold
+    pub held: i32,
+    /// The most we've seen anyone pay for one run's worth of input, and of
+    /// output
+    pub input_value: Option<i64>,
+    pub output_value: Option<i64>,
+}
+
+impl RefineOption {
+    /// How many times the ship could refine this before running out.
+    pub fn runs(&self) -> i32 {
+        self.held / REFINE_INPUT
+    }
+
+    /// Whether the output sells for more than the ore would, going by
+    /// prices we've seen. Not knowing either price means no.
+    pub fn pays(&self) -> bool {
+        matches!(
+            (self.input_value, self.output_value),
+            (Some(input), Some(output)) if output > input
+        )
+    }
+}
+
+/// What `products` the ship could refine from its `cargo`, with what each is
+/// worth at `markets`.
+pub fn refine_options(
+    cargo: &[ShipCargoItem],
+    products: &[Produce],
+    markets: &[Market],
+) -> Vec<RefineOption> {
+    let prices = best_sell_prices(markets);
+    let value = |trade_symbol: TradeSymbol, units: i32| {
+        prices
+            .get(&trade_symbol.to_string())
+            .map(|price| i64::from(*price) * i64::from(units))
+    };
+    products
+        .iter()
+        .filter_map(|&produce| {
+            let (input, output) = refine_goods(produce);
+            let held = cargo
+                .iter()
+                .find(|item| item.symbol == input)
+                .map(|item| item.units)?;
+            Some(RefineOption {
+                produce,
+                input,
+                output,
+                held,
+                input_value: value(input, REFINE_INPUT),
+                output_value: value(output, REFINE_OUTPUT),
+            })
+        })
+        .collect()
+}
+
+/**
+ * What an extraction with the survey is likely to be worth. Each extraction
+ * turns up one of its deposits, so that's the average of their best sell
+ * prices among the markets we know. Nobody buying a deposit makes it worth
+ * nothing.
+ */
+pub fn survey_value(survey: &Survey, markets: &[Market]) -> f64 {
+    if survey.deposits.is_empty() {
+        return 0.0;
+    }
+    let best = best_sell_prices(markets);
+    let total: i32 = survey
+        .deposits
+        .iter()
+        .map(|d| best.get(&d.symbol).copied().unwrap_or(0))
+        .sum();
+    f64::from(total) / survey.deposits.len() as f64
+}
+
+/// Surveys with what they're worth, the most valuable first.
+pub fn rank_surveys(surveys: Vec<Survey>, markets: &[Market]) -> Vec<(Survey, f64)> {
+    let mut ranked: Vec<(Survey, f64)> = surveys
+        .into_iter()
+        .map(|s| {
+            let value = survey_value(&s, markets);
+            (s, value)
+        })
+        .collect();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    ranked
+}
+
+/// What the ship is working with when it starts the trade.
+pub struct TradeShip<'a> {
+    pub location: &'a str,
+    pub cargo_capacity: i32,
+    pub fuel_capacity: i32,
+    pub engine_speed: i32,
+}
+
+impl<'a> From<&'a Ship> for TradeShip<'a> {
+    fn from(ship: &'a Ship) -> Self {
+        TradeShip {
+            location: &ship.nav.waypoint_symbol,
+            cargo_capacity: ship.cargo.capacity,
+            fuel_capacity: ship.fuel.capacity,
+            engine_speed: ship.engine.speed,
+        }
+    }
+}
+
+/**
+ * Cheapest fuel we know of, in credits per unit of ship fuel. Ships refuel
+ * wherever they happen to be, but this is close enough to rank trades.
+ */
+fn fuel_price(markets: &[Market]) -> f64 {
+    let price = markets
+        .iter()
+        .filter_map(|m| m.trade_goods.as_ref())
+        .flatten()
+        .filter(|g| g.symbol == TradeSymbol::Fuel)
+        .map(|g| g.purchase_price)
+        .min()
+        .unwrap_or(FUEL_PRICE_GUESS);
+    price as f64 / FUEL_PER_MARKET_UNIT
+}
+
+/// Fuel and seconds for one cruise-speed leg, or `None` if the tank can't
+/// hold enough to make it.
+fn leg(ship: &TradeShip, from: &Waypoint, to: &Waypoint) -> Option<(i32, i64)> {
+    let distance = nav::distance(from, to);
+    let fuel = nav::fuel_cost(distance, ShipNavFlightMode::Cruise);
+    // Probes and the like have no tank and fly for free
+    if ship.fuel_capacity > 0 && fuel > ship.fuel_capacity {
+        return None;
+    }
+    let fuel = if ship.fuel_capacity > 0 { fuel } else { 0 };
+    let time = nav::travel_time(distance, ShipNavFlightMode::Cruise, ship.engine_speed);
+    Some((fuel, time))
+}
+
+/**
+ * Ranks every buy-here-sell-there pair among the markets we have prices for,
+ * best profit per hour first. Unprofitable trades are left out.
+ *
+ * Markets only quote a price for `trade_volume` units at a time, and every
+ * transaction after that moves the price against us. So a trade only counts
+ * the units we can move in one transaction at each end.
+ */
+pub fn best_trades(ship: TradeShip, waypoints: &[Waypoint], markets: &[Market]) -> Vec<TradeRoute> {
+    let Some(start) = waypoints.iter().find(|w| w.symbol == ship.location) else {
+        return vec![];
+    };
+    if ship.cargo_capacity == 0 {
+        return vec![];
+    }
+    let fuel_price = fuel_price(markets);
+
+    let priced: Vec<(&Waypoint, &Vec<MarketTradeGood>)> = markets
+        .iter()
+        .filter_map(|m| {
+            let waypoint = waypoints.iter().find(|w| w.symbol == m.symbol)?;
+            Some((waypoint, m.trade_goods.as_ref()?))
+        })
+        .collect();
+
+    let mut routes = vec![];
+    for (buy_waypoint, buy_goods) in priced.iter() {
+        let Some((fuel_there, time_there)) = leg(&ship, start, buy_waypoint) else {
+            continue;
+        };
+
+        for (sell_waypoint, sell_goods) in priced.iter() {
+            if buy_waypoint.symbol == sell_waypoint.symbol {
+                continue;
+            }
+            let Some((fuel_across, time_across)) = leg(&ship, buy_waypoint, sell_waypoint) else {
+                continue;
+            };
+
+            for buy in buy_goods.iter() {
+                let Some(sell) = sell_goods.iter().find(|g| g.symbol == buy.symbol) else {
+                    continue;
+                };
+                if sell.sell_price <= buy.purchase_price {
+                    continue;
+                }
+
+                let units = ship
+                    .cargo_capacity
+                    .min(buy.trade_volume)
+                    .min(sell.trade_volume);
+                let fuel_cost = ((fuel_there + fuel_across) as f64 * fuel_price).ceil() as i64;
+                let profit =
+                    units as i64 * (sell.sell_price - buy.purchase_price) as i64 - fuel_cost;
+                if profit <= 0 {
+                    continue;
+                }
+
+                routes.push(TradeRoute {
+                    trade_symbol: buy.symbol,
+                    buy_at: buy_waypoint.symbol.clone(),
+                    sell_at: sell_waypoint.symbol.clone(),
+                    units,
+                    purchase_price: buy.purchase_price,
+                    sell_price: sell.sell_price,
+                    profit,
+                    duration: time_there + time_across,
+                });
+            }
+        }
+    }
+
+    routes.sort_by(|a, b| {
+        b.profit_per_hour()
+            .partial_cmp(&a.profit_per_hour())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use spacedust::models::survey::Size;
+    use spacedust::models::SurveyDeposit;
+
+    use super::*;
+    use crate::test_util::{good, market, waypoint};
+
+    fn cargo(symbol: TradeSymbol, units: i32) -> ShipCargoItem {
+        ShipCargoItem::new(symbol, String::new(), String::new(), units)
+    }
+
+    fn survey(deposits: &[&str]) -> Survey {
+        Survey::new(
+            String::new(),
+            "X1-TEST-B".to_string(),
+            deposits
+                .iter()
+                .map(|d| SurveyDeposit::new(d.to_string()))
+                .collect(),
+            String::new(),
+            Size::Small,
+        )
+    }
+
+    /// Iron ore is cheap at A and dear at B, 100 units to the east.
+    fn iron_run() -> (Vec<Waypoint>, Vec<Market>) {
+        let waypoints = vec![waypoint("X1-TEST-A", 0), waypoint("X1-TEST-B", 100)];
+        let markets = vec![
+            market(
+                "X1-TEST-A",
+                vec![good(TradeSymbol::IronOre, 20, 10, 8)],
+                &[],
+            ),
+            market(
+                "X1-TEST-B",
+                vec![good(TradeSymbol::IronOre, 10, 35, 30)],
+                &[],
+            ),
+        ];
+        (waypoints, markets)
+    }
+
+    fn trade_ship(cargo_capacity: i32, fuel_capacity: i32) -> TradeShip<'static> {
+        TradeShip {
+            location: "X1-TEST-A",
+            cargo_capacity,
+            fuel_capacity,
+            engine_speed: 30,
+        }
+    }
+
+    #[test]
+    fn trades_only_what_both_markets_take_at_once() {
+        let (waypoints, markets) = iron_run();
+        let routes = best_trades(trade_ship(40, 0), &waypoints, &markets);
+
+        assert_eq!(routes.len(), 1);
+        let route = &routes[0];
+        assert_eq!(
+            (route.buy_at.as_str(), route.sell_at.as_str()),
+            ("X1-TEST-A", "X1-TEST-B")
+        );
+        assert_eq!(route.units, 10);
+        // Probes have no tank, so the fuel's free
+        assert_eq!(route.profit, 200);
+    }
+
+    #[test]
+    fn trades_pay_for_fuel() {
+        let (waypoints, markets) = iron_run();
+        let routes = best_trades(trade_ship(40, 200), &waypoints, &markets);
+        // 100 units of fuel at the guessed price
+        assert_eq!(routes[0].profit, 200 - 72);
+    }
+
+    #[test]
+    fn no_trades_out_of_range_or_without_a_hold() {
+        let (waypoints, markets) = iron_run();
+        assert!(best_trades(trade_ship(40, 50), &waypoints, &markets).is_empty());
+        assert!(best_trades(trade_ship(0, 0), &waypoints, &markets).is_empty());
+    }
+
+    #[test]
+    fn sales_go_a_trade_volume_at_a_time() {
+        let iron = good(TradeSymbol::IronOre, 20, 10, 15);
+        assert_eq!(next_sale(50, &iron, 10), Some(20));
+        assert_eq!(next_sale(5, &iron, 10), Some(5));
+        assert_eq!(next_sale(0, &iron, 10), None);
+
+        let no_volume = good(TradeSymbol::IronOre, 0, 10, 15);
+        assert_eq!(next_sale(50, &no_volume, 10), Some(1));
+    }
+
+    #[test]
+    fn sales_stop_below_the_floor() {
+        let iron = good(TradeSymbol::IronOre, 20, 10, 15);
+        assert_eq!(next_sale(50, &iron, 15), Some(20));
+        assert_eq!(next_sale(50, &iron, 16), None);
+    }
+
+    #[test]
+    fn surveys_are_worth_their_average_deposit() {
+        let markets = vec![
+            market(
+                "X1-TEST-A",
+                vec![good(TradeSymbol::IronOre, 20, 12, 10)],
+                &[],
+            ),
+            market(
+                "X1-TEST-B",
+                vec![good(TradeSymbol::IronOre, 20, 22, 20)],
+                &[],
+            ),
+        ];
+        // Nobody buys quartz sand, so it's worth nothing
+        let iron = survey(&["IRON_ORE", "IRON_ORE", "QUARTZ_SAND"]);
+        assert_eq!(survey_value(&iron, &markets), 40.0 / 3.0);
+        assert_eq!(survey_value(&survey(&[]), &markets), 0.0);
+
+        let ranked = rank_surveys(vec![survey(&["QUARTZ_SAND"]), iron], &markets);
+        assert_eq!(ranked[0].1, 40.0 / 3.0);
+        assert_eq!(ranked[1].1, 0.0);
+    }
+
+    #[test]
+    fn unknown_prices_count_for_a_credit() {
+        let market = market(
+            "X1-TEST-A",
+            vec![good(TradeSymbol::IronOre, 20, 25, 20)],
+            &[TradeSymbol::IceWater],
+        );
+        let hold = [
+            cargo(TradeSymbol::IronOre, 10),
+            cargo(TradeSymbol::IceWater, 5),
+            // Not bought here at all
+            cargo(TradeSymbol::QuartzSand, 7),
+        ];
+        assert_eq!(cargo_value(&hold, &market), 10 * 20 + 5);
+    }
+
+    #[test]
+    fn refine_options_for_held_ore() {
+        let markets = vec![market(
+            "X1-TEST-A",
+            vec![
+                good(TradeSymbol::IronOre, 20, 12, 10),
+                good(TradeSymbol::Iron, 20, 45, 40),
+                good(TradeSymbol::CopperOre, 20, 12, 10),
+                good(TradeSymbol::Copper, 20, 25, 20),
+            ],
+            &[],
+        )];
+        let hold = [
+            cargo(TradeSymbol::IronOre, 65),
+            cargo(TradeSymbol::CopperOre, 10),
+            cargo(TradeSymbol::Hydrocarbon, 30),
+        ];
+        let options = refine_options(
+            &hold,
+            &[Produce::Iron, Produce::Copper, Produce::Gold, Produce::Fuel],
+            &markets,
+        );
+        let produced: Vec<_> = options.iter().map(|o| o.produce).collect();
+        assert_eq!(
+            produced,
+            vec![Produce::Iron, Produce::Copper, Produce::Fuel]
+        );
+
+        let iron = &options[0];
+        assert_eq!(iron.runs(), 2);
+        assert_eq!(
+            (iron.input_value, iron.output_value),
+            (Some(300), Some(400))
+        );
+        assert!(iron.pays());
+
+        // 30 ore is worth more than the 10 copper it makes
+        let copper = &options[1];
+        assert_eq!(copper.runs(), 0);
+        assert!(!copper.pays());
+
+        // Never seen a price for either
+        let fuel = &options[2];
+        assert_eq!((fuel.input_value, fuel.output_value), (None, None));
+        assert!(!fuel.pays());
+    }
+}